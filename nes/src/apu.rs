@@ -0,0 +1,657 @@
+use super::*;
+
+const LENGTHS: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTIES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const TRIANGLE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Region specific periods, all in CPU cycles
+struct Tables {
+    noise: [u16; 16],
+    dmc: [u16; 16],
+    /// Frame counter steps of the 4 and 5 step sequences
+    frame: [[usize; 5]; 2],
+}
+
+const NTSC: Tables = Tables {
+    noise: [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068],
+    dmc: [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54],
+    frame: [[7457, 14913, 22371, 29829, 29830], [7457, 14913, 22371, 29829, 37281]],
+};
+
+const PAL: Tables = Tables {
+    noise: [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778],
+    dmc: [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50],
+    frame: [[8313, 16627, 24939, 33253, 33254], [8313, 16627, 24939, 33253, 41565]],
+};
+
+#[derive(Debug, Clone, Default)]
+struct Envelope {
+    start: bool,
+    divider: u8,
+    decay: u8,
+    /// Volume or envelope period
+    volume: u8,
+    constant: bool,
+    /// Also halts the length counter
+    looping: bool,
+}
+
+impl Envelope {
+    fn write(&mut self, data: u8) {
+        self.looping = data & 0x20 != 0;
+        self.constant = data & 0x10 != 0;
+        self.volume = data & 0x0f;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+
+            if self.decay != 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.constant { self.volume } else { self.decay }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Pulse {
+    /// Pulse 1 negates in ones' complement
+    ones_complement: bool,
+    envelope: Envelope,
+    duty: u8,
+    step: u8,
+    period: u16,
+    timer: u16,
+    length: u8,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+}
+
+impl Pulse {
+    fn target_period(&self) -> u16 {
+        let change = self.period >> self.sweep_shift;
+
+        if !self.sweep_negate {
+            self.period + change
+        } else if self.ones_complement {
+            self.period.saturating_sub(change + 1)
+        } else {
+            self.period.saturating_sub(change)
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.period < 8 || self.target_period() > 0x7ff
+    }
+
+    fn write(&mut self, reg: u16, data: u8, enabled: bool) {
+        match reg {
+            0 => {
+                self.duty = data >> 6;
+                self.envelope.write(data);
+            },
+            1 => {
+                self.sweep_enabled = data & 0x80 != 0;
+                self.sweep_period = (data >> 4) & 7;
+                self.sweep_negate = data & 0x08 != 0;
+                self.sweep_shift = data & 7;
+                self.sweep_reload = true;
+            },
+            2 => self.period = (self.period & 0x700) | data as u16,
+            _ => {
+                self.period = (self.period & 0xff) | ((data as u16 & 7) << 8);
+                self.step = 0;
+                self.envelope.start = true;
+
+                if enabled {
+                    self.length = LENGTHS[data as usize >> 3];
+                }
+            },
+        }
+    }
+
+    /// Every other CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        if self.length != 0 && !self.envelope.looping {
+            self.length -= 1;
+        }
+
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift != 0 && !self.muted() {
+            self.period = self.target_period();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length == 0 || self.muted() || DUTIES[self.duty as usize][self.step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Triangle {
+    control: bool,
+    linear_reload_value: u8,
+    linear: u8,
+    linear_reload: bool,
+    step: u8,
+    period: u16,
+    timer: u16,
+    length: u8,
+}
+
+impl Triangle {
+    fn write(&mut self, reg: u16, data: u8, enabled: bool) {
+        match reg {
+            0 => {
+                self.control = data & 0x80 != 0;
+                self.linear_reload_value = data & 0x7f;
+            },
+            2 => self.period = (self.period & 0x700) | data as u16,
+            3 => {
+                self.period = (self.period & 0xff) | ((data as u16 & 7) << 8);
+                self.linear_reload = true;
+
+                if enabled {
+                    self.length = LENGTHS[data as usize >> 3];
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+
+            if self.length != 0 && self.linear != 0 {
+                self.step = (self.step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        if self.linear_reload {
+            self.linear = self.linear_reload_value;
+        } else if self.linear != 0 {
+            self.linear -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        if self.length != 0 && !self.control {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        // ultrasonic periods would just pop, real hardware outputs them though
+        if self.period < 2 { 7 } else { TRIANGLE[self.step as usize] }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Noise {
+    envelope: Envelope,
+    mode: bool,
+    period: u16,
+    timer: u16,
+    shift: u16,
+    length: u8,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            envelope: Envelope::default(),
+            mode: false,
+            period: 4,
+            timer: 0,
+            shift: 1,
+            length: 0,
+        }
+    }
+}
+
+impl Noise {
+    fn write(&mut self, reg: u16, data: u8, enabled: bool, tables: &Tables) {
+        match reg {
+            0 => self.envelope.write(data),
+            2 => {
+                self.mode = data & 0x80 != 0;
+                // the table is in CPU cycles, the timer runs every other one
+                self.period = tables.noise[data as usize & 0x0f] / 2;
+            },
+            3 => {
+                self.envelope.start = true;
+
+                if enabled {
+                    self.length = LENGTHS[data as usize >> 3];
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period - 1;
+            let tap = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+            self.shift = (self.shift >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        if self.length != 0 && !self.envelope.looping {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length == 0 || self.shift & 1 != 0 { 0 } else { self.envelope.output() }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Dmc {
+    irq_enabled: bool,
+    irq: bool,
+    looping: bool,
+    period: u16,
+    timer: u16,
+    level: u8,
+
+    sample_addr: u16,
+    sample_len: u16,
+    addr: u16,
+    remaining: u16,
+    buffer: Option<u8>,
+
+    shift: u8,
+    bits: u8,
+    silent: bool,
+}
+
+impl Dmc {
+    fn write(&mut self, reg: u16, data: u8, tables: &Tables) {
+        match reg {
+            0 => {
+                self.irq_enabled = data & 0x80 != 0;
+                self.looping = data & 0x40 != 0;
+                self.period = tables.dmc[data as usize & 0x0f];
+
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            },
+            1 => self.level = data & 0x7f,
+            2 => self.sample_addr = 0xc000 | ((data as u16) << 6),
+            _ => self.sample_len = ((data as u16) << 4) | 1,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.addr = self.sample_addr;
+        self.remaining = self.sample_len;
+    }
+
+    /// Every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period;
+
+        if !self.silent {
+            if self.shift & 1 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+
+        self.shift >>= 1;
+
+        if self.bits != 0 {
+            self.bits -= 1;
+        }
+
+        if self.bits == 0 {
+            self.bits = 8;
+
+            match self.buffer.take() {
+                Some(b) => {
+                    self.shift = b;
+                    self.silent = false;
+                },
+                None => self.silent = true,
+            }
+        }
+    }
+
+    /// Address of the next sample byte if the reader wants one
+    fn fetch_addr(&self) -> Option<u16> {
+        (self.buffer.is_none() && self.remaining != 0).then_some(self.addr)
+    }
+
+    fn fill(&mut self, data: u8) {
+        self.buffer = Some(data);
+        self.addr = self.addr.checked_add(1).unwrap_or(0x8000);
+        self.remaining -= 1;
+
+        if self.remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+}
+
+/// 2A03 sound channels and frame counter
+#[derive(Debug, Clone)]
+pub struct Apu {
+    pulse: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+    /// $4015 channel enables
+    enabled: u8,
+
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    /// CPU cycles into the frame counter sequence
+    frame_cycle: usize,
+    odd_cycle: bool,
+
+    /// Mixed output, see [`Apu::sample_rate`]
+    pub samples: Vec<f32>,
+    /// Rate [`Apu::samples`] are produced at
+    pub sample_rate: u32,
+    sample_sum: f32,
+    sample_count: u32,
+    sample_clock: u64,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            pulse: [Pulse { ones_complement: true, ..Default::default() }, Pulse::default()],
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            dmc: Dmc::default(),
+            enabled: 0,
+
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            odd_cycle: false,
+
+            samples: Vec::new(),
+            sample_rate: 44100,
+            sample_sum: 0.0,
+            sample_count: 0,
+            sample_clock: 0,
+        }
+    }
+
+    /// Frame counter or DMC interrupt pending
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    /// Silence every channel like $4015 = 0 does
+    pub fn silence(&mut self) {
+        self.write_status(0);
+    }
+
+    /// RESET silences the channels and restarts the frame counter, its mode is kept
+    pub fn reset(&mut self) {
+        self.silence();
+        self.frame_irq = false;
+        self.frame_cycle = 0;
+        self.triangle.step = 0;
+        self.dmc.level &= 1;
+    }
+
+    /// Mixed output of all channels in 0..1, with the nonlinear DAC curve
+    pub fn output(&self) -> f32 {
+        let pulse = (self.pulse[0].output() + self.pulse[1].output()) as f32;
+        let pulse = if pulse == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse + 100.0) };
+
+        let tnd = self.triangle.output() as f32 / 8227.0 + self.noise.output() as f32 / 12241.0 + self.dmc.level as f32 / 22638.0;
+        let tnd = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+
+        pulse + tnd
+    }
+
+    fn write_status(&mut self, data: u8) {
+        self.enabled = data & 0x1f;
+
+        let [p1, p2] = &mut self.pulse;
+        for (i, length) in [&mut p1.length, &mut p2.length, &mut self.triangle.length, &mut self.noise.length].into_iter().enumerate() {
+            if data & (1 << i) == 0 {
+                *length = 0;
+            }
+        }
+
+        if data & 0x10 == 0 {
+            self.dmc.remaining = 0;
+        } else if self.dmc.remaining == 0 {
+            self.dmc.restart();
+        }
+
+        self.dmc.irq = false;
+    }
+
+    /// $4015 without the side effects of reading it, bit 5 isn't driven
+    pub fn peek_status(&self) -> u8 {
+        let lengths = [self.pulse[0].length, self.pulse[1].length, self.triangle.length, self.noise.length];
+
+        lengths.iter().enumerate().fold(0, |r, (i, &l)| r | ((l != 0) as u8) << i)
+            | ((self.dmc.remaining != 0) as u8) << 4
+            | (self.frame_irq as u8) << 6
+            | (self.dmc.irq as u8) << 7
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse[0].envelope.clock();
+        self.pulse[1].envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse[0].clock_half_frame();
+        self.pulse[1].clock_half_frame();
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+}
+
+impl Nes {
+    fn apu_tables(&self) -> &'static Tables {
+        match self.region {
+            // Dendy's CPU runs at NTSC-like speed, so it keeps the NTSC periods
+            Region::Ntsc | Region::Dendy => &NTSC,
+            Region::Pal => &PAL,
+        }
+    }
+
+    /// One CPU cycle of the APU
+    pub(crate) fn step_apu(&mut self) {
+        let tables = self.apu_tables();
+        let apu = &mut self.apu;
+
+        apu.triangle.clock_timer();
+        apu.dmc.clock_timer();
+
+        apu.odd_cycle ^= true;
+        if apu.odd_cycle {
+            apu.pulse[0].clock_timer();
+            apu.pulse[1].clock_timer();
+            apu.noise.clock_timer();
+        }
+
+        apu.frame_cycle += 1;
+        let steps = &tables.frame[apu.five_step as usize];
+
+        match steps.iter().position(|&s| s == apu.frame_cycle) {
+            Some(0 | 2) => apu.clock_quarter_frame(),
+            Some(1) => {
+                apu.clock_quarter_frame();
+                apu.clock_half_frame();
+            },
+            Some(3) if !apu.five_step => {
+                apu.clock_quarter_frame();
+                apu.clock_half_frame();
+                apu.frame_irq |= !apu.irq_inhibit;
+            },
+            Some(4) => {
+                if apu.five_step {
+                    apu.clock_quarter_frame();
+                    apu.clock_half_frame();
+                } else {
+                    apu.frame_irq |= !apu.irq_inhibit;
+                }
+
+                apu.frame_cycle = 0;
+            },
+            _ => {},
+        }
+
+        // box filter down to the output rate
+        apu.sample_sum += apu.output();
+        apu.sample_count += 1;
+        // counted in master clock ticks times the sample rate
+        apu.sample_clock += apu.sample_rate as u64 * self.region.cpu_divider();
+
+        let master_hz = self.region.master_hz();
+        if apu.sample_clock >= master_hz {
+            apu.sample_clock -= master_hz;
+            apu.samples.push(apu.sample_sum / apu.sample_count as f32);
+            apu.sample_sum = 0.0;
+            apu.sample_count = 0;
+        }
+
+        if let Some(addr) = self.apu.dmc.fetch_addr() {
+            self.dmc_fetch(addr);
+        }
+    }
+
+    /// DMC sample fetch, stalls the CPU
+    fn dmc_fetch(&mut self, addr: u16) {
+        self.drive_bus(addr);
+        self.apu.dmc.fill(self.data_bus);
+        self.elapse_cycles(4);
+    }
+
+    pub(crate) fn store_apu(&mut self, addr: u16, data: u8) {
+        let tables = self.apu_tables();
+        let apu = &mut self.apu;
+        let reg = addr & 3;
+
+        match addr {
+            0x4000..=0x4003 => apu.pulse[0].write(reg, data, apu.enabled & 1 != 0),
+            0x4004..=0x4007 => apu.pulse[1].write(reg, data, apu.enabled & 2 != 0),
+            0x4008..=0x400b => apu.triangle.write(reg, data, apu.enabled & 4 != 0),
+            0x400c..=0x400f => apu.noise.write(reg, data, apu.enabled & 8 != 0, tables),
+            0x4010..=0x4013 => apu.dmc.write(reg, data, tables),
+            0x4015 => apu.write_status(data),
+            0x4017 => {
+                apu.five_step = data & 0x80 != 0;
+                apu.irq_inhibit = data & 0x40 != 0;
+                apu.frame_cycle = 0;
+
+                if apu.irq_inhibit {
+                    apu.frame_irq = false;
+                }
+
+                if apu.five_step {
+                    apu.clock_quarter_frame();
+                    apu.clock_half_frame();
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// $4015, bit 5 comes from open bus
+    pub(crate) fn load_apu_status(&mut self) -> u8 {
+        let r = self.apu.peek_status();
+        self.apu.frame_irq = false;
+        r
+    }
+}
@@ -1,9 +1,54 @@
+/// Cartridge memories exposed to debuggers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Memory {
+    PrgRom,
+    PrgRam,
+    Chr,
+}
+
+/// Nothing drives the data bus at an address, reads of it return whatever was last on the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenBus;
+
+/// Saved cartridge state that doesn't fit the cartridge it's restored to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadState;
+
 pub trait Cartridge {
     /// Tries to load a `u8` from cartridge PRGR*M
-    fn load(&mut self, addr: u16) -> Result<u8, ()>;
-    fn store(&mut self, addr: u16, data: u8) -> Result<(), ()>;
+    fn load(&mut self, addr: u16) -> Result<u8, OpenBus>;
+    fn store(&mut self, addr: u16, data: u8) -> Result<(), OpenBus>;
+    /// Like [`Cartridge::load`] but must not have side effects, used by debuggers and tracing
+    fn peek(&self, addr: u16) -> Result<u8, OpenBus>;
+    /// Bits the cartridge drives when loading `addr`, the others read back as open bus
+    fn driven_bits(&self, _addr: u16) -> u8 { 0xff }
 
     /// Load a `u8` from video memory, return low byte of `addr` on open bus
     fn vmem_load(&mut self, ciram: &crate::ppu::CiRam, addr: u16) -> u8;
     fn vmem_store(&mut self, ciram: &mut crate::ppu::CiRam, addr: u16, data: u8);
+    /// Like [`Cartridge::vmem_load`] but must not have side effects, used by PPU viewers
+    fn vmem_peek(&self, ciram: &crate::ppu::CiRam, addr: u16) -> u8;
+
+    /// Which PRG bank is mapped at `addr`, for bank qualified breakpoints
+    fn prg_bank(&self, _addr: u16) -> Option<usize> { None }
+    /// Whether the cartridge is asserting the CPU IRQ line
+    fn irq(&self) -> bool { false }
+    /// Battery backed memory that outlives power off, empty without a battery
+    fn nvram(&self) -> &[u8] { &[] }
+    fn nvram_mut(&mut self) -> &mut [u8] { &mut [] }
+    /// Append mapper registers and RAM for [`crate::snapshot::Snapshot`]
+    fn save_state(&self, _out: &mut Vec<u8>) {}
+    /// Restore what [`Cartridge::save_state`] wrote, leaving everything as it was on `Err`
+    fn load_state(&mut self, _state: &[u8]) -> Result<(), BadState> { Ok(()) }
+    /// Called once per CPU cycle, for cycle based IRQ counters and such
+    fn cpu_clock(&mut self) {}
+    /// The console's reset button was pressed
+    fn reset(&mut self) {}
+
+    /// Raw contents of a cartridge memory for debuggers, empty if there is none
+    fn memory(&self, _mem: Memory) -> &[u8] { &[] }
+    fn memory_mut(&mut self, _mem: Memory) -> &mut [u8] { &mut [] }
+
+    /// Append mapper state (bank registers, IRQ counters...) to execution trace lines
+    fn trace_state(&self, _out: &mut String) {}
 }
@@ -0,0 +1,143 @@
+//! Game Genie, Pro Action Rocky and raw cheat codes, substituted into CPU bus reads
+
+use crate::cart::OpenBus;
+
+/// One substituted read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub addr: u16,
+    pub value: u8,
+    /// Only substitute while the byte underneath is this, so bank switched ROM elsewhere is
+    /// left alone
+    pub compare: Option<u8>,
+}
+
+impl Patch {
+    /// Game Genie (6 or 8 letters), Pro Action Rocky (8 hex digits) or raw `addr:val[:cmp]` in
+    /// hex
+    pub fn decode(code: &str) -> Result<Self, String> {
+        let code = code.trim();
+
+        if code.contains(':') {
+            return raw(code);
+        }
+
+        match code.len() {
+            6 | 8 if code.chars().all(|c| GG_LETTERS.contains(c.to_ascii_uppercase())) => Ok(game_genie(code)),
+            8 if code.chars().all(|c| c.is_ascii_hexdigit()) => Ok(pro_action_rocky(u32::from_str_radix(code, 16).unwrap())),
+            _ => Err(format!("`{code}` isn't a Game Genie, Pro Action Rocky or addr:val[:cmp] code")),
+        }
+    }
+
+    fn apply(&self, addr: u16, value: Result<u8, OpenBus>) -> Option<u8> {
+        let matches = match self.compare {
+            Some(compare) => value == Ok(compare),
+            None => true,
+        };
+        (addr == self.addr && matches).then_some(self.value)
+    }
+}
+
+const GG_LETTERS: &str = "APZLGITYEOXUKSVN";
+
+fn game_genie(code: &str) -> Patch {
+    let n: Vec<u16> = code.chars().map(|c| GG_LETTERS.find(c.to_ascii_uppercase()).unwrap() as u16).collect();
+
+    let addr = 0x8000 | (n[3] & 7) << 12 | (n[5] & 7) << 8 | (n[4] & 8) << 8
+        | (n[2] & 7) << 4 | (n[1] & 8) << 4 | (n[4] & 7) | (n[3] & 8);
+    // the 8 letter codes move the value's top bit to make room for the compare value
+    let last = if n.len() == 8 { n[7] } else { n[5] };
+    let value = (n[1] & 7) << 4 | (n[0] & 8) << 4 | (n[0] & 7) | (last & 8);
+    let compare = (n.len() == 8).then(|| ((n[7] & 7) << 4 | (n[6] & 8) << 4 | (n[6] & 7) | (n[5] & 8)) as u8);
+
+    Patch { addr, value: value as u8, compare }
+}
+
+/// Bit positions of the decrypted address, compare and value bits
+const PAR_BITS: [u32; 31] = [
+    3, 13, 14, 1, 6, 9, 5, 0, 12, 7, 2, 8, 10, 11, 4,
+    19, 21, 23, 22, 20, 17, 16, 18,
+    29, 31, 24, 26, 25, 30, 27, 28,
+];
+
+fn pro_action_rocky(code: u32) -> Patch {
+    let mut key: u32 = 0x7e5e_e93a;
+    // bit 0 isn't used
+    let mut code = code >> 1;
+    let mut out = 0;
+
+    for &bit in PAR_BITS.iter().rev() {
+        if ((key ^ code) >> 30) & 1 != 0 {
+            out |= 1 << bit;
+            key ^= 0x5c18_4b91;
+        }
+
+        code <<= 1;
+        key <<= 1;
+    }
+
+    Patch { addr: (out & 0x7fff) as u16 | 0x8000, value: (out >> 24) as u8, compare: Some((out >> 16) as u8) }
+}
+
+fn raw(code: &str) -> Result<Patch, String> {
+    let hex = |s: &str, max: u32| u32::from_str_radix(s.trim(), 16).ok().filter(|&v| v <= max).ok_or(format!("bad hex `{s}` in `{code}`"));
+    let parts: Vec<&str> = code.split(':').collect();
+
+    let [addr, value, ref compare @ ..] = parts[..] else { return Err(format!("`{code}` needs addr:val[:cmp]")) };
+    let compare = match compare {
+        [] => None,
+        [compare] => Some(hex(compare, 0xff)? as u8),
+        _ => return Err(format!("`{code}` has too many parts")),
+    };
+
+    Ok(Patch { addr: hex(addr, 0xffff)? as u16, value: hex(value, 0xff)? as u8, compare })
+}
+
+/// A code with a description and whether it's on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub patch: Patch,
+}
+
+impl Cheat {
+    pub fn new(code: &str, name: &str) -> Result<Self, String> {
+        let patch = Patch::decode(code)?;
+        Ok(Self { code: code.trim().to_ascii_uppercase(), name: name.trim().to_string(), enabled: true, patch })
+    }
+}
+
+/// `.cht` files, a cheat per line, `+` or `-` for on and off then the code and its name
+///
+/// ```text
+/// + SXIOPO Infinite lives
+/// - 0075:09 Start on world 8
+/// ```
+pub fn parse_cht(text: &str) -> Result<Vec<Cheat>, String> {
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let err = |e: String| format!("line {}: {e}", i + 1);
+            let line = line.trim();
+            let (enabled, rest) = match (line.strip_prefix('+'), line.strip_prefix('-')) {
+                (Some(rest), _) => (true, rest),
+                (_, Some(rest)) => (false, rest),
+                _ => return Err(err("cheats start with + or -".to_string())),
+            };
+            let rest = rest.trim_start();
+            let (code, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            Ok(Cheat { enabled, ..Cheat::new(code, name).map_err(err)? })
+        })
+        .collect()
+}
+
+pub fn write_cht(cheats: &[Cheat]) -> String {
+    cheats.iter().map(|c| format!("{} {} {}", if c.enabled { '+' } else { '-' }, c.code, c.name).trim_end().to_string() + "\n").collect()
+}
+
+/// What a read of `addr` returns with the patches applied, the last matching one wins
+pub(crate) fn patch(patches: &[Patch], addr: u16, value: Result<u8, OpenBus>) -> Result<u8, OpenBus> {
+    patches.iter().rev().find_map(|p| p.apply(addr, value)).map_or(value, Ok)
+}
@@ -47,9 +47,10 @@ macro_rules! set_val_nz {
 macro_rules! dcp {
     ($self: tt $name: ident) => {{
         let addr = $self.$name();
-        let m = $self.load(addr) - 1;
+        let m = $self.load(addr);
+        $self.store(addr, m);
+        let m = m - 1;
         $self.store(addr, m);
-        $self.elapse_cycles(1);
         $self.set_n($self.cpu.a - m);
         $self.set_z($self.cpu.a - m);
         $self.cpu.p &= 0xfe;
@@ -60,14 +61,15 @@ macro_rules! dcp {
 macro_rules! isc {
     ($self: tt $name: ident) => {{
         let addr = $self.$name();
-        let m = $self.load(addr) + 1;
+        let m = $self.load(addr);
+        $self.store(addr, m);
+        let m = m + 1;
         $self.store(addr, m);
-        $self.elapse_cycles(1);
 
         let res = $self.cpu.a as i8 as i16 - m as i8 as i16 - (1 - ($self.cpu.p & 1)) as i16;
         $self.cpu.p &= 0xbe;
         $self.cpu.p |= ((res as i8) < 0) as u8;
-        $self.cpu.p |= ((res > 127 || res < -128) as u8) << 6;
+        $self.cpu.p |= (!(-128..=127).contains(&res) as u8) << 6;
         $self.cpu.a = res as u8;
         $self.set_n($self.cpu.a);
         $self.set_z($self.cpu.a);
@@ -81,8 +83,8 @@ macro_rules! rla {
         let v = (m << 1) | ($self.cpu.p & 1);
         $self.cpu.p &= 0xfe;
         $self.cpu.p |= m >> 7;
+        $self.store(addr, m);
         $self.store(addr, v);
-        $self.elapse_cycles(1);
 
         set_val_nz!($self $self.cpu.a, &= v);
     }};
@@ -93,8 +95,8 @@ macro_rules! rra {
         let addr = $self.$name();
         let m = $self.load(addr);
         let v = ($self.cpu.p << 7) | (m >> 1);
+        $self.store(addr, m);
         $self.store(addr, v);
-        $self.elapse_cycles(1);
 
         let (a, c1) = $self.cpu.a.overflowing_add(v);
         let (res, c2) = a.overflowing_add(m & 1);
@@ -110,8 +112,8 @@ macro_rules! slo {
     ($self: tt $name: ident) => {{
         let addr = $self.$name();
         let m = $self.load(addr);
+        $self.store(addr, m);
         $self.store(addr, m << 1);
-        $self.elapse_cycles(1);
 
         $self.cpu.a |= m << 1;
         $self.set_n($self.cpu.a);
@@ -127,19 +129,33 @@ macro_rules! sre {
         let addr = $self.$name();
         let m = $self.load(addr);
         let v = m >> 1;
+        $self.store(addr, m);
         $self.store(addr, v);
-        $self.elapse_cycles(1);
 
         set_val_nz!($self $self.cpu.a, ^= v);
         $self.cpu.p |= m & 1;
     }};
 }
 
-impl Nes<'_> {
+impl Nes {
+    /// Add an index register to `base`. The CPU first reads from the address before the high byte
+    /// is fixed, which only costs a cycle on page crossings for reads but always happens for writes.
+    fn index(&mut self, base: u16, idx: u8, always: bool) -> u16 {
+        let addr = base + idx as u16;
+        let unfixed = (base & 0xff00) | (addr & 0xff);
+
+        if always || unfixed != addr {
+            self.load(unfixed);
+        }
+
+        addr
+    }
+
     // aka (zp, x)
     fn addr_of_indx_indr(&mut self) -> u16 {
-        let ind = self.fetch_pc() + self.cpu.x;
-        self.elapse_cycles(1);
+        let ptr = self.fetch_pc();
+        self.load(ptr as u16);
+        let ind = ptr + self.cpu.x;
         self.load(ind as u16) as u16 | (self.load((ind + 1) as u16) as u16) << 8
     }
 
@@ -153,54 +169,51 @@ impl Nes<'_> {
 
     fn addr_of_zp_x(&mut self) -> u16 {
         let off = self.fetch_pc();
-        self.elapse_cycles(1);
+        self.load(off as u16);
         (off + self.cpu.x) as u16
     }
 
     fn addr_of_zp_y(&mut self) -> u16 {
         let off = self.fetch_pc();
-        self.elapse_cycles(1);
+        self.load(off as u16);
         (off + self.cpu.y) as u16
     }
 
+    fn indr(&mut self) -> u16 {
+        let ind = self.fetch_pc();
+        self.load(ind as u16) as u16 | (self.load((ind + 1) as u16) as u16) << 8
+    }
+
     // aka (zp), y
     fn addr_of_indr_indx(&mut self) -> u16 {
-        let ind = self.fetch_pc();
-        let a = self.load(ind as u16) as u16 | (self.load((ind + 1) as u16) as u16) << 8;
-        self.elapse_cycles(((a as u8).overflowing_add(self.cpu.y).1) as usize);
-        a + self.cpu.y as u16
+        let base = self.indr();
+        self.index(base, self.cpu.y, false)
     }
 
     fn addr_of_abs_x(&mut self) -> u16 {
-        let off = self.fetch_u16();
-        self.elapse_cycles(((off as u8).overflowing_add(self.cpu.x).1) as usize);
-        off + self.cpu.x as u16
+        let base = self.fetch_u16();
+        self.index(base, self.cpu.x, false)
     }
 
     fn addr_of_abs_y(&mut self) -> u16 {
-        let off = self.fetch_u16();
-        self.elapse_cycles(((off as u8).overflowing_add(self.cpu.y).1) as usize);
-        off + self.cpu.y as u16
+        let base = self.fetch_u16();
+        self.index(base, self.cpu.y, false)
     }
 
     // aka (zp), y
     fn addr_of_indr_indx_store(&mut self) -> u16 {
-        let ind = self.fetch_pc();
-        let a = self.load(ind as u16) as u16 | (self.load((ind + 1) as u16) as u16) << 8;
-        self.elapse_cycles(1);
-        a + self.cpu.y as u16
+        let base = self.indr();
+        self.index(base, self.cpu.y, true)
     }
 
     fn addr_of_abs_x_store(&mut self) -> u16 {
-        let off = self.fetch_u16();
-        self.elapse_cycles(1);
-        off + self.cpu.x as u16
+        let base = self.fetch_u16();
+        self.index(base, self.cpu.x, true)
     }
 
     fn addr_of_abs_y_store(&mut self) -> u16 {
-        let off = self.fetch_u16();
-        self.elapse_cycles(1);
-        off + self.cpu.y as u16
+        let base = self.fetch_u16();
+        self.index(base, self.cpu.y, true)
     }
 
     fn set_n(&mut self, v: u8) {
@@ -223,6 +236,25 @@ impl Nes<'_> {
     }
 
     pub(crate) fn step_everything(&mut self) {
+        if self.debugger.is_some() && self.debug_instruction() {
+            // halted, no time passes
+            return;
+        }
+
+        if core::mem::take(&mut self.nmi) {
+            self.interrupt(0xfffa, false);
+            return;
+        }
+
+        if self.cpu.p & 0x04 == 0 && (self.cart.irq() || self.apu.irq()) {
+            self.interrupt(0xfffe, false);
+            return;
+        }
+
+        if self.tracer.is_some() {
+            self.trace();
+        }
+
         let inst = self.fetch_pc();
         let a = inst >> 5;
         let b = (inst >> 2) & 7;
@@ -235,10 +267,14 @@ impl Nes<'_> {
             (0..=3 | 6..=7, 5, 0) => { addr_mode!(load self addr_of_zp_x); },
             (0..=3 | 6..=7, 7, 0) => { addr_mode!(load self addr_of_abs_x); },
 
+            (0, 0, 0) => self.interrupt(0xfffe, true), // brk
             (1, 0, 0) => {
-                self.push_u16(self.cpu.pc + 1);
-                self.cpu.pc = self.addr_of_abs();
-                self.elapse_cycles(1);
+                let l = self.fetch_pc();
+                self.load(0x0100 + self.cpu.s as u16);
+                self.push_u16(self.cpu.pc);
+                let h = self.fetch_pc();
+                self.cpu.pc = ((h as u16) << 8) | (l as u16);
+                self.debug_call(debugger::Call::Jsr);
             },
             (1, 1, 0) => { // bit zp
                 let m = addr_mode!(load self addr_of_zp);
@@ -255,13 +291,13 @@ impl Nes<'_> {
 
             (0, 2, 0) => self.push(self.cpu.p | 0x10),
             (1, 2, 0) => {
+                self.load(0x0100 + self.cpu.s as u16);
                 self.cpu.p = (self.pop() & 0xef) | 0x20;
-                self.elapse_cycles(1);
             },
             (2, 2, 0) => self.push(self.cpu.a),
             (3, 2, 0) => {
+                self.load(0x0100 + self.cpu.s as u16);
                 set_val_nz!(self self.cpu.a, = self.pop());
-                self.elapse_cycles(1);
             },
             (4, 2, 0) => set_val_nz!(self self.cpu.y, -= 1),
             (5, 2, 0) => set_val_nz!(self self.cpu.y, = self.cpu.a),
@@ -295,19 +331,32 @@ impl Nes<'_> {
 
                 let inc = self.fetch_pc() as i8 as u16;
                 if bit == cond & 1 {
-                    self.elapse_cycles(1 + (self.cpu.pc >> 8 != (self.cpu.pc + inc) >> 8) as usize);
-                    self.cpu.pc += inc as i8 as u16;
+                    // reads the next opcode, then the target before its high byte is fixed
+                    let target = self.cpu.pc + inc;
+                    let unfixed = (self.cpu.pc & 0xff00) | (target & 0xff);
+                    self.load(self.cpu.pc);
+
+                    if unfixed != target {
+                        self.load(unfixed);
+                    }
+
+                    self.cpu.pc = target;
                 }
             },
 
             (2, 0, 0) => { // rti
-                self.cpu.p = self.pop() | 0x20;
+                self.fetch_pc(); // padding byte
+                self.load(0x0100 + self.cpu.s as u16);
+                self.cpu.p = (self.pop() & 0xef) | 0x20;
                 self.cpu.pc = self.pop_u16();
-                self.elapse_cycles(1);
+                self.debug_return();
             },
             (3, 0, 0) => {
-                self.cpu.pc = self.pop_u16() + 1;
-                self.elapse_cycles(2);
+                self.fetch_pc(); // padding byte
+                self.load(0x0100 + self.cpu.s as u16);
+                self.cpu.pc = self.pop_u16();
+                self.fetch_pc();
+                self.debug_return();
             },
 
             (4, 0, 0) => { self.fetch_pc(); },
@@ -381,7 +430,7 @@ impl Nes<'_> {
 
                         self.cpu.p &= 0xbe;
                         self.cpu.p |= (res as i8 >= 0) as u8;
-                        self.cpu.p |= ((res > 127 || res < -128) as u8) << 6;
+                        self.cpu.p |= (!(-128..=127).contains(&res) as u8) << 6;
                         self.cpu.a = res as u8;
                     },
                     _ => unreachable!(),
@@ -462,7 +511,7 @@ impl Nes<'_> {
                 self.set_z(v);
 
                 if let Some(addr) = addr {
-                    self.elapse_cycles(1);
+                    self.store(addr, m);
                     self.store(addr, v);
                 } else {
                     self.cpu.a = v;
@@ -493,50 +542,50 @@ impl Nes<'_> {
             (0, 0, 3) => slo!(self addr_of_indx_indr),
             (0, 1, 3) => slo!(self addr_of_zp),
             (0, 3, 3) => slo!(self addr_of_abs),
-            (0, 4, 3) => slo!(self addr_of_indr_indx),
+            (0, 4, 3) => slo!(self addr_of_indr_indx_store),
             (0, 5, 3) => slo!(self addr_of_zp_x),
-            (0, 6, 3) => slo!(self addr_of_abs_y),
-            (0, 7, 3) => slo!(self addr_of_abs_x),
+            (0, 6, 3) => slo!(self addr_of_abs_y_store),
+            (0, 7, 3) => slo!(self addr_of_abs_x_store),
 
             (1, 0, 3) => rla!(self addr_of_indx_indr),
             (1, 1, 3) => rla!(self addr_of_zp),
             (1, 3, 3) => rla!(self addr_of_abs),
-            (1, 4, 3) => rla!(self addr_of_indr_indx),
+            (1, 4, 3) => rla!(self addr_of_indr_indx_store),
             (1, 5, 3) => rla!(self addr_of_zp_x),
-            (1, 6, 3) => rla!(self addr_of_abs_y),
-            (1, 7, 3) => rla!(self addr_of_abs_x),
+            (1, 6, 3) => rla!(self addr_of_abs_y_store),
+            (1, 7, 3) => rla!(self addr_of_abs_x_store),
 
             (2, 0, 3) => sre!(self addr_of_indx_indr),
             (2, 1, 3) => sre!(self addr_of_zp),
             (2, 3, 3) => sre!(self addr_of_abs),
-            (2, 4, 3) => sre!(self addr_of_indr_indx),
+            (2, 4, 3) => sre!(self addr_of_indr_indx_store),
             (2, 5, 3) => sre!(self addr_of_zp_x),
-            (2, 6, 3) => sre!(self addr_of_abs_y),
-            (2, 7, 3) => sre!(self addr_of_abs_x),
+            (2, 6, 3) => sre!(self addr_of_abs_y_store),
+            (2, 7, 3) => sre!(self addr_of_abs_x_store),
 
             (3, 0, 3) => rra!(self addr_of_indx_indr),
             (3, 1, 3) => rra!(self addr_of_zp),
             (3, 3, 3) => rra!(self addr_of_abs),
-            (3, 4, 3) => rra!(self addr_of_indr_indx),
+            (3, 4, 3) => rra!(self addr_of_indr_indx_store),
             (3, 5, 3) => rra!(self addr_of_zp_x),
-            (3, 6, 3) => rra!(self addr_of_abs_y),
-            (3, 7, 3) => rra!(self addr_of_abs_x),
+            (3, 6, 3) => rra!(self addr_of_abs_y_store),
+            (3, 7, 3) => rra!(self addr_of_abs_x_store),
 
             (6, 0, 3) => dcp!(self addr_of_indx_indr),
             (6, 1, 3) => dcp!(self addr_of_zp),
             (6, 3, 3) => dcp!(self addr_of_abs),
-            (6, 4, 3) => dcp!(self addr_of_indr_indx),
+            (6, 4, 3) => dcp!(self addr_of_indr_indx_store),
             (6, 5, 3) => dcp!(self addr_of_zp_x),
-            (6, 6, 3) => dcp!(self addr_of_abs_y),
-            (6, 7, 3) => dcp!(self addr_of_abs_x),
+            (6, 6, 3) => dcp!(self addr_of_abs_y_store),
+            (6, 7, 3) => dcp!(self addr_of_abs_x_store),
 
             (7, 0, 3) => isc!(self addr_of_indx_indr),
             (7, 1, 3) => isc!(self addr_of_zp),
             (7, 3, 3) => isc!(self addr_of_abs),
-            (7, 4, 3) => isc!(self addr_of_indr_indx),
+            (7, 4, 3) => isc!(self addr_of_indr_indx_store),
             (7, 5, 3) => isc!(self addr_of_zp_x),
-            (7, 6, 3) => isc!(self addr_of_abs_y),
-            (7, 7, 3) => isc!(self addr_of_abs_x),
+            (7, 6, 3) => isc!(self addr_of_abs_y_store),
+            (7, 7, 3) => isc!(self addr_of_abs_x_store),
             _ => todo!("{inst:02x} {a} {b} {c}"),
         }
 
@@ -545,6 +594,29 @@ impl Nes<'_> {
         }
     }
 
+    /// Push `pc` and `p`, then jump through `vector`. Takes 7 cycles including the opcode fetch of
+    /// BRK.
+    fn interrupt(&mut self, vector: u16, brk: bool) {
+        if brk {
+            // padding byte
+            self.fetch_pc();
+        } else {
+            self.load(self.cpu.pc);
+            self.load(self.cpu.pc);
+        }
+
+        self.push_u16(self.cpu.pc);
+        self.push(self.cpu.p | 0x20 | ((brk as u8) << 4));
+        self.cpu.p |= 0x04;
+        self.cpu.pc = self.load_u16(vector);
+
+        self.debug_call(match (brk, vector) {
+            (true, _) => debugger::Call::Brk,
+            (_, 0xfffa) => debugger::Call::Nmi,
+            _ => debugger::Call::Irq,
+        });
+    }
+
     fn fetch_pc(&mut self) -> u8 {
         let ret = self.load(self.cpu.pc);
         self.cpu.pc += 1;
@@ -560,11 +632,20 @@ impl Nes<'_> {
     }
 
     fn push(&mut self, val: u8) {
+        if self.debugger.is_some() {
+            self.debug_push(self.cpu.s);
+        }
+
         self.store(0x0100 + self.cpu.s as u16, val);
         self.cpu.s -= 1;
     }
 
     fn push_u16(&mut self, val: u16) {
+        if self.debugger.is_some() {
+            self.debug_push(self.cpu.s);
+            self.debug_push(self.cpu.s - 1);
+        }
+
         self.store_u16(0x00ff + self.cpu.s as u16, val);
         self.cpu.s -= 2;
     }
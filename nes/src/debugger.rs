@@ -0,0 +1,316 @@
+use super::*;
+
+mod expr;
+
+pub use expr::Condition;
+
+/// Execution breakpoint, hits before the instruction at `addr` runs
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub addr: u16,
+    /// Only hit if [`cart::Cartridge::prg_bank`] reports this bank at `addr`
+    pub bank: Option<usize>,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    pub fn new(addr: u16) -> Self {
+        Self {
+            addr,
+            bank: None,
+            condition: None,
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Space {
+    /// CPU bus as seen by [`Nes::load`] and [`Nes::store`]
+    Cpu,
+    /// PPU bus as accessed through PPUDATA
+    Ppu,
+}
+
+/// Memory watchpoint, hits after the instruction that accessed `start..=end`
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub space: Space,
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl Watchpoint {
+    pub fn new(space: Space, start: u16, end: u16, read: bool, write: bool) -> Self {
+        Self {
+            space,
+            start,
+            end,
+            read,
+            write,
+            condition: None,
+            enabled: true,
+        }
+    }
+}
+
+/// Hits when the PPU reaches the given position, `None` matches everything
+#[derive(Debug, Clone)]
+pub struct PpuBreakpoint {
+    pub scanline: Option<usize>,
+    pub dot: Option<usize>,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl PpuBreakpoint {
+    pub fn new(scanline: Option<usize>, dot: Option<usize>) -> Self {
+        Self {
+            scanline,
+            dot,
+            condition: None,
+            enabled: true,
+        }
+    }
+}
+
+/// Why the CPU halted, indices point into the lists of [`Debugger`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Break {
+    Breakpoint(usize),
+    Watchpoint { index: usize, addr: u16, value: u8, write: bool },
+    Ppu(usize),
+    Nmi,
+    Irq,
+    Step,
+}
+
+/// What pushed a return address onto the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    /// The pushed address is one less than where RTS returns to
+    Jsr,
+    Brk,
+    Nmi,
+    Irq,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Into,
+    /// Break once the call depth is back to at most this
+    Over(isize),
+    /// Break once the call depth is below this
+    Out(isize),
+}
+
+/// Breakpoints and stepping. Set [`Nes::debugger`] to enable, while it is `None` none of the
+/// checks run.
+///
+/// Once something hits, the CPU halts at the next instruction boundary until [`Debugger::resume`]
+/// is called.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub ppu_breakpoints: Vec<PpuBreakpoint>,
+    pub break_on_nmi: bool,
+    pub break_on_irq: bool,
+
+    /// JSR and interrupt nesting depth
+    depth: isize,
+    /// Indexed by the stack slot holding the low byte of a pushed return address
+    calls: [Option<Call>; 256],
+    step: Option<Step>,
+    hit: Option<Break>,
+    /// Don't break again at the instruction we halted at once resumed
+    resume_pc: Option<u16>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            ppu_breakpoints: Vec::new(),
+            break_on_nmi: false,
+            break_on_irq: false,
+
+            depth: 0,
+            calls: [None; 256],
+            step: None,
+            hit: None,
+            resume_pc: None,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Halt before the next instruction, also useful as a pause button
+    pub fn step_into(&mut self) {
+        self.step = Some(Step::Into);
+    }
+
+    /// Halt before the next instruction, treating subroutine calls and interrupts as a single one
+    pub fn step_over(&mut self) {
+        self.step = Some(Step::Over(self.depth));
+    }
+
+    /// Halt after returning from the current subroutine or interrupt handler
+    pub fn step_out(&mut self) {
+        self.step = Some(Step::Out(self.depth));
+    }
+
+    /// Whether anything is set that can halt the CPU, without it the debugger can be removed
+    pub fn has_breakpoints(&self) -> bool {
+        !self.breakpoints.is_empty() || !self.watchpoints.is_empty() || !self.ppu_breakpoints.is_empty()
+            || self.break_on_nmi || self.break_on_irq || self.step.is_some() || self.hit.is_some()
+    }
+
+    pub fn hit(&self) -> Option<Break> {
+        self.hit
+    }
+
+    /// Continue execution, steps set before this are kept
+    pub fn resume(&mut self) -> Option<Break> {
+        self.hit.take()
+    }
+
+    pub fn depth(&self) -> isize {
+        self.depth
+    }
+
+    /// What pushed the return address whose low byte is at $0100 + `slot`, if anything
+    pub fn stack_call(&self, slot: u8) -> Option<Call> {
+        self.calls[slot as usize]
+    }
+}
+
+impl Nes {
+    /// Whether the debugger has halted the CPU
+    pub fn halted(&self) -> bool {
+        self.debugger.as_ref().is_some_and(|d| d.hit.is_some())
+    }
+
+    /// Checks breakpoints and steps at an instruction boundary, returns whether the CPU should halt
+    pub(crate) fn debug_instruction(&mut self) -> bool {
+        let Some(d) = &self.debugger else { return false };
+
+        if d.hit.is_some() {
+            return true;
+        }
+
+        let pc = self.cpu.pc;
+        if d.resume_pc == Some(pc) {
+            self.debugger.as_mut().unwrap().resume_pc = None;
+            return false;
+        }
+
+        let stepped = match d.step {
+            Some(Step::Into) => true,
+            Some(Step::Over(depth)) => d.depth <= depth,
+            Some(Step::Out(depth)) => d.depth < depth,
+            None => false,
+        };
+
+        let hit = if stepped {
+            Some(Break::Step)
+        } else {
+            d.breakpoints.iter().position(|b| {
+                b.enabled && b.addr == pc
+                    && b.bank.is_none_or(|bank| self.cart.prg_bank(pc) == Some(bank))
+                    && b.condition.as_ref().is_none_or(|c| c.eval(self, None))
+            }).map(Break::Breakpoint)
+        };
+
+        let d = self.debugger.as_mut().unwrap();
+        d.resume_pc = None;
+
+        if hit.is_some() {
+            if stepped {
+                d.step = None;
+            }
+
+            d.hit = hit;
+            d.resume_pc = Some(pc);
+        }
+
+        hit.is_some()
+    }
+
+    pub(crate) fn debug_access(&mut self, space: Space, addr: u16, value: u8, write: bool) {
+        let Some(d) = &self.debugger else { return };
+
+        if d.hit.is_some() || d.watchpoints.is_empty() {
+            return;
+        }
+
+        if let Some(index) = d.watchpoints.iter().position(|w| {
+            w.enabled && w.space == space
+                && if write { w.write } else { w.read }
+                && (w.start..=w.end).contains(&addr)
+                && w.condition.as_ref().is_none_or(|c| c.eval(self, Some((addr, value))))
+        }) {
+            self.debugger.as_mut().unwrap().hit = Some(Break::Watchpoint { index, addr, value, write });
+        }
+    }
+
+    pub(crate) fn debug_ppu(&mut self) {
+        let Some(d) = &self.debugger else { return };
+
+        if d.hit.is_some() || d.ppu_breakpoints.is_empty() {
+            return;
+        }
+
+        if let Some(i) = d.ppu_breakpoints.iter().position(|b| {
+            b.enabled
+                && b.scanline.is_none_or(|s| s == self.ppu.scanline)
+                && b.dot.is_none_or(|c| c == self.ppu.cycle)
+                && b.condition.as_ref().is_none_or(|c| c.eval(self, None))
+        }) {
+            self.debugger.as_mut().unwrap().hit = Some(Break::Ppu(i));
+        }
+    }
+
+    /// Called right after a return address (and for interrupts, `p`) got pushed
+    pub(crate) fn debug_call(&mut self, call: Call) {
+        let Some(d) = &mut self.debugger else { return };
+
+        d.depth += 1;
+        let slot = if call == Call::Jsr { self.cpu.s + 1 } else { self.cpu.s + 2 };
+        d.calls[slot as usize] = Some(call);
+
+        let brk = match call {
+            Call::Nmi => d.break_on_nmi,
+            Call::Irq => d.break_on_irq,
+            _ => false,
+        };
+
+        if brk && d.hit.is_none() {
+            d.hit = Some(if call == Call::Nmi { Break::Nmi } else { Break::Irq });
+        }
+    }
+
+    /// Called on RTS and RTI
+    pub(crate) fn debug_return(&mut self) {
+        if let Some(d) = &mut self.debugger {
+            d.depth -= 1;
+        }
+    }
+
+    /// Forget return address annotations of stack slots that get overwritten
+    pub(crate) fn debug_push(&mut self, slot: u8) {
+        if let Some(d) = &mut self.debugger {
+            d.calls[slot as usize] = None;
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::*;
+
+/// Parsed breakpoint condition, e.g. `a == $10 && [$0300] > 5 || {$fe} == $c000`
+///
+/// - numbers are decimal, or hex with a `$` or `0x` prefix
+/// - `a`, `x`, `y`, `s`, `p`, `pc` are CPU registers, `c`, `z`, `i`, `d`, `v`, `n` are flags
+/// - `scanline`, `dot` and `cycle` are the PPU position and CPU cycle count
+/// - `address` and `value` are the access that triggered a watchpoint
+/// - `[addr]` reads a byte and `{addr}` reads a little endian word from the CPU bus
+/// - operators and precedence follow C
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub text: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut p = Parser { chars: text.chars().peekable() };
+        let expr = p.expr(0)?;
+
+        p.skip_ws();
+        if let Some(c) = p.chars.peek() {
+            return Err(format!("unexpected `{c}`"));
+        }
+
+        Ok(Self { text: text.to_string(), expr })
+    }
+
+    /// `access` is the address and value of the memory access being checked, if any
+    pub fn eval(&self, nes: &Nes, access: Option<(u16, u8)>) -> bool {
+        self.expr.eval(nes, access) != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(i64),
+    Var(Var),
+    Byte(Box<Expr>),
+    Word(Box<Expr>),
+    Unary(char, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Var {
+    A, X, Y, S, P, Pc,
+    C, Z, I, D, V, N,
+    Scanline, Dot, Cycle,
+    Address, Value,
+}
+
+/// Binary operators from lowest to highest precedence
+const BINARY_OPS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Expr {
+    fn eval(&self, nes: &Nes, access: Option<(u16, u8)>) -> i64 {
+        match self {
+            Self::Num(n) => *n,
+            Self::Var(v) => {
+                let flag = |bit: u8| ((nes.cpu.p >> bit) & 1) as i64;
+
+                match v {
+                    Var::A => nes.cpu.a as i64,
+                    Var::X => nes.cpu.x as i64,
+                    Var::Y => nes.cpu.y as i64,
+                    Var::S => nes.cpu.s as i64,
+                    Var::P => nes.cpu.p as i64,
+                    Var::Pc => nes.cpu.pc as i64,
+                    Var::C => flag(0),
+                    Var::Z => flag(1),
+                    Var::I => flag(2),
+                    Var::D => flag(3),
+                    Var::V => flag(6),
+                    Var::N => flag(7),
+                    Var::Scanline => nes.ppu.scanline as i64,
+                    Var::Dot => nes.ppu.cycle as i64,
+                    Var::Cycle => nes.cycles() as i64,
+                    Var::Address => access.map_or(0, |a| a.0 as i64),
+                    Var::Value => access.map_or(0, |a| a.1 as i64),
+                }
+            },
+            Self::Byte(a) => nes.peek(a.eval(nes, access) as u16) as i64,
+            Self::Word(a) => {
+                let a = a.eval(nes, access) as u16;
+                ((nes.peek(a + 1) as i64) << 8) | nes.peek(a) as i64
+            },
+            Self::Unary(op, e) => {
+                let e = e.eval(nes, access);
+
+                match op {
+                    '-' => -e,
+                    '~' => !e,
+                    '!' => (e == 0) as i64,
+                    _ => unreachable!(),
+                }
+            },
+            Self::Binary(op, l, r) => {
+                let l = l.eval(nes, access);
+
+                // short circuit so conditions like `x < 8 && [$300 + x] == 0` are cheap
+                match *op {
+                    "&&" if l == 0 => return 0,
+                    "||" if l != 0 => return 1,
+                    _ => {},
+                }
+
+                let r = r.eval(nes, access);
+
+                match *op {
+                    "||" | "&&" => (r != 0) as i64,
+                    "|" => l | r,
+                    "^" => l ^ r,
+                    "&" => l & r,
+                    "==" => (l == r) as i64,
+                    "!=" => (l != r) as i64,
+                    "<=" => (l <= r) as i64,
+                    ">=" => (l >= r) as i64,
+                    "<" => (l < r) as i64,
+                    ">" => (l > r) as i64,
+                    "<<" => l.wrapping_shl(r as u32),
+                    ">>" => l.wrapping_shr(r as u32),
+                    "+" => l.wrapping_add(r),
+                    "-" => l.wrapping_sub(r),
+                    "*" => l.wrapping_mul(r),
+                    "/" => l.checked_div(r).unwrap_or(0),
+                    "%" => l.checked_rem(r).unwrap_or(0),
+                    _ => unreachable!(),
+                }
+            },
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip_ws();
+
+        let mut ahead = self.chars.clone();
+        if !op.chars().all(|c| ahead.next_if_eq(&c).is_some()) {
+            return false;
+        }
+
+        // don't mistake `||` for `|`, `<<` or `<=` for `<`...
+        if let (&[o], Some(&n)) = (op.as_bytes(), ahead.peek()) {
+            if n == o as char || (n == '=' && matches!(o, b'<' | b'>')) {
+                return false;
+            }
+        }
+
+        self.chars = ahead;
+        true
+    }
+
+    fn expr(&mut self, level: usize) -> Result<Expr, String> {
+        if level == BINARY_OPS.len() {
+            return self.unary();
+        }
+
+        let mut l = self.expr(level + 1)?;
+
+        'outer: loop {
+            for op in BINARY_OPS[level] {
+                if self.eat(op) {
+                    let r = self.expr(level + 1)?;
+                    l = Expr::Binary(op, Box::new(l), Box::new(r));
+                    continue 'outer;
+                }
+            }
+
+            return Ok(l);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.skip_ws();
+
+        if let Some(op) = self.chars.next_if(|c| matches!(c, '-' | '~' | '!')) {
+            return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_ws();
+
+        let close = |p: &mut Self, c: char| {
+            p.skip_ws();
+            p.chars.next_if_eq(&c).map(|_| ()).ok_or_else(|| format!("expected `{c}`"))
+        };
+
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let e = self.expr(0)?;
+                close(self, ')')?;
+                Ok(e)
+            },
+            Some('[') => {
+                self.chars.next();
+                let e = self.expr(0)?;
+                close(self, ']')?;
+                Ok(Expr::Byte(Box::new(e)))
+            },
+            Some('{') => {
+                self.chars.next();
+                let e = self.expr(0)?;
+                close(self, '}')?;
+                Ok(Expr::Word(Box::new(e)))
+            },
+            Some('$') => {
+                self.chars.next();
+                self.number(16)
+            },
+            Some(c @ '0'..='9') => {
+                let mut ahead = self.chars.clone();
+                ahead.next();
+
+                if c == '0' && ahead.next().is_some_and(|c| c == 'x' || c == 'X') {
+                    self.chars = ahead;
+                    self.number(16)
+                } else {
+                    self.number(10)
+                }
+            },
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c.to_ascii_lowercase());
+                }
+
+                Ok(Expr::Var(match name.as_str() {
+                    "a" => Var::A,
+                    "x" => Var::X,
+                    "y" => Var::Y,
+                    "s" | "sp" => Var::S,
+                    "p" => Var::P,
+                    "pc" => Var::Pc,
+                    "c" => Var::C,
+                    "z" => Var::Z,
+                    "i" => Var::I,
+                    "d" => Var::D,
+                    "v" => Var::V,
+                    "n" => Var::N,
+                    "scanline" => Var::Scanline,
+                    "dot" => Var::Dot,
+                    "cycle" => Var::Cycle,
+                    "address" => Var::Address,
+                    "value" => Var::Value,
+                    _ => return Err(format!("unknown variable `{name}`")),
+                }))
+            },
+            Some(c) => Err(format!("unexpected `{c}`")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> Result<Expr, String> {
+        let mut n = 0_i64;
+        let mut any = false;
+
+        while let Some(d) = self.chars.peek().and_then(|c| c.to_digit(radix)) {
+            self.chars.next();
+            n = n.wrapping_mul(radix as i64).wrapping_add(d as i64);
+            any = true;
+        }
+
+        if !any {
+            return Err("expected a number".to_string());
+        }
+
+        Ok(Expr::Num(n))
+    }
+}
@@ -0,0 +1,190 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    /// aka (zp, x)
+    IndexedIndirect,
+    /// aka (zp), y
+    IndirectIndexed,
+    Relative,
+}
+
+impl Mode {
+    /// Length of an instruction with this addressing mode in bytes, including the opcode
+    pub fn size(self) -> u16 {
+        match self {
+            Self::Implied | Self::Accumulator => 1,
+            Self::Absolute | Self::AbsoluteX | Self::AbsoluteY | Self::Indirect => 3,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+    pub illegal: bool,
+}
+
+macro_rules! opcodes {
+    ($($mn: ident $mode: ident $($ill: literal)?),* $(,)?) => {
+        [$(Opcode {
+            mnemonic: stringify!($mn),
+            mode: opcodes!(@mode $mode),
+            illegal: opcodes!(@ill $($ill)?),
+        }),*]
+    };
+    (@ill) => { false };
+    (@ill $ill: literal) => { true };
+    (@mode imp) => { Mode::Implied };
+    (@mode acc) => { Mode::Accumulator };
+    (@mode imm) => { Mode::Immediate };
+    (@mode zp) => { Mode::ZeroPage };
+    (@mode zpx) => { Mode::ZeroPageX };
+    (@mode zpy) => { Mode::ZeroPageY };
+    (@mode abs) => { Mode::Absolute };
+    (@mode abx) => { Mode::AbsoluteX };
+    (@mode aby) => { Mode::AbsoluteY };
+    (@mode ind) => { Mode::Indirect };
+    (@mode izx) => { Mode::IndexedIndirect };
+    (@mode izy) => { Mode::IndirectIndexed };
+    (@mode rel) => { Mode::Relative };
+}
+
+/// Every opcode, illegal ones are marked and named like nestest does
+pub const OPCODES: [Opcode; 256] = opcodes!(
+    // 0x00
+    BRK imp, ORA izx, JAM imp '*', SLO izx '*', NOP zp '*', ORA zp, ASL zp, SLO zp '*',
+    PHP imp, ORA imm, ASL acc, ANC imm '*', NOP abs '*', ORA abs, ASL abs, SLO abs '*',
+    // 0x10
+    BPL rel, ORA izy, JAM imp '*', SLO izy '*', NOP zpx '*', ORA zpx, ASL zpx, SLO zpx '*',
+    CLC imp, ORA aby, NOP imp '*', SLO aby '*', NOP abx '*', ORA abx, ASL abx, SLO abx '*',
+    // 0x20
+    JSR abs, AND izx, JAM imp '*', RLA izx '*', BIT zp, AND zp, ROL zp, RLA zp '*',
+    PLP imp, AND imm, ROL acc, ANC imm '*', BIT abs, AND abs, ROL abs, RLA abs '*',
+    // 0x30
+    BMI rel, AND izy, JAM imp '*', RLA izy '*', NOP zpx '*', AND zpx, ROL zpx, RLA zpx '*',
+    SEC imp, AND aby, NOP imp '*', RLA aby '*', NOP abx '*', AND abx, ROL abx, RLA abx '*',
+    // 0x40
+    RTI imp, EOR izx, JAM imp '*', SRE izx '*', NOP zp '*', EOR zp, LSR zp, SRE zp '*',
+    PHA imp, EOR imm, LSR acc, ALR imm '*', JMP abs, EOR abs, LSR abs, SRE abs '*',
+    // 0x50
+    BVC rel, EOR izy, JAM imp '*', SRE izy '*', NOP zpx '*', EOR zpx, LSR zpx, SRE zpx '*',
+    CLI imp, EOR aby, NOP imp '*', SRE aby '*', NOP abx '*', EOR abx, LSR abx, SRE abx '*',
+    // 0x60
+    RTS imp, ADC izx, JAM imp '*', RRA izx '*', NOP zp '*', ADC zp, ROR zp, RRA zp '*',
+    PLA imp, ADC imm, ROR acc, ARR imm '*', JMP ind, ADC abs, ROR abs, RRA abs '*',
+    // 0x70
+    BVS rel, ADC izy, JAM imp '*', RRA izy '*', NOP zpx '*', ADC zpx, ROR zpx, RRA zpx '*',
+    SEI imp, ADC aby, NOP imp '*', RRA aby '*', NOP abx '*', ADC abx, ROR abx, RRA abx '*',
+    // 0x80
+    NOP imm '*', STA izx, NOP imm '*', SAX izx '*', STY zp, STA zp, STX zp, SAX zp '*',
+    DEY imp, NOP imm '*', TXA imp, XAA imm '*', STY abs, STA abs, STX abs, SAX abs '*',
+    // 0x90
+    BCC rel, STA izy, JAM imp '*', AHX izy '*', STY zpx, STA zpx, STX zpy, SAX zpy '*',
+    TYA imp, STA aby, TXS imp, TAS aby '*', SHY abx '*', STA abx, SHX aby '*', AHX aby '*',
+    // 0xa0
+    LDY imm, LDA izx, LDX imm, LAX izx '*', LDY zp, LDA zp, LDX zp, LAX zp '*',
+    TAY imp, LDA imm, TAX imp, LAX imm '*', LDY abs, LDA abs, LDX abs, LAX abs '*',
+    // 0xb0
+    BCS rel, LDA izy, JAM imp '*', LAX izy '*', LDY zpx, LDA zpx, LDX zpy, LAX zpy '*',
+    CLV imp, LDA aby, TSX imp, LAS aby '*', LDY abx, LDA abx, LDX aby, LAX aby '*',
+    // 0xc0
+    CPY imm, CMP izx, NOP imm '*', DCP izx '*', CPY zp, CMP zp, DEC zp, DCP zp '*',
+    INY imp, CMP imm, DEX imp, AXS imm '*', CPY abs, CMP abs, DEC abs, DCP abs '*',
+    // 0xd0
+    BNE rel, CMP izy, JAM imp '*', DCP izy '*', NOP zpx '*', CMP zpx, DEC zpx, DCP zpx '*',
+    CLD imp, CMP aby, NOP imp '*', DCP aby '*', NOP abx '*', CMP abx, DEC abx, DCP abx '*',
+    // 0xe0
+    CPX imm, SBC izx, NOP imm '*', ISB izx '*', CPX zp, SBC zp, INC zp, ISB zp '*',
+    INX imp, SBC imm, NOP imp, SBC imm '*', CPX abs, SBC abs, INC abs, ISB abs '*',
+    // 0xf0
+    BEQ rel, SBC izy, JAM imp '*', ISB izy '*', NOP zpx '*', SBC zpx, INC zpx, ISB zpx '*',
+    SED imp, SBC aby, NOP imp '*', ISB aby '*', NOP abx '*', SBC abx, INC abx, ISB abx '*',
+);
+
+impl Nes {
+    /// Disassemble the instruction at `pc` without touching the bus. If `annotate` is set, the
+    /// effective address and the memory value it points to are appended like nestest does.
+    ///
+    /// Returns the text and the length of the instruction.
+    pub fn disassemble(&self, pc: u16, annotate: bool) -> (String, u16) {
+        let op = OPCODES[self.peek(pc) as usize];
+        let b1 = self.peek(pc + 1);
+        let w1 = ((self.peek(pc + 2) as u16) << 8) | b1 as u16;
+
+        let operand = match op.mode {
+            Mode::Implied => String::new(),
+            Mode::Accumulator => " A".to_string(),
+            Mode::Immediate => format!(" #${b1:02X}"),
+            Mode::ZeroPage => format!(" ${b1:02X}"),
+            Mode::ZeroPageX => format!(" ${b1:02X},X"),
+            Mode::ZeroPageY => format!(" ${b1:02X},Y"),
+            Mode::Absolute => format!(" ${w1:04X}"),
+            Mode::AbsoluteX => format!(" ${w1:04X},X"),
+            Mode::AbsoluteY => format!(" ${w1:04X},Y"),
+            Mode::Indirect => format!(" (${w1:04X})"),
+            Mode::IndexedIndirect => format!(" (${b1:02X},X)"),
+            Mode::IndirectIndexed => format!(" (${b1:02X}),Y"),
+            Mode::Relative => format!(" ${:04X}", pc + 2 + b1 as i8 as u16),
+        };
+
+        let mut s = format!("{}{}{operand}", if op.illegal { "*" } else { "" }, op.mnemonic);
+
+        if annotate {
+            let zp_u16 = |a: u8| ((self.peek((a + 1) as u16) as u16) << 8) | self.peek(a as u16) as u16;
+
+            match op.mode {
+                Mode::ZeroPage => s += &format!(" = {:02X}", self.peek(b1 as u16)),
+                Mode::ZeroPageX => {
+                    let a = b1 + self.cpu.x;
+                    s += &format!(" @ {a:02X} = {:02X}", self.peek(a as u16));
+                },
+                Mode::ZeroPageY => {
+                    let a = b1 + self.cpu.y;
+                    s += &format!(" @ {a:02X} = {:02X}", self.peek(a as u16));
+                },
+                Mode::Absolute if !matches!(op.mnemonic, "JMP" | "JSR") => {
+                    s += &format!(" = {:02X}", self.peek(w1));
+                },
+                Mode::AbsoluteX => {
+                    let a = w1 + self.cpu.x as u16;
+                    s += &format!(" @ {a:04X} = {:02X}", self.peek(a));
+                },
+                Mode::AbsoluteY => {
+                    let a = w1 + self.cpu.y as u16;
+                    s += &format!(" @ {a:04X} = {:02X}", self.peek(a));
+                },
+                Mode::Indirect => {
+                    let l = self.peek(w1);
+                    let h = self.peek((w1 & 0xff00) | ((w1 + 1) & 0xff));
+                    s += &format!(" = {:04X}", ((h as u16) << 8) | l as u16);
+                },
+                Mode::IndexedIndirect => {
+                    let ind = b1 + self.cpu.x;
+                    let a = zp_u16(ind);
+                    s += &format!(" @ {ind:02X} = {a:04X} = {:02X}", self.peek(a));
+                },
+                Mode::IndirectIndexed => {
+                    let base = zp_u16(b1);
+                    let a = base + self.cpu.y as u16;
+                    s += &format!(" = {base:04X} @ {a:04X} = {:02X}", self.peek(a));
+                },
+                _ => {},
+            }
+        }
+
+        (s, op.mode.size())
+    }
+}
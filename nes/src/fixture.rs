@@ -0,0 +1,37 @@
+//! Test ROMs, also for the frontend's tests with the `fixture` feature
+
+use crate::{cart, ppu::CiRam, Nes, Region};
+
+/// 32 KiB of PRG ROM at $8000, with the reset vector pointing at $c000
+pub struct RomCart(Vec<u8>);
+
+impl RomCart {
+    pub fn new(prg: &[u8]) -> Self {
+        let mut rom = vec![0xea; 0x8000];
+        rom[0x4000..0x4000 + prg.len()].copy_from_slice(prg);
+        rom[0x7ffc] = 0x00;
+        rom[0x7ffd] = 0xc0;
+        Self(rom)
+    }
+}
+
+impl cart::Cartridge for RomCart {
+    fn load(&mut self, addr: u16) -> Result<u8, cart::OpenBus> { self.peek(addr) }
+    fn store(&mut self, _addr: u16, _data: u8) -> Result<(), cart::OpenBus> { Err(cart::OpenBus) }
+
+    fn peek(&self, addr: u16) -> Result<u8, cart::OpenBus> {
+        if addr >= 0x8000 { Ok(self.0[addr as usize - 0x8000]) } else { Err(cart::OpenBus) }
+    }
+
+    fn vmem_load(&mut self, _ciram: &CiRam, addr: u16) -> u8 { addr as u8 }
+    fn vmem_store(&mut self, _ciram: &mut CiRam, _addr: u16, _data: u8) {}
+    fn vmem_peek(&self, _ciram: &CiRam, addr: u16) -> u8 { addr as u8 }
+}
+
+/// `jmp $c000`
+pub const IDLE: &[u8] = &[0x4c, 0x00, 0xc0];
+
+/// Console running `prg` from $c000
+pub fn rom_nes(prg: &[u8], region: Region) -> Nes {
+    Nes::new(Box::new(RomCart::new(prg)), None, region)
+}
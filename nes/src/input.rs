@@ -0,0 +1,269 @@
+//! The two controller ports, the Famicom expansion port and the devices that plug into them.
+//! Writes to $4016 reach every device, reads of $4016 and $4017 reach the device in port 1 and 2
+//! respectively and the expansion port device sees both.
+
+use super::*;
+
+pub mod autofire;
+pub mod joypad;
+pub mod keyboard;
+pub mod microphone;
+pub mod mouse;
+pub mod power_pad;
+pub mod vaus;
+pub mod zapper;
+
+/// What the host's keyboard, mouse and microphone are doing, each device picks what it uses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostInput {
+    /// Standard controller buttons of players 1-4, see [`joypad`]
+    pub buttons: [u8; 4],
+    /// Screen pixel the mouse points at, `None` when it's off the screen
+    pub pointer: Option<(usize, usize)>,
+    /// Mouse movement since the last update, in screen pixels
+    pub motion: (i32, i32),
+    /// Left mouse button
+    pub primary: bool,
+    /// Right mouse button
+    pub secondary: bool,
+    /// Power Pad buttons 1-12 in bits 0-11
+    pub power_pad: u16,
+    /// Family BASIC keyboard matrix, see [`keyboard::KEYS`]
+    pub keyboard: [u8; 18],
+    pub microphone: bool,
+}
+
+pub trait InputDevice: std::fmt::Debug {
+    fn device(&self) -> Device;
+    /// $4016 writes, OUT0 is the strobe in bit 0, the expansion port also gets OUT1-2 in bits 1-2
+    fn strobe(&mut self, out: u8);
+    /// D0-D4 of a $4016 (`reg` 0) or $4017 (1) read
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8;
+    /// [`InputDevice::read`] without shifting anything out
+    fn peek(&self, reg: usize, ppu: &ppu::Ppu) -> u8;
+    /// Take over the host's input, once a frame
+    fn update(&mut self, host: &HostInput);
+    fn box_clone(&self) -> Box<dyn InputDevice>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// Controller port 1 or 2, read through $4016 and $4017
+    Port(usize),
+    /// Famicom expansion port
+    Expansion,
+}
+
+/// Registry of every device there is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Unplugged,
+    Joypad,
+    /// NES Four Score or Satellite, players 3 and 4 on top of 1 and 2, takes both ports
+    FourScore,
+    /// Players 3 and 4 on Famicom expansion port controllers
+    FamicomPads,
+    Zapper,
+    /// Arkanoid paddle for the NES
+    VausNes,
+    /// Arkanoid paddle for the Famicom
+    VausFamicom,
+    PowerPad,
+    SnesMouse,
+    /// Family BASIC keyboard
+    Keyboard,
+    /// The microphone on the Famicom's second controller
+    Microphone,
+}
+
+impl Device {
+    pub const ALL: [Device; 11] = [
+        Device::Unplugged, Device::Joypad, Device::FourScore, Device::FamicomPads, Device::Zapper,
+        Device::VausNes, Device::VausFamicom, Device::PowerPad, Device::SnesMouse, Device::Keyboard,
+        Device::Microphone,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Device::Unplugged => "Nothing",
+            Device::Joypad => "Controller",
+            Device::FourScore => "Four Score",
+            Device::FamicomPads => "Famicom controllers 3 and 4",
+            Device::Zapper => "Zapper",
+            Device::VausNes => "Arkanoid Vaus",
+            Device::VausFamicom => "Arkanoid Vaus (Famicom)",
+            Device::PowerPad => "Power Pad",
+            Device::SnesMouse => "SNES mouse",
+            Device::Keyboard => "Family BASIC keyboard",
+            Device::Microphone => "Famicom microphone",
+        }
+    }
+
+    pub fn fits(self, slot: Slot) -> bool {
+        match self {
+            Device::Unplugged => true,
+            Device::FamicomPads | Device::VausFamicom | Device::Keyboard | Device::Microphone => slot == Slot::Expansion,
+            _ => slot != Slot::Expansion,
+        }
+    }
+
+    pub fn create(self, slot: Slot) -> Box<dyn InputDevice> {
+        let port = match slot {
+            Slot::Port(port) => port,
+            Slot::Expansion => 0,
+        };
+
+        match self {
+            Device::Unplugged => Box::new(Unplugged),
+            Device::Joypad => Box::new(joypad::Joypad::new(port)),
+            Device::FourScore => Box::new(joypad::FourScore::new(port)),
+            Device::FamicomPads => Box::new(joypad::FamicomPads::default()),
+            Device::Zapper => Box::new(zapper::Zapper::default()),
+            Device::VausNes => Box::new(vaus::Vaus::new(false)),
+            Device::VausFamicom => Box::new(vaus::Vaus::new(true)),
+            Device::PowerPad => Box::new(power_pad::PowerPad::default()),
+            Device::SnesMouse => Box::new(mouse::SnesMouse::default()),
+            Device::Keyboard => Box::new(keyboard::Keyboard::default()),
+            Device::Microphone => Box::new(microphone::Microphone::default()),
+        }
+    }
+
+    /// Port 1, port 2 and expansion port devices for a NES 2.0 default expansion device
+    pub fn from_nes2(id: u8) -> [Device; 3] {
+        use Device::*;
+
+        match id {
+            0x02 => [FourScore, FourScore, Unplugged],
+            0x03 => [Joypad, Joypad, FamicomPads],
+            0x08 => [Joypad, Zapper, Unplugged],
+            0x09 => [Zapper, Zapper, Unplugged],
+            0x0b | 0x0c => [Joypad, PowerPad, Unplugged],
+            0x0f => [Joypad, VausNes, Unplugged],
+            0x10 => [Joypad, Joypad, VausFamicom],
+            0x23 => [Joypad, Joypad, Keyboard],
+            0x29 => [Joypad, SnesMouse, Unplugged],
+            _ => [Joypad, Joypad, Unplugged],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Unplugged;
+
+impl InputDevice for Unplugged {
+    fn device(&self) -> Device {
+        Device::Unplugged
+    }
+
+    fn strobe(&mut self, _out: u8) {}
+
+    fn read(&mut self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        0
+    }
+
+    fn peek(&self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        0
+    }
+
+    fn update(&mut self, _host: &HostInput) {}
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
+
+/// Everything plugged into the console
+#[derive(Debug)]
+pub struct Input {
+    pub ports: [Box<dyn InputDevice>; 2],
+    pub expansion: Box<dyn InputDevice>,
+    /// What was last given to [`Input::update`]
+    pub host: HostInput,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            ports: [Device::Joypad.create(Slot::Port(0)), Device::Joypad.create(Slot::Port(1))],
+            expansion: Device::Unplugged.create(Slot::Expansion),
+            host: HostInput::default(),
+        }
+    }
+}
+
+impl Clone for Input {
+    fn clone(&self) -> Self {
+        Self {
+            ports: [self.ports[0].box_clone(), self.ports[1].box_clone()],
+            expansion: self.expansion.box_clone(),
+            host: self.host.clone(),
+        }
+    }
+}
+
+impl Input {
+    /// Port 1, port 2 and expansion port devices
+    pub fn devices(&self) -> [Device; 3] {
+        [self.ports[0].device(), self.ports[1].device(), self.expansion.device()]
+    }
+
+    /// Replace what's in `slot`. The Four Score takes both ports, plugging something else in
+    /// either one puts a controller in the other.
+    pub fn plug(&mut self, slot: Slot, device: Device) {
+        match slot {
+            Slot::Port(port) => {
+                if device == Device::FourScore {
+                    self.ports = [0, 1].map(|p| Device::FourScore.create(Slot::Port(p)));
+                } else {
+                    if self.ports[port].device() == Device::FourScore {
+                        self.ports[port ^ 1] = Device::Joypad.create(Slot::Port(port ^ 1));
+                    }
+
+                    self.ports[port] = device.create(slot);
+                }
+            },
+            Slot::Expansion => self.expansion = device.create(slot),
+        }
+
+        let host = self.host.clone();
+        self.update(host);
+    }
+
+    pub fn update(&mut self, host: HostInput) {
+        for device in self.ports.iter_mut().chain([&mut self.expansion]) {
+            device.update(&host);
+        }
+
+        self.host = host;
+    }
+
+    fn strobe(&mut self, out: u8) {
+        self.ports[0].strobe(out & 1);
+        self.ports[1].strobe(out & 1);
+        self.expansion.strobe(out & 7);
+    }
+
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.ports[reg].read(reg, ppu) | self.expansion.read(reg, ppu)
+    }
+
+    fn peek(&self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.ports[reg].peek(reg, ppu) | self.expansion.peek(reg, ppu)
+    }
+}
+
+impl Nes {
+    /// $4016 writes
+    pub(crate) fn store_input(&mut self, data: u8) {
+        self.input.strobe(data);
+    }
+
+    /// $4016 and $4017, only D0-D4 are connected
+    pub(crate) fn load_input(&mut self, reg: usize) -> u8 {
+        self.input.read(reg, &self.ppu) & 0x1f
+    }
+
+    pub(crate) fn peek_input(&self, reg: usize) -> u8 {
+        self.input.peek(reg, &self.ppu) & 0x1f
+    }
+}
@@ -0,0 +1,196 @@
+//! Turbo buttons and input macros, applied to the controllers' buttons once per emulated frame
+//! before the console sees them, so movies record what they pressed
+
+use super::joypad;
+
+/// Frames a button is pressed (`x`) and released (`.`), repeated while it's held
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(pub Vec<bool>);
+
+impl Default for Pattern {
+    /// Every other frame, the fastest there is
+    fn default() -> Self {
+        Self::every(2)
+    }
+}
+
+impl Pattern {
+    /// Pressed on one frame out of `frames`
+    pub fn every(frames: usize) -> Self {
+        Self((0..frames.max(1)).map(|i| i == 0).collect())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let frames = text.trim().chars().map(|c| match c {
+            'x' | 'X' => Ok(true),
+            '.' => Ok(false),
+            _ => Err(format!("`{c}` in a pattern, only x and . are frames")),
+        });
+        let frames: Vec<bool> = frames.collect::<Result<_, _>>()?;
+
+        if frames.is_empty() {
+            return Err("empty pattern".to_string());
+        }
+
+        Ok(Self(frames))
+    }
+
+    pub fn write(&self) -> String {
+        self.0.iter().map(|&on| if on { 'x' } else { '.' }).collect()
+    }
+
+    fn pressed(&self, frame: usize) -> bool {
+        self.0[frame % self.0.len()]
+    }
+}
+
+/// Buttons held for a number of frames, one after the other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<(u8, usize)>,
+    /// Start over after the last step until stopped
+    pub repeat: bool,
+}
+
+impl Macro {
+    /// Steps separated by commas, each `+` separated button names or `-` for none, optionally
+    /// followed by `x<frames>`. A trailing `...` repeats it.
+    ///
+    /// ```text
+    /// Right+A, Right x2, ...
+    /// ```
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        let mut repeat = false;
+
+        for step in text.split(',').map(str::trim) {
+            if repeat {
+                return Err("`...` has to be last".to_string());
+            }
+
+            if step == "..." {
+                repeat = true;
+                continue;
+            }
+
+            let (buttons, frames) = match step.rsplit_once(" x") {
+                Some((buttons, n)) => (buttons, n.trim().parse().map_err(|_| format!("bad frame count in `{step}`"))?),
+                None => (step, 1),
+            };
+
+            if frames == 0 {
+                return Err(format!("`{step}` lasts no frames"));
+            }
+
+            steps.push((parse_buttons(buttons)?, frames));
+        }
+
+        if steps.is_empty() {
+            return Err("a macro needs a step".to_string());
+        }
+
+        Ok(Self { name: name.to_string(), steps, repeat })
+    }
+
+    pub fn write(&self) -> String {
+        let mut steps: Vec<String> = self.steps.iter().map(|&(buttons, frames)| {
+            let buttons = write_buttons(buttons);
+            if frames == 1 { buttons } else { format!("{buttons} x{frames}") }
+        }).collect();
+
+        if self.repeat {
+            steps.push("...".to_string());
+        }
+
+        steps.join(", ")
+    }
+
+    pub fn frames(&self) -> usize {
+        self.steps.iter().map(|&(_, frames)| frames).sum()
+    }
+
+    fn buttons(&self, frame: usize) -> Option<u8> {
+        let frame = if self.repeat { frame % self.frames() } else { frame };
+        let mut end = 0;
+
+        self.steps.iter().find(|&&(_, frames)| {
+            end += frames;
+            frame < end
+        }).map(|&(buttons, _)| buttons)
+    }
+}
+
+fn parse_buttons(text: &str) -> Result<u8, String> {
+    if text.trim() == "-" {
+        return Ok(0);
+    }
+
+    text.split('+').map(str::trim).try_fold(0, |acc, name| {
+        let (_, bit) = joypad::NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).ok_or(format!("unknown button `{name}`"))?;
+        Ok(acc | bit)
+    })
+}
+
+fn write_buttons(buttons: u8) -> String {
+    match buttons {
+        0 => "-".to_string(),
+        _ => joypad::NAMES.iter().filter(|(_, bit)| buttons & bit != 0).map(|(name, _)| *name).collect::<Vec<_>>().join("+"),
+    }
+}
+
+/// Turbo and running macros of one controller
+#[derive(Debug, Clone, Default)]
+pub struct Player {
+    pub turbo_a: Pattern,
+    pub turbo_b: Pattern,
+    /// Macro being played and the frame it's on
+    pub running: Option<(Macro, usize)>,
+    /// Frames the turbo A and B buttons have been held
+    held: [usize; 2],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Autofire {
+    pub players: [Player; 4],
+}
+
+impl Autofire {
+    pub fn start(&mut self, player: usize, m: Macro) {
+        self.players[player].running = Some((m, 0));
+    }
+
+    pub fn stop(&mut self, player: usize) {
+        self.players[player].running = None;
+    }
+
+    /// Add turbo and macro buttons to one frame's `buttons`. `turbo` are the buttons held
+    /// on turbo, only A and B count.
+    pub fn apply(&mut self, buttons: &mut [u8; 4], turbo: [u8; 4]) {
+        for ((player, buttons), turbo) in self.players.iter_mut().zip(buttons).zip(turbo) {
+            for (i, (bit, pattern)) in [(joypad::A, &player.turbo_a), (joypad::B, &player.turbo_b)].into_iter().enumerate() {
+                if turbo & bit == 0 {
+                    player.held[i] = 0;
+                    continue;
+                }
+
+                // the first frame is the pattern's first, so turbo responds right away
+                if pattern.pressed(player.held[i]) {
+                    *buttons |= bit;
+                }
+
+                player.held[i] += 1;
+            }
+
+            if let Some((m, frame)) = &mut player.running {
+                match m.buttons(*frame) {
+                    Some(held) => {
+                        *buttons |= held;
+                        *frame += 1;
+                    },
+                    None => player.running = None,
+                }
+            }
+        }
+    }
+}
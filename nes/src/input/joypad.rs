@@ -0,0 +1,183 @@
+use super::*;
+
+// button bits, in the order the controller shifts them out
+pub const A: u8 = 0x01;
+pub const B: u8 = 0x02;
+pub const SELECT: u8 = 0x04;
+pub const START: u8 = 0x08;
+pub const UP: u8 = 0x10;
+pub const DOWN: u8 = 0x20;
+pub const LEFT: u8 = 0x40;
+pub const RIGHT: u8 = 0x80;
+
+pub const NAMES: [(&str, u8); 8] = [
+    ("A", A), ("B", B), ("Select", SELECT), ("Start", START),
+    ("Up", UP), ("Down", DOWN), ("Left", LEFT), ("Right", RIGHT),
+];
+
+/// A parallel-in serial-out shift register latching `input` while strobed, like the 4021 in
+/// every controller. Shifts in 1s once the latched bits run out.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Shifter {
+    pub input: u32,
+    strobe: bool,
+    shift: u32,
+    /// Bits latched, 8 for a controller
+    width: u32,
+}
+
+impl Shifter {
+    pub fn new(width: u32) -> Self {
+        Self { width, ..Default::default() }
+    }
+
+    pub fn strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+
+        if strobe {
+            self.shift = self.input;
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            self.shift = self.input;
+        }
+
+        let bit = self.peek();
+        self.shift = (self.shift >> 1) | 1 << (self.width - 1);
+        bit
+    }
+
+    pub fn peek(&self) -> u8 {
+        if self.strobe { (self.input & 1) as u8 } else { (self.shift & 1) as u8 }
+    }
+}
+
+/// Standard controller on D0
+#[derive(Debug, Clone, Copy)]
+pub struct Joypad {
+    /// Index into [`HostInput::buttons`]
+    pub player: usize,
+    shifter: Shifter,
+}
+
+impl Joypad {
+    pub fn new(player: usize) -> Self {
+        Self { player, shifter: Shifter::new(8) }
+    }
+
+    /// Buttons held right now
+    pub fn buttons(&self) -> u8 {
+        self.shifter.input as u8
+    }
+}
+
+impl InputDevice for Joypad {
+    fn device(&self) -> Device {
+        Device::Joypad
+    }
+
+    fn strobe(&mut self, out: u8) {
+        self.shifter.strobe(out & 1 != 0);
+    }
+
+    fn read(&mut self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.read()
+    }
+
+    fn peek(&self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.peek()
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.shifter.input = host.buttons[self.player] as u32;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
+
+/// Four Score half on one port, players 1 and 3 or 2 and 4 followed by a signature telling
+/// games it's there
+#[derive(Debug, Clone, Copy)]
+pub struct FourScore {
+    port: usize,
+    shifter: Shifter,
+}
+
+impl FourScore {
+    pub fn new(port: usize) -> Self {
+        Self { port, shifter: Shifter::new(24) }
+    }
+}
+
+impl InputDevice for FourScore {
+    fn device(&self) -> Device {
+        Device::FourScore
+    }
+
+    fn strobe(&mut self, out: u8) {
+        self.shifter.strobe(out & 1 != 0);
+    }
+
+    fn read(&mut self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.read()
+    }
+
+    fn peek(&self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.peek()
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        let signature: u32 = if self.port == 0 { 0x08 } else { 0x04 };
+        self.shifter.input = host.buttons[self.port] as u32 | (host.buttons[self.port + 2] as u32) << 8 | signature << 16;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
+
+/// Players 3 and 4 on controllers plugged into the Famicom's expansion port, read on D1
+#[derive(Debug, Clone, Copy)]
+pub struct FamicomPads {
+    pads: [Joypad; 2],
+}
+
+impl Default for FamicomPads {
+    fn default() -> Self {
+        Self { pads: [Joypad::new(2), Joypad::new(3)] }
+    }
+}
+
+impl InputDevice for FamicomPads {
+    fn device(&self) -> Device {
+        Device::FamicomPads
+    }
+
+    fn strobe(&mut self, out: u8) {
+        for pad in &mut self.pads {
+            pad.strobe(out);
+        }
+    }
+
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.pads[reg].read(reg, ppu) << 1
+    }
+
+    fn peek(&self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.pads[reg].peek(reg, ppu) << 1
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        for pad in &mut self.pads {
+            pad.update(host);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+/// Key names by row, the first four are column 0 and the rest column 1, each column's keys in
+/// D1-D4 order
+pub const KEYS: [[&str; 8]; 9] = [
+    ["]", "[", "RETURN", "F8", "STOP", "¥", "RSHIFT", "KANA"],
+    [";", ":", "@", "F7", "^", "-", "/", "_"],
+    ["K", "L", "O", "F6", "0", "P", ",", "."],
+    ["J", "U", "I", "F5", "8", "9", "N", "M"],
+    ["H", "G", "Y", "F4", "6", "7", "V", "B"],
+    ["D", "R", "T", "F3", "4", "5", "C", "F"],
+    ["A", "S", "W", "F2", "3", "E", "Z", "X"],
+    ["CTR", "Q", "ESC", "F1", "2", "1", "GRPH", "LSHIFT"],
+    ["LEFT", "RIGHT", "UP", "CLR", "INS", "DEL", "SPACE", "DOWN"],
+];
+
+/// Where a key from [`KEYS`] is in [`HostInput::keyboard`], the index and bit
+pub fn key_position(name: &str) -> Option<(usize, u8)> {
+    let (row, keys) = KEYS.iter().enumerate().find(|(_, keys)| keys.contains(&name))?;
+    let i = keys.iter().position(|&k| k == name)?;
+    Some((row * 2 + i / 4, 1 << (i % 4)))
+}
+
+/// Family BASIC keyboard on the expansion port, its matrix is scanned through $4016 writes
+/// and read on $4017 D1-D4
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Keyboard {
+    /// Keys held, 4 bits for each row and column
+    pub keys: [u8; 18],
+    row: usize,
+    column: usize,
+    enabled: bool,
+}
+
+impl InputDevice for Keyboard {
+    fn device(&self) -> Device {
+        Device::Keyboard
+    }
+
+    /// Bit 0 goes back to the first row, bit 1 picks the column and going from 1 to 0 moves to
+    /// the next row, bit 2 enables the keyboard
+    fn strobe(&mut self, out: u8) {
+        let column = (out >> 1 & 1) as usize;
+
+        if out & 1 != 0 {
+            self.row = 0;
+        } else if self.column == 1 && column == 0 {
+            self.row += 1;
+        }
+
+        self.column = column;
+        self.enabled = out & 4 != 0;
+    }
+
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.peek(reg, ppu)
+    }
+
+    /// Held keys read 0
+    fn peek(&self, reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        match (reg, self.enabled && self.row < KEYS.len()) {
+            (1, true) => (!self.keys[self.row * 2 + self.column] & 0xf) << 1,
+            (1, false) => 0x1e,
+            _ => 0,
+        }
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.keys = host.keyboard;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,32 @@
+use super::*;
+
+/// The microphone on the Famicom's second controller, on $4016 D2
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Microphone {
+    /// Something loud is going on, the real thing is a noisy 1 bit level
+    pub loud: bool,
+}
+
+impl InputDevice for Microphone {
+    fn device(&self) -> Device {
+        Device::Microphone
+    }
+
+    fn strobe(&mut self, _out: u8) {}
+
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.peek(reg, ppu)
+    }
+
+    fn peek(&self, reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        if reg == 0 { (self.loud as u8) << 2 } else { 0 }
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.loud = host.microphone;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
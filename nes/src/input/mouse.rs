@@ -0,0 +1,56 @@
+use super::{joypad::Shifter, *};
+
+/// SNES mouse through an adapter, 32 bits on D0: a 0 byte, the buttons and signature, then
+/// vertical and horizontal movement since the last latch as sign and magnitude
+#[derive(Debug, Clone, Copy)]
+pub struct SnesMouse {
+    pub left: bool,
+    pub right: bool,
+    /// Movement not reported yet
+    pub motion: (i32, i32),
+    shifter: Shifter,
+}
+
+impl Default for SnesMouse {
+    fn default() -> Self {
+        Self { left: false, right: false, motion: (0, 0), shifter: Shifter::new(32) }
+    }
+}
+
+impl InputDevice for SnesMouse {
+    fn device(&self) -> Device {
+        Device::SnesMouse
+    }
+
+    fn strobe(&mut self, out: u8) {
+        if out & 1 != 0 {
+            let axis = |d: i32| ((d < 0) as u32) << 7 | d.unsigned_abs().min(0x7f);
+            let (dx, dy) = self.motion;
+            let report = ((self.right as u32) << 7 | (self.left as u32) << 6 | 0x01) << 16 | axis(dy) << 8 | axis(dx);
+            // shifted out MSB first
+            self.shifter.input = report.reverse_bits();
+            self.motion = (0, 0);
+        }
+
+        self.shifter.strobe(out & 1 != 0);
+    }
+
+    fn read(&mut self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.read()
+    }
+
+    fn peek(&self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifter.peek()
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.left = host.primary;
+        self.right = host.secondary;
+        self.motion.0 += host.motion.0;
+        self.motion.1 += host.motion.1;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
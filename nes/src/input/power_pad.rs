@@ -0,0 +1,57 @@
+use super::{joypad::Shifter, *};
+
+/// Buttons, numbered 1-12 as on side B, in the order D3 shifts them out
+const D3_ORDER: [usize; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+/// And D4, which then returns 1s
+const D4_ORDER: [usize; 4] = [4, 3, 12, 8];
+
+/// Power Pad, or Family Trainer mat, on port 2
+#[derive(Debug, Clone, Copy)]
+pub struct PowerPad {
+    /// Buttons 1-12 in bits 0-11
+    pub buttons: u16,
+    shifters: [Shifter; 2],
+}
+
+impl Default for PowerPad {
+    fn default() -> Self {
+        Self { buttons: 0, shifters: [Shifter::new(8); 2] }
+    }
+}
+
+impl PowerPad {
+    fn bits(&self) -> u8 {
+        self.shifters[0].peek() << 3 | self.shifters[1].peek() << 4
+    }
+}
+
+impl InputDevice for PowerPad {
+    fn device(&self) -> Device {
+        Device::PowerPad
+    }
+
+    fn strobe(&mut self, out: u8) {
+        for shifter in &mut self.shifters {
+            shifter.strobe(out & 1 != 0);
+        }
+    }
+
+    fn read(&mut self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.shifters[0].read() << 3 | self.shifters[1].read() << 4
+    }
+
+    fn peek(&self, _reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.bits()
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.buttons = host.power_pad;
+        let bits = |order: &[usize]| order.iter().enumerate().map(|(i, b)| ((self.buttons >> (b - 1)) as u32 & 1) << i).sum::<u32>();
+        self.shifters[0].input = bits(&D3_ORDER);
+        self.shifters[1].input = bits(&D4_ORDER) | 0xf0;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+/// Knob readings at the far left and right, roughly what the real potentiometer gives
+const KNOB_MIN: u32 = 0x62;
+const KNOB_MAX: u32 = 0xf2;
+
+/// Arkanoid paddle, the knob follows the mouse across the screen and either button fires.
+/// The knob reading is shifted out MSB first and inverted.
+#[derive(Debug, Clone, Copy)]
+pub struct Vaus {
+    /// Famicom version on the expansion port, otherwise the NES one on port 2
+    pub famicom: bool,
+    pub knob: u8,
+    pub button: bool,
+    shift: u8,
+}
+
+impl Vaus {
+    pub fn new(famicom: bool) -> Self {
+        Self { famicom, knob: KNOB_MIN as u8, button: false, shift: 0 }
+    }
+
+    fn bits(&self, reg: usize) -> u8 {
+        let data = self.shift >> 7;
+
+        match (self.famicom, reg) {
+            // NES, data on D3 and the button on D4
+            (false, _) => data << 3 | (self.button as u8) << 4,
+            // Famicom, the button on $4016 D1 and data on $4017 D1
+            (true, 0) => (self.button as u8) << 1,
+            (true, _) => data << 1,
+        }
+    }
+}
+
+impl InputDevice for Vaus {
+    fn device(&self) -> Device {
+        if self.famicom { Device::VausFamicom } else { Device::VausNes }
+    }
+
+    fn strobe(&mut self, out: u8) {
+        if out & 1 != 0 {
+            self.shift = !self.knob;
+        }
+    }
+
+    fn read(&mut self, reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        let bits = self.bits(reg);
+
+        if reg == 1 {
+            self.shift <<= 1;
+        }
+
+        bits
+    }
+
+    fn peek(&self, reg: usize, _ppu: &ppu::Ppu) -> u8 {
+        self.bits(reg)
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        if let Some((x, _)) = host.pointer {
+            let x = x.min(ppu::FRAME_WIDTH - 1) as u32;
+            self.knob = (KNOB_MIN + x * (KNOB_MAX - KNOB_MIN) / (ppu::FRAME_WIDTH as u32 - 1)) as u8;
+        }
+
+        self.button = host.primary || host.secondary;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
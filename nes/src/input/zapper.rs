@@ -0,0 +1,67 @@
+use super::*;
+
+/// Scanlines the sensor keeps reporting light after the beam passed a bright spot
+const SENSE_LINES: usize = 25;
+/// The sensor sees a few pixels around where it's aimed
+const SENSE_RADIUS: usize = 2;
+/// Brightness, 0 black to 1 white, that triggers the sensor
+const SENSE_LEVEL: f32 = 0.8;
+
+/// Light gun, aimed with the mouse and fired with either button, the right one shoots away
+/// from the screen
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zapper {
+    /// Screen position aimed at, `None` when pointing away from the screen
+    pub aim: Option<(usize, usize)>,
+    pub trigger: bool,
+}
+
+impl InputDevice for Zapper {
+    fn device(&self) -> Device {
+        Device::Zapper
+    }
+
+    fn strobe(&mut self, _out: u8) {}
+
+    fn read(&mut self, reg: usize, ppu: &ppu::Ppu) -> u8 {
+        self.peek(reg, ppu)
+    }
+
+    /// Bit 3 is 0 while light is sensed and bit 4 is the trigger
+    fn peek(&self, _reg: usize, ppu: &ppu::Ppu) -> u8 {
+        let light = self.aim.is_some_and(|(x, y)| light_sensed(ppu, x, y));
+        (!light as u8) << 3 | (self.trigger as u8) << 4
+    }
+
+    fn update(&mut self, host: &HostInput) {
+        self.aim = if host.secondary { None } else { host.pointer };
+        self.trigger = host.primary || host.secondary;
+    }
+
+    fn box_clone(&self) -> Box<dyn InputDevice> {
+        Box::new(*self)
+    }
+}
+
+/// Whether a bright pixel near `x`, `y` was drawn during the last few scanlines
+fn light_sensed(ppu: &ppu::Ppu, x: usize, y: usize) -> bool {
+    let (scanline, cycle) = (ppu.scanline, ppu.cycle);
+    // dot 0 is idle, pixel x is output on dot x + 1
+    let beam = scanline * ppu::FRAME_WIDTH + cycle.saturating_sub(1).min(ppu::FRAME_WIDTH);
+
+    let lines = y.saturating_sub(SENSE_RADIUS)..=(y + SENSE_RADIUS).min(ppu::FRAME_HEIGHT - 1);
+    let dots = x.saturating_sub(SENSE_RADIUS)..=(x + SENSE_RADIUS).min(ppu::FRAME_WIDTH - 1);
+
+    lines.flat_map(|py| dots.clone().map(move |px| (px, py))).any(|(px, py)| {
+        // only what the beam already drew this frame, and not too long ago
+        let drawn = py * ppu::FRAME_WIDTH + px < beam && scanline - py <= SENSE_LINES;
+        let color = ppu.framebuffer[py * ppu::FRAME_WIDTH + px];
+        drawn && luminance(color) >= SENSE_LEVEL
+    })
+}
+
+/// Average level of a framebuffer pixel's signal
+fn luminance(px: u16) -> f32 {
+    let (color, emphasis) = ((px & 0x3f) as u8, (px >> 6) as u8);
+    (0..12).map(|p| palette::signal(color, emphasis, p)).sum::<f32>() / 12.0
+}
@@ -1,70 +1,248 @@
+pub mod apu;
 pub mod cart;
+pub mod cheat;
 pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod input;
+pub mod movie;
+pub mod ntsc;
+pub mod palette;
+pub mod power;
 pub mod ppu;
+pub mod search;
+pub mod snapshot;
+pub mod trace;
 
+#[cfg(any(test, feature = "fixture"))]
+pub mod fixture;
 #[cfg(test)]
 mod test;
 
-pub struct Nes<'a> {
+/// TV system, decides the PPU frame layout and the clock rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    /// Famiclones with PAL frame size but NTSC-like CPU timing
+    Dendy,
+}
+
+impl Region {
+    /// Scanlines per frame, including vblank and the pre-render line
+    pub fn scanlines(self) -> usize {
+        match self {
+            Self::Ntsc => 262,
+            Self::Pal | Self::Dendy => 312,
+        }
+    }
+
+    /// Scanline vblank and NMI start on
+    pub fn vblank_scanline(self) -> usize {
+        match self {
+            Self::Ntsc | Self::Pal => 241,
+            Self::Dendy => 291,
+        }
+    }
+
+    /// Master clock ticks per second, every component runs off a divider of it
+    pub fn master_hz(self) -> u64 {
+        match self {
+            Self::Ntsc => 21_477_272,
+            Self::Pal | Self::Dendy => 26_601_712,
+        }
+    }
+
+    /// Master clock ticks per CPU cycle
+    pub fn cpu_divider(self) -> u64 {
+        match self {
+            Self::Ntsc => 12,
+            Self::Pal => 16,
+            Self::Dendy => 15,
+        }
+    }
+
+    /// Master clock ticks per PPU dot
+    pub fn ppu_divider(self) -> u64 {
+        match self {
+            Self::Ntsc => 4,
+            Self::Pal | Self::Dendy => 5,
+        }
+    }
+
+    pub fn cpu_hz(self) -> f64 {
+        self.master_hz() as f64 / self.cpu_divider() as f64
+    }
+
+    /// Width over height of a PPU pixel on a TV, from the dot clock against the TV's sampling rate
+    pub fn pixel_aspect(self) -> f64 {
+        match self {
+            Self::Ntsc => 8.0 / 7.0,
+            Self::Pal | Self::Dendy => 2_950_000.0 / 2_128_137.0,
+        }
+    }
+
+    /// Exact emulated frame rate, about 60.0988 for NTSC
+    pub fn frame_rate(self) -> f64 {
+        let (num, den) = self.frame_rate_ratio();
+        num as f64 / den as f64
+    }
+
+    /// [`Region::frame_rate`] as a fraction, for video containers
+    pub fn frame_rate_ratio(self) -> (u64, u64) {
+        // every other NTSC frame is a dot shorter, count in half dots
+        let half_dots = 2 * 341 * self.scanlines() as u64 - (self == Self::Ntsc) as u64;
+        let (num, den) = (2 * self.master_hz(), self.ppu_divider() * half_dots);
+
+        let (mut a, mut b) = (num, den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+
+        (num / a, den / a)
+    }
+}
+
+pub struct Nes {
     pub cpu: cpu::Cpu,
     pub ppu: ppu::Ppu,
+    pub apu: apu::Apu,
+    pub region: Region,
 
     pub iram: [u8; 0x800],
-    pub cart: &'a mut dyn cart::Cartridge,
+    pub cart: Box<dyn cart::Cartridge>,
+    /// Controllers and whatever else is plugged in
+    pub input: input::Input,
+    /// Enabled cheats, substituted into CPU reads
+    pub cheats: Vec<cheat::Patch>,
+
+    pub tracer: Option<trace::Tracer>,
+    pub debugger: Option<debugger::Debugger>,
 
-    last_read: u8,
-    cycles_ahead: usize,
+    /// NMI edge detected, serviced before the next instruction
+    nmi: bool,
+    /// Last value on the CPU data bus, reads of undriven bits return it
+    data_bus: u8,
+    /// Master clock ticks since power on. The CPU drives the clock, everything else catches up
+    /// to it.
+    master_clock: u64,
+    /// Master clock tick the PPU has caught up to
+    ppu_clock: u64,
     fetched_bytes: usize,
 }
 
-impl<'a> Nes<'a> {
-    pub fn new(cart: &'a mut dyn cart::Cartridge, start: Option<u16>) -> Self {
+impl Nes {
+    /// Power on with zeroed RAM
+    pub fn new(cart: Box<dyn cart::Cartridge>, start: Option<u16>, region: Region) -> Self {
+        Self::with_config(cart, start, region, &power::PowerOnConfig::default())
+    }
+
+    pub fn with_config(mut cart: Box<dyn cart::Cartridge>, start: Option<u16>, region: Region, config: &power::PowerOnConfig) -> Self {
+        if let Some(fill) = config.prg_ram {
+            fill.fill(cart.memory_mut(cart::Memory::PrgRam));
+        }
+
+        let mut iram = [0; 0x800];
+        config.ram.fill(&mut iram);
+
         let fffc = cart.load(0xfffc).unwrap();
         let fffd = cart.load(0xfffd).unwrap();
+        // the reset sequence takes 7 cycles, the PPU starts out caught up
+        let master_clock = 7 * region.cpu_divider();
 
         Self {
             cpu: cpu::Cpu::new(start, fffc, fffd),
             ppu: ppu::Ppu::new(),
+            apu: apu::Apu::new(),
+            region,
 
-            iram: [0; 0x800],
+            iram,
             cart,
+            input: Default::default(),
+            cheats: Vec::new(),
 
-            last_read: 0,
-            cycles_ahead: 7,
+            tracer: None,
+            debugger: None,
+
+            nmi: false,
+            data_bus: 0,
+            master_clock,
+            ppu_clock: master_clock,
             fetched_bytes: 0,
         }
     }
 
-    pub fn step(&mut self) {
-        self.cycles_ahead -= 1;
+    /// Run one whole instruction or interrupt, unless the debugger halted the CPU. The PPU, APU
+    /// and cartridge are ticked through every CPU cycle it takes, there is no way to stop
+    /// halfway through an instruction.
+    pub fn step_instruction(&mut self) {
+        self.step_everything();
+    }
+
+    /// Run until the PPU starts the next frame or the debugger halts the CPU
+    pub fn step_frame(&mut self) {
+        let frame = self.ppu.frame;
 
-        if self.cycles_ahead != 0 {
-            return;
+        while self.ppu.frame == frame && !self.halted() {
+            self.step_instruction();
         }
+    }
 
-        self.step_everything();
+    /// CPU cycles since power on
+    pub fn cycles(&self) -> usize {
+        (self.master_clock / self.region.cpu_divider()) as usize
     }
 
-    fn step_not_cpu(&mut self) {
-        for _ in 0..3 { self.step_ppu(); }
+    pub fn master_clock(&self) -> u64 {
+        self.master_clock
     }
 
+    /// Run the CPU for `cy` cycles, ticking the other components along
     fn elapse_cycles(&mut self, cy: usize) {
-        self.cycles_ahead += cy;
-
         for _ in 0..cy {
-            self.step_not_cpu();
+            self.master_clock += self.region.cpu_divider();
+            self.catch_up_ppu();
+            self.step_apu();
+            self.cart.cpu_clock();
+        }
+    }
+
+    fn catch_up_ppu(&mut self) {
+        let divider = self.region.ppu_divider();
+
+        while self.ppu_clock + divider <= self.master_clock {
+            self.ppu_clock += divider;
+            self.step_ppu();
         }
     }
 
     fn load(&mut self, addr: u16) -> u8 {
         self.elapse_cycles(1);
+        self.drive_bus(addr);
+
+        if self.debugger.is_some() {
+            self.debug_access(debugger::Space::Cpu, addr, self.data_bus, false);
+        }
 
+        self.data_bus
+    }
+
+    /// Read `addr` onto the data bus, bits the device doesn't drive keep their old value
+    fn drive_bus(&mut self, addr: u16) {
         if let Ok(v) = self._load(addr) {
-            self.last_read = v;
-            v
-        } else {
-            self.last_read
+            let mask = self.driven_bits(addr);
+            self.data_bus = (self.data_bus & !mask) | (v & mask);
+        }
+    }
+
+    /// Bits driven by a successful load or peek of `addr`
+    fn driven_bits(&self, addr: u16) -> u8 {
+        match addr {
+            0x4015 => 0xdf, // internal to the 2A03, bit 5 isn't connected
+            0x4016 | 0x4017 => 0x1f, // controller ports
+            0x4020..=0xffff => self.cart.driven_bits(addr),
+            _ => 0xff,
         }
     }
 
@@ -74,33 +252,75 @@ impl<'a> Nes<'a> {
         ((h as u16) << 8) | (l as u16)
     }
 
-    fn _load(&mut self, addr: u16) -> Result<u8, ()> {
-        match addr {
+    fn _load(&mut self, addr: u16) -> Result<u8, cart::OpenBus> {
+        let value = match addr {
             0x0000..=0x1fff => Ok(self.iram[addr as usize & 0x7ff]),
             0x2000..=0x3fff => self.load_ppu_mmio(addr), // PPU regs
-            0x4000..=0x4017 => Err(()), // APU & IO
-            0x4018..=0x401f => Err(()), // APU & IO test mode
+            0x4015 => Ok(self.load_apu_status()),
+            0x4016 | 0x4017 => Ok(self.load_input(addr as usize - 0x4016)),
+            0x4000..=0x4017 => Err(cart::OpenBus), // APU & IO
+            0x4018..=0x401f => Err(cart::OpenBus), // APU & IO test mode
             0x4020..=0xffff => self.cart.load(addr),
+        };
+
+        match self.cheats.is_empty() {
+            true => value,
+            false => cheat::patch(&self.cheats, addr, value),
         }
     }
 
+    /// Read the CPU bus without side effects, for debuggers and tracing
+    pub fn peek(&self, addr: u16) -> u8 {
+        let value = match addr {
+            0x0000..=0x1fff => Ok(self.iram[addr as usize & 0x7ff]),
+            0x2000..=0x3fff => self.peek_ppu_mmio(addr),
+            0x4015 => Ok(self.apu.peek_status()),
+            0x4016 | 0x4017 => Ok(self.peek_input(addr as usize - 0x4016)),
+            0x4000..=0x401f => Err(cart::OpenBus),
+            0x4020..=0xffff => self.cart.peek(addr),
+        };
+
+        match self.cheats.is_empty() {
+            true => value,
+            false => cheat::patch(&self.cheats, addr, value),
+        }.map_or(self.data_bus, |v| {
+            let mask = self.driven_bits(addr);
+            (self.data_bus & !mask) | (v & mask)
+        })
+    }
+
     fn store(&mut self, addr: u16, val: u8) {
         self.elapse_cycles(1);
+        self.data_bus = val;
+
+        if self.debugger.is_some() {
+            self.debug_access(debugger::Space::Cpu, addr, val, true);
+        }
 
         _ = self._store(addr, val);
     }
 
+    /// Write the CPU bus without taking a cycle, for scripts. Writes to $4014 are dropped, OAM
+    /// DMA would run the console in the middle of a hook.
+    pub fn poke(&mut self, addr: u16, val: u8) {
+        if addr != 0x4014 {
+            _ = self._store(addr, val);
+        }
+    }
+
     fn store_u16(&mut self, addr: u16, val: u16) {
         self.store(addr, val as u8);
         self.store(addr + 1, (val >> 8) as u8);
     }
 
-    fn _store(&mut self, addr: u16, val: u8) -> Result<(), ()> {
+    fn _store(&mut self, addr: u16, val: u8) -> Result<(), cart::OpenBus> {
         match addr {
-            0x0000..=0x1fff => Ok(self.iram[addr as usize & 0x7ff] = val),
-            0x2000..=0x3fff => Ok(self.store_ppu_mmio(addr, val)), // PPU regs
-            0x4000..=0x4017 => Err(()), // APU & IO
-            0x4018..=0x401f => Err(()), // APU & IO test mode
+            0x0000..=0x1fff => { self.iram[addr as usize & 0x7ff] = val; Ok(()) },
+            0x2000..=0x3fff => { self.store_ppu_mmio(addr, val); Ok(()) }, // PPU regs
+            0x4014 => { self.oam_dma(val); Ok(()) },
+            0x4016 => { self.store_input(val); Ok(()) },
+            0x4000..=0x4013 | 0x4015 | 0x4017 => { self.store_apu(addr, val); Ok(()) },
+            0x4018..=0x401f => Err(cart::OpenBus), // APU & IO test mode
             0x4020..=0xffff => self.cart.store(addr, val),
         }
     }
@@ -0,0 +1,402 @@
+use super::*;
+use core::fmt::Write;
+
+/// Where a movie starts
+#[derive(Debug, Clone)]
+pub enum Anchor {
+    /// A console just created with [`Nes::with_config`]
+    PowerOn(power::PowerOnConfig),
+    /// Only lives in memory, movie files can't store snapshots
+    Snapshot(Box<snapshot::Snapshot>),
+}
+
+/// What movies are recorded and replayed with, [`Frame`] only holds the two controllers
+pub const DEVICES: [input::Device; 3] = [input::Device::Joypad, input::Device::Joypad, input::Device::Unplugged];
+
+/// Input for one frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub joypads: [u8; 2],
+    /// Press the reset button before running the frame
+    pub reset: bool,
+    /// [`ppu::Ppu::frame_hash`] after the frame, only the native format stores these
+    pub hash: Option<u64>,
+}
+
+/// Controller input recorded frame by frame from an [`Anchor`], replays bit exactly
+#[derive(Debug, Clone)]
+pub struct Movie {
+    pub anchor: Anchor,
+    pub region: Region,
+    /// FCEUX style `base64:` encoded MD5 of PRG and CHR ROM
+    pub rom_checksum: Option<String>,
+    pub rom_name: Option<String>,
+    pub rerecords: u32,
+    pub frames: Vec<Frame>,
+}
+
+// FM2 and BizHawk button order, bit 7 first
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+const BIZHAWK_BUTTONS: [(u8, &str, u8); 8] = [
+    (b'U', "Up", input::joypad::UP),
+    (b'D', "Down", input::joypad::DOWN),
+    (b'L', "Left", input::joypad::LEFT),
+    (b'R', "Right", input::joypad::RIGHT),
+    (b'S', "Start", input::joypad::START),
+    (b's', "Select", input::joypad::SELECT),
+    (b'B', "B", input::joypad::B),
+    (b'A', "A", input::joypad::A),
+];
+
+impl Movie {
+    pub fn new(anchor: Anchor, region: Region) -> Self {
+        Self { anchor, region, rom_checksum: None, rom_name: None, rerecords: 0, frames: Vec::new() }
+    }
+
+    /// Start a movie from the console's current state, which needs [`DEVICES`] plugged
+    pub fn from_snapshot(nes: &Nes) -> Result<Self, String> {
+        if nes.input.devices() != DEVICES {
+            return Err("movies only hold two controllers, unplug everything else first".to_string());
+        }
+
+        Ok(Self::new(Anchor::Snapshot(Box::new(nes.snapshot())), nes.region))
+    }
+
+    /// A console at the movie's start with `cart` in it. Fails if a snapshot anchor doesn't fit
+    /// the cartridge.
+    pub fn start(&self, cart: Box<dyn cart::Cartridge>) -> Result<Nes, cart::BadState> {
+        match &self.anchor {
+            Anchor::PowerOn(config) => Ok(Nes::with_config(cart, None, self.region, config)),
+            Anchor::Snapshot(snap) => {
+                let mut nes = Nes::new(cart, None, self.region);
+                nes.restore(snap)?;
+                Ok(nes)
+            },
+        }
+    }
+
+    /// Hold the buttons in `frame` and press reset if it asks to, before the frame runs
+    pub fn start_frame(nes: &mut Nes, frame: &Frame) {
+        if frame.reset {
+            nes.reset();
+        }
+
+        let mut host = nes.input.host.clone();
+        host.buttons[..2].copy_from_slice(&frame.joypads);
+        nes.input.update(host);
+    }
+
+    /// Hold the buttons in `frame` and run it
+    pub fn run_frame(nes: &mut Nes, frame: &Frame) {
+        Self::start_frame(nes, frame);
+        nes.step_frame();
+    }
+
+    /// Run a frame with the buttons held right now and append it
+    pub fn record_frame(&mut self, nes: &mut Nes, reset: bool) {
+        let mut frame = Frame {
+            joypads: [nes.input.host.buttons[0], nes.input.host.buttons[1]],
+            reset,
+            hash: None,
+        };
+
+        Self::run_frame(nes, &frame);
+        frame.hash = Some(nes.ppu.frame_hash());
+        self.frames.push(frame);
+    }
+
+    /// Replay on a console from [`Movie::start`], `Err` holds the first frame whose picture
+    /// differs from the recording. A snapshot anchor that doesn't fit the cartridge fails on
+    /// frame 0 and leaves `None` for the console.
+    pub fn verify(&self, cart: Box<dyn cart::Cartridge>) -> (Option<Nes>, Result<(), usize>) {
+        let Ok(mut nes) = self.start(cart) else { return (None, Err(0)) };
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            Self::run_frame(&mut nes, frame);
+
+            if frame.hash.is_some_and(|hash| hash != nes.ppu.frame_hash()) {
+                return (Some(nes), Err(i));
+            }
+        }
+
+        (Some(nes), Ok(()))
+    }
+
+    fn power_on(&self) -> Result<&power::PowerOnConfig, String> {
+        match &self.anchor {
+            Anchor::PowerOn(config) => Ok(config),
+            Anchor::Snapshot(_) => Err("movies starting from a snapshot can't be saved".to_string()),
+        }
+    }
+
+    /// Native format, FM2-like text with the power on config and frame hashes
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+
+        if lines.next().map(str::trim) != Some("rustyness-movie 1") {
+            return Err("not a rustyness movie".to_string());
+        }
+
+        let mut config = power::PowerOnConfig::default();
+        let mut movie = Self::new(Anchor::PowerOn(Default::default()), Region::Ntsc);
+
+        for line in lines {
+            if let Some(input) = line.strip_prefix('|') {
+                let mut fields = input.split('|');
+                let mut frame = parse_fm2_frame(&mut fields, movie.frames.is_empty())?;
+                let hash = fields.next().unwrap_or("").trim();
+
+                if !hash.is_empty() {
+                    frame.hash = Some(u64::from_str_radix(hash, 16).map_err(|_| format!("bad frame hash `{hash}`"))?);
+                }
+
+                movie.frames.push(frame);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "region" => movie.region = match value {
+                    "ntsc" => Region::Ntsc,
+                    "pal" => Region::Pal,
+                    "dendy" => Region::Dendy,
+                    _ => return Err(format!("unknown region `{value}`")),
+                },
+                "ram" => config.ram = parse_ram_fill(value)?,
+                "prgRam" => config.prg_ram = Some(parse_ram_fill(value)?),
+                "romChecksum" => movie.rom_checksum = Some(value.to_string()),
+                "romFilename" => movie.rom_name = Some(value.to_string()),
+                "rerecordCount" => movie.rerecords = value.parse().map_err(|_| format!("bad rerecord count `{value}`"))?,
+                "" => {},
+                _ => return Err(format!("unknown key `{key}`")),
+            }
+        }
+
+        movie.anchor = Anchor::PowerOn(config);
+        Ok(movie)
+    }
+
+    pub fn write(&self) -> Result<String, String> {
+        let config = self.power_on()?;
+        let mut out = String::from("rustyness-movie 1\n");
+
+        _ = writeln!(out, "region {}", match self.region {
+            Region::Ntsc => "ntsc",
+            Region::Pal => "pal",
+            Region::Dendy => "dendy",
+        });
+        _ = writeln!(out, "ram {}", write_ram_fill(config.ram));
+
+        if let Some(fill) = config.prg_ram {
+            _ = writeln!(out, "prgRam {}", write_ram_fill(fill));
+        }
+
+        self.write_rom_info(&mut out);
+
+        for frame in &self.frames {
+            write_fm2_frame(&mut out, frame);
+
+            if let Some(hash) = frame.hash {
+                _ = write!(out, "{hash:016x}");
+            }
+
+            out.push('\n');
+        }
+
+        Ok(out)
+    }
+
+    /// FCEUX movie, see <https://fceux.com/web/help/fm2.html>
+    pub fn parse_fm2(text: &str) -> Result<Self, String> {
+        let mut movie = Self::new(Anchor::PowerOn(Default::default()), Region::Ntsc);
+
+        for line in text.lines() {
+            if let Some(input) = line.strip_prefix('|') {
+                let frame = parse_fm2_frame(&mut input.split('|'), movie.frames.is_empty())?;
+                movie.frames.push(frame);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "version" if value != "3" => return Err(format!("unsupported fm2 version {value}")),
+                "palFlag" => movie.region = if value == "1" { Region::Pal } else { Region::Ntsc },
+                "romChecksum" => movie.rom_checksum = Some(value.to_string()),
+                "romFilename" => movie.rom_name = Some(value.to_string()),
+                "rerecordCount" => movie.rerecords = value.parse().unwrap_or(0),
+                "savestate" => return Err("fm2 movies starting from a savestate aren't supported".to_string()),
+                "fourscore" | "FDS" if value != "0" => return Err(format!("unsupported fm2 setting `{key} {value}`")),
+                "port0" | "port1" if value != "0" && value != "1" => return Err(format!("unsupported fm2 controller `{key} {value}`")),
+                _ => {},
+            }
+        }
+
+        Ok(movie)
+    }
+
+    pub fn write_fm2(&self) -> Result<String, String> {
+        self.power_on()?;
+        let mut out = String::from("version 3\nemuVersion 22020\n");
+        _ = writeln!(out, "palFlag {}", (self.region == Region::Pal) as u8);
+        out.push_str("guid 00000000-0000-0000-0000-000000000000\nfourscore 0\nmicrophone 0\nport0 1\nport1 1\nport2 0\nFDS 0\nNewPPU 0\n");
+        self.write_rom_info(&mut out);
+
+        for frame in &self.frames {
+            write_fm2_frame(&mut out, frame);
+            out.push_str("|\n");
+        }
+
+        Ok(out)
+    }
+
+    /// The `Input Log.txt` inside a BizHawk `.bk2`, only the NES controller layout
+    pub fn parse_bizhawk(text: &str) -> Result<Self, String> {
+        let mut movie = Self::new(Anchor::PowerOn(Default::default()), Region::Ntsc);
+        let mut key = None;
+
+        for line in text.lines().map(str::trim) {
+            if let Some(k) = line.strip_prefix("LogKey:") {
+                key = Some(k.split('#').filter(|g| !g.is_empty()).map(|g| {
+                    g.split('|').filter(|name| !name.is_empty()).collect::<Vec<_>>()
+                }).collect::<Vec<_>>());
+                continue;
+            }
+
+            let Some(input) = line.strip_prefix('|') else { continue };
+            let key = key.as_ref().ok_or("input before the LogKey line")?;
+            let mut frame = Frame::default();
+
+            for (group, names) in input.split('|').zip(key) {
+                for (c, name) in group.bytes().zip(names) {
+                    if c == b'.' {
+                        continue;
+                    }
+
+                    match *name {
+                        "Reset" => frame.reset = true,
+                        "Power" if movie.frames.is_empty() => {},
+                        _ => {
+                            let button = name.strip_prefix("P1 ").map(|b| (0, b))
+                                .or_else(|| name.strip_prefix("P2 ").map(|b| (1, b)))
+                                .and_then(|(port, b)| {
+                                    let (_, _, bit) = BIZHAWK_BUTTONS.iter().find(|(_, n, _)| *n == b)?;
+                                    Some((port, *bit))
+                                });
+
+                            let (port, bit) = button.ok_or(format!("unsupported input `{name}`"))?;
+                            frame.joypads[port] |= bit;
+                        },
+                    }
+                }
+            }
+
+            movie.frames.push(frame);
+        }
+
+        Ok(movie)
+    }
+
+    pub fn write_bizhawk(&self) -> Result<String, String> {
+        self.power_on()?;
+        let mut out = String::from("[Input]\nLogKey:#Reset|Power|");
+
+        for port in 1..=2 {
+            out.push('#');
+
+            for (_, name, _) in BIZHAWK_BUTTONS {
+                _ = write!(out, "P{port} {name}|");
+            }
+        }
+
+        out.push('\n');
+
+        for frame in &self.frames {
+            out.push('|');
+            out.push(if frame.reset { 'r' } else { '.' });
+            out.push_str(".|");
+
+            for buttons in frame.joypads {
+                for (c, _, bit) in BIZHAWK_BUTTONS {
+                    out.push(if buttons & bit != 0 { c as char } else { '.' });
+                }
+
+                out.push('|');
+            }
+
+            out.push('\n');
+        }
+
+        out.push_str("[/Input]\n");
+        Ok(out)
+    }
+
+    fn write_rom_info(&self, out: &mut String) {
+        _ = writeln!(out, "rerecordCount {}", self.rerecords);
+
+        if let Some(name) = &self.rom_name {
+            _ = writeln!(out, "romFilename {name}");
+        }
+
+        if let Some(checksum) = &self.rom_checksum {
+            _ = writeln!(out, "romChecksum {checksum}");
+        }
+    }
+}
+
+/// `commands|port0|port1|`, the leading `|` already stripped
+fn parse_fm2_frame<'a>(fields: &mut impl Iterator<Item = &'a str>, first: bool) -> Result<Frame, String> {
+    let mut frame = Frame::default();
+    let commands: u8 = fields.next().unwrap_or("").trim().parse().map_err(|_| "bad fm2 commands".to_string())?;
+
+    frame.reset = commands & 1 != 0;
+
+    // a power cycle on the first frame is how FCEUX starts recording from power on
+    if commands & 2 != 0 && !first {
+        return Err("power cycling inside a movie isn't supported".to_string());
+    }
+
+    for pad in &mut frame.joypads {
+        for (i, c) in fields.next().unwrap_or("").bytes().take(8).enumerate() {
+            if c != b'.' && c != b' ' {
+                *pad |= 0x80 >> i;
+            }
+        }
+    }
+
+    Ok(frame)
+}
+
+fn write_fm2_frame(out: &mut String, frame: &Frame) {
+    _ = write!(out, "|{}|", frame.reset as u8);
+
+    for buttons in frame.joypads {
+        for (i, &c) in FM2_BUTTONS.iter().enumerate() {
+            out.push(if buttons & (0x80 >> i) != 0 { c as char } else { '.' });
+        }
+
+        out.push('|');
+    }
+}
+
+fn parse_ram_fill(text: &str) -> Result<power::RamFill, String> {
+    match text.split_once(' ') {
+        Some(("random", seed)) => seed.parse().map(power::RamFill::Random).map_err(|_| format!("bad seed `{seed}`")),
+        _ => match text {
+            "zeros" => Ok(power::RamFill::Zeros),
+            "ones" => Ok(power::RamFill::Ones),
+            "pattern" => Ok(power::RamFill::Pattern),
+            _ => Err(format!("unknown RAM fill `{text}`")),
+        },
+    }
+}
+
+fn write_ram_fill(fill: power::RamFill) -> String {
+    match fill {
+        power::RamFill::Zeros => "zeros".to_string(),
+        power::RamFill::Ones => "ones".to_string(),
+        power::RamFill::Random(seed) => format!("random {seed}"),
+        power::RamFill::Pattern => "pattern".to_string(),
+    }
+}
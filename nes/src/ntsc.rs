@@ -0,0 +1,117 @@
+//! NTSC video filter. Rebuilds the 2C02's signal from the framebuffer and decodes it like a TV
+//! would, so dithering blends, colors fringe at edges and the dot pattern crawls with the color
+//! burst phase.
+
+use crate::{
+    palette::{self, Palette, Params},
+    ppu::{FRAME_HEIGHT, FRAME_WIDTH},
+};
+
+/// Output pixels per PPU pixel, every output pixel covers 4 of the 8 signal samples of a dot
+pub const SCALE: usize = 2;
+pub const WIDTH: usize = FRAME_WIDTH * SCALE;
+
+const SAMPLES: usize = FRAME_WIDTH * 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Luma and chroma share one signal and bleed into each other
+    Composite,
+    /// Luma and chroma are separate, only chroma is blurred
+    SVideo,
+    /// No signal at all, palette colors
+    Rgb,
+    /// A black and white TV, the color carrier shows up as a fine pattern
+    Monochrome,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Composite, Preset::SVideo, Preset::Rgb, Preset::Monochrome];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Composite => "Composite",
+            Preset::SVideo => "S-Video",
+            Preset::Rgb => "RGB",
+            Preset::Monochrome => "Monochrome",
+        }
+    }
+}
+
+/// RGBA image [`WIDTH`] by [`FRAME_HEIGHT`] of a framebuffer. `burst_phase` is
+/// [`crate::ppu::Ppu::burst_phase`], `palette` is only used by [`Preset::Rgb`].
+pub fn filter(framebuffer: &[u16], burst_phase: u8, preset: Preset, palette: &Palette, params: &Params) -> Vec<u8> {
+    let mut out = Vec::with_capacity(WIDTH * FRAME_HEIGHT * 4);
+
+    if preset == Preset::Rgb {
+        for &px in framebuffer {
+            for _ in 0..SCALE {
+                out.extend(palette.rgba(px));
+            }
+        }
+
+        return out;
+    }
+
+    // taps of the box filters separating luma and chroma, a full cycle removes the carrier
+    let (luma_taps, chroma_taps) = match preset {
+        Preset::Composite => (10, 24),
+        Preset::SVideo => (2, 12),
+        _ => (4, 0),
+    };
+
+    // running sums of luma, and chroma demodulated into I and Q
+    let mut y = vec![0.0; SAMPLES + 1];
+    let mut i = vec![0.0; SAMPLES + 1];
+    let mut q = vec![0.0; SAMPLES + 1];
+
+    for (line, row) in framebuffer.chunks(FRAME_WIDTH).enumerate() {
+        // every line is 341 dots, 2728 samples, a third of a cycle more than whole cycles
+        let phase = (burst_phase as usize + line) % 3 * 4;
+
+        for (x, &px) in row.iter().enumerate() {
+            let (color, emphasis) = ((px & 0x3f) as u8, (px >> 6) as u8);
+            // pixels start on the dot after the line's first
+            let first = (phase + (x + 1) * 8) % 12;
+
+            // S-Video carries the average level of the pixel apart from the chroma
+            let luma = (preset == Preset::SVideo)
+                .then(|| (0..12).map(|p| palette::signal(color, emphasis, p)).sum::<f32>() / 12.0);
+
+            for s in 0..8 {
+                let n = x * 8 + s;
+                let p = ((first + s) % 12) as u8;
+                let v = palette::signal(color, emphasis, p);
+                let (l, c) = match luma {
+                    Some(l) => (l, v - l),
+                    None => (v, v),
+                };
+                let angle = palette::carrier(p, params);
+
+                y[n + 1] = y[n] + l;
+                i[n + 1] = i[n] + c * angle.cos();
+                q[n + 1] = q[n] + c * angle.sin();
+            }
+        }
+
+        for o in 0..WIDTH {
+            let center = o * 8 / SCALE + 4 / SCALE;
+            let window = |sums: &[f32], taps: usize| {
+                let start = center.saturating_sub(taps / 2);
+                let end = (center + taps.div_ceil(2)).min(SAMPLES);
+                (sums[end] - sums[start]) / (end - start) as f32
+            };
+
+            let (ci, cq) = if chroma_taps == 0 {
+                (0.0, 0.0)
+            } else {
+                (2.0 * window(&i, chroma_taps), 2.0 * window(&q, chroma_taps))
+            };
+
+            let [r, g, b] = palette::decode(window(&y, luma_taps), ci, cq, params);
+            out.extend([r, g, b, 0xff]);
+        }
+    }
+
+    out
+}
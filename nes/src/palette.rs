@@ -0,0 +1,194 @@
+/// PPU a palette is generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// NTSC composite video
+    Ntsc2C02,
+    /// Arcade and Famicom Titler RGB PPUs, these ignore the decoding parameters
+    Rgb2C03,
+    /// PAL composite video, red and green emphasis are swapped
+    Pal2C07,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Ntsc2C02, Preset::Rgb2C03, Preset::Pal2C07];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Ntsc2C02 => "2C02 NTSC",
+            Preset::Rgb2C03 => "2C03/2C05 RGB",
+            Preset::Pal2C07 => "2C07 PAL",
+        }
+    }
+}
+
+/// How the TV decodes the composite signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    /// Degrees
+    pub hue: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub brightness: f32,
+    /// The display's gamma, 2.2 leaves the decoded levels as they are
+    pub gamma: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self { hue: 0.0, saturation: 1.0, contrast: 1.0, brightness: 0.0, gamma: 2.2 }
+    }
+}
+
+/// RGB for every framebuffer value, 64 colors for each of the 8 emphasis combinations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+// composite voltages for the luma levels while the square wave is low and high
+const SIGNAL_LOW: [f32; 4] = [0.228, 0.312, 0.552, 0.880];
+const SIGNAL_HIGH: [f32; 4] = [0.616, 0.840, 1.100, 1.100];
+const BLACK: f32 = 0.312;
+const WHITE: f32 = 1.100;
+// color burst phase in samples
+const HUE_OFFSET: f32 = 4.0;
+// emphasis attenuates the signal during half of the color cycle
+const ATTENUATION: f32 = 0.746;
+
+// 2C03 levels, 3 bits per channel
+const RGB_2C03: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o000, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
+];
+
+impl Palette {
+    pub fn generate(preset: Preset, params: &Params) -> Self {
+        let colors = (0..512u16).map(|px| {
+            let (color, emphasis) = ((px & 0x3f) as u8, (px >> 6) as u8);
+
+            match preset {
+                Preset::Ntsc2C02 => composite(color, emphasis, params),
+                Preset::Pal2C07 => composite(color, emphasis & 4 | (emphasis & 1) << 1 | (emphasis & 2) >> 1, params),
+                Preset::Rgb2C03 => {
+                    // emphasis drives a channel fully on instead of dimming the others
+                    let rgb = RGB_2C03[color as usize];
+                    std::array::from_fn(|c| {
+                        let level = if emphasis & 1 << c != 0 { 7 } else { rgb >> (6 - 3 * c) & 7 };
+                        (level * 255 / 7) as u8
+                    })
+                },
+            }
+        });
+
+        Self { colors: colors.collect() }
+    }
+
+    /// 64 colors with the emphasis combinations approximated by dimming the other channels
+    pub fn from_colors(colors: &[[u8; 3]; 64]) -> Self {
+        let colors = (0..512).map(|px| {
+            let emphasis = px >> 6;
+            let rgb = colors[px & 0x3f];
+
+            std::array::from_fn(|c| {
+                let dims = (emphasis & !(1 << c)).count_ones() as i32;
+                (rgb[c] as f32 * ATTENUATION.powi(dims)).round() as u8
+            })
+        });
+
+        Self { colors: colors.collect() }
+    }
+
+    /// A `.pal` file, 64 RGB triples or 512 with emphasis
+    pub fn parse_pal(data: &[u8]) -> Result<Self, String> {
+        let rgb = |i: usize| [data[i * 3], data[i * 3 + 1], data[i * 3 + 2]];
+
+        match data.len() {
+            192 => Ok(Self::from_colors(&std::array::from_fn(rgb))),
+            1536 => Ok(Self { colors: (0..512).map(rgb).collect() }),
+            len => Err(format!("a .pal file has 192 or 1536 bytes, not {len}")),
+        }
+    }
+
+    /// A `.pal` file with 64 or 512 entries
+    pub fn write_pal(&self, entries: usize) -> Vec<u8> {
+        self.colors[..entries].iter().flatten().copied().collect()
+    }
+
+    pub fn rgba(&self, px: u16) -> [u8; 4] {
+        let [r, g, b] = self.colors[px as usize & 0x1ff];
+        [r, g, b, 0xff]
+    }
+
+    /// RGBA image of a PPU framebuffer
+    pub fn framebuffer_rgba(&self, framebuffer: &[u16]) -> Vec<u8> {
+        framebuffer.iter().flat_map(|&px| self.rgba(px)).collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::generate(Preset::Ntsc2C02, &Params::default())
+    }
+}
+
+/// Decode one color from the 12 samples per color cycle the PPU generates
+fn composite(color: u8, emphasis: u8, params: &Params) -> [u8; 3] {
+    let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+
+    for p in 0..12 {
+        let v = signal(color, emphasis, p);
+        let angle = carrier(p, params);
+        y += v;
+        i += v * angle.cos();
+        q += v * angle.sin();
+    }
+
+    // demodulating halves the amplitude
+    decode(y / 12.0, i / 6.0, q / 6.0, params)
+}
+
+/// The PPU's video signal for a pixel at color cycle phase `p` (0-11), 0 is black and 1 white
+pub(crate) fn signal(color: u8, emphasis: u8, p: u8) -> f32 {
+    let hue = color & 0x0f;
+    let level = (color >> 4 & 3) as usize;
+    let in_phase = |hue: u8| (hue + p) % 12 < 6;
+
+    let (low, high) = match hue {
+        0x0 => (SIGNAL_HIGH[level], SIGNAL_HIGH[level]),
+        0xd => (SIGNAL_LOW[level], SIGNAL_LOW[level]),
+        0xe | 0xf => (BLACK, BLACK),
+        _ => (SIGNAL_LOW[level], SIGNAL_HIGH[level]),
+    };
+
+    let mut signal = if in_phase(hue) { high } else { low };
+
+    let attenuated = emphasis & 1 != 0 && in_phase(0xc)
+        || emphasis & 2 != 0 && in_phase(0x4)
+        || emphasis & 4 != 0 && in_phase(0x8);
+
+    if attenuated && hue < 0xe {
+        signal *= ATTENUATION;
+    }
+
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+/// Angle of the color subcarrier at phase `p`, in radians
+pub(crate) fn carrier(p: u8, params: &Params) -> f32 {
+    std::f32::consts::PI * (p as f32 + HUE_OFFSET) / 6.0 + params.hue.to_radians()
+}
+
+/// RGB from demodulated YIQ, with the TV's controls applied
+pub(crate) fn decode(y: f32, i: f32, q: f32, params: &Params) -> [u8; 3] {
+    let y = y * params.contrast + params.brightness;
+    let i = i * params.saturation * params.contrast;
+    let q = q * params.saturation * params.contrast;
+
+    [
+        y + 0.956 * i + 0.621 * q,
+        y - 0.272 * i - 0.647 * q,
+        y - 1.106 * i + 1.703 * q,
+    ].map(|c| (c.clamp(0.0, 1.0).powf(params.gamma / 2.2) * 255.0).round() as u8)
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+/// What RAM holds at power on, real consoles come up with semi random contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RamFill {
+    #[default]
+    Zeros,
+    Ones,
+    /// Reproducible noise from a seed
+    Random(u64),
+    /// 4 bytes of $00 then 4 bytes of $FF, what FCEUX and many consoles show
+    Pattern,
+}
+
+impl RamFill {
+    pub fn fill(self, ram: &mut [u8]) {
+        match self {
+            Self::Zeros => ram.fill(0),
+            Self::Ones => ram.fill(0xff),
+            Self::Random(seed) => {
+                // xorshift64, the state must not be 0
+                let mut state = seed | 1;
+
+                for b in ram {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    *b = (state >> 32) as u8;
+                }
+            },
+            Self::Pattern => {
+                for (i, b) in ram.iter_mut().enumerate() {
+                    *b = if i & 4 == 0 { 0x00 } else { 0xff };
+                }
+            },
+        }
+    }
+}
+
+/// Console state that isn't defined by hardware at power on
+#[derive(Debug, Clone, Default)]
+pub struct PowerOnConfig {
+    pub ram: RamFill,
+    /// Fill cartridge PRG RAM too, battery backed RAM is normally loaded over it afterwards
+    pub prg_ram: Option<RamFill>,
+}
+
+impl Nes {
+    /// Pull the RESET line like the console's reset button does. Unlike power on, RAM and most
+    /// registers are kept.
+    pub fn reset(&mut self) {
+        self.nmi = false;
+
+        // same sequence as an interrupt, with the pushes turned into reads
+        self.load(self.cpu.pc);
+        self.load(self.cpu.pc);
+
+        for _ in 0..3 {
+            self.load(0x0100 + self.cpu.s as u16);
+            self.cpu.s -= 1;
+        }
+
+        self.cpu.p |= 0x04;
+        self.cpu.pc = self.load_u16(0xfffc);
+
+        self.ppu.reset();
+        self.apu.reset();
+        self.cart.reset();
+    }
+}
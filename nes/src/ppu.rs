@@ -1,14 +1,88 @@
 use super::*;
 
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
+
+/// Lines and pixels cut off each edge of the picture, like a TV's bezel hides them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overscan {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Overscan {
+    /// Roughly what NTSC TVs hide
+    pub const TV: Overscan = Overscan { top: 8, bottom: 8, left: 0, right: 0 };
+
+    /// At most the whole frame but one pixel is cut off
+    pub fn clamped(self) -> Self {
+        let top = self.top.min(FRAME_HEIGHT - 1);
+        let left = self.left.min(FRAME_WIDTH - 1);
+        Self { top, bottom: self.bottom.min(FRAME_HEIGHT - 1 - top), left, right: self.right.min(FRAME_WIDTH - 1 - left) }
+    }
+
+    pub fn width(&self) -> usize {
+        let c = self.clamped();
+        FRAME_WIDTH - c.left - c.right
+    }
+
+    pub fn height(&self) -> usize {
+        let c = self.clamped();
+        FRAME_HEIGHT - c.top - c.bottom
+    }
+
+    /// The visible part of a frame sized image with `per_pixel` items for every pixel
+    pub fn crop<T: Copy>(&self, image: &[T], per_pixel: usize) -> Vec<T> {
+        let c = self.clamped();
+        let row = FRAME_WIDTH * per_pixel;
+
+        image.chunks(row)
+            .skip(c.top)
+            .take(self.height())
+            .flat_map(|line| &line[c.left * per_pixel..(FRAME_WIDTH - c.right) * per_pixel])
+            .copied()
+            .collect()
+    }
+}
+
+/// About 600ms, after which bits of the PPU I/O latch that weren't refreshed read back as 0
+const IO_LATCH_DECAY_FRAMES: usize = 36;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sprite {
+    x: u8,
+    attr: u8,
+    /// Pattern bits of the current row, already flipped horizontally if needed
+    lo: u8,
+    hi: u8,
+    zero: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Ppu {
     pub scanline: usize,
     pub cycle: usize,
+    /// Frames since power on
+    pub frame: usize,
+    /// Color subcarrier phase in thirds of a cycle at the first dot of the frame in the
+    /// framebuffer, for composite video filters
+    pub burst_phase: u8,
+    /// Same for the current dot, every dot is two thirds of a cycle
+    dot_phase: u8,
 
     pub ciram: CiRam,
+    /// Backdrop and palette colors, index with [`palette_index`]
+    pub palette: [u8; 32],
+    /// Sprite attribute memory, 4 bytes per sprite: y, tile, attributes, x
+    pub oam: [u8; 256],
+    pub oam_addr: u8,
+
+    /// Last rendered frame, row major. Each pixel is a 6 bit color with the emphasis bits above
+    /// it, see [`Ppu::emphasis`].
+    pub framebuffer: Box<[u16]>,
 
-    /// Right shifted 8 bits
-    pub base_nt: u8,
     pub ppudata_inc: u8,
     pub sp_pattern: bool,
     pub bg_pattern: bool,
@@ -20,27 +94,54 @@ pub struct Ppu {
     pub sp_show_left: bool,
     pub show_bg: bool,
     pub show_sp: bool,
-    // TODO: emphasize colors
+    /// Red, green and blue in bits 0-2, the top 3 bits of PPUMASK
+    pub emphasis: u8,
 
     pub sp_overflow: bool,
     pub sp0_hit: bool,
     pub vblank_flag: bool,
 
-    pub scroll: [u8; 2],
-    pub addr_status: u16,
-
     /// current VRAM address
-    v: u16,
+    pub v: u16,
     /// temp VRAM address (top left on-screen tile)
-    t: u16,
+    pub t: u16,
     /// fine x scroll
-    x: u8,
+    pub x: u8,
     /// write toggle
-    w: bool,
+    pub w: bool,
+
+    /// PPUDATA reads return the previous read's value
+    read_buffer: u8,
+    /// Value left on the CPU-PPU data bus, read back for bits no register drives
+    io_latch: u8,
+    /// Frame each bit of `io_latch` was last driven in
+    io_latch_frame: [usize; 8],
+
+    nt_byte: u8,
+    at_bits: u8,
+    pattern_lo: u8,
+    pattern_hi: u8,
+    /// Background pattern bits, the high byte is being drawn
+    bg_shift: [u16; 2],
+    /// Attribute bits, widened to match `bg_shift`
+    at_shift: [u16; 2],
+
+    /// Sprites on the current scanline
+    sprites: [Sprite; 8],
+    sprite_count: usize,
 
     /// odd frame toggle
     /// https://www.nesdev.org/wiki/PPU_frame_timing#Even/Odd_Frames
     frame_odd: bool,
+    /// After a reset PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR ignore writes until the pre-render
+    /// line
+    reset_ignore: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Ppu {
@@ -48,11 +149,18 @@ impl Ppu {
         Self {
             scanline: 0,
             cycle: 21,
+            frame: 0,
+            burst_phase: 0,
+            dot_phase: 0,
 
             ciram: [0; 2048],
+            palette: [0; 32],
+            oam: [0; 256],
+            oam_addr: 0,
+
+            framebuffer: vec![0; FRAME_WIDTH * FRAME_HEIGHT].into_boxed_slice(),
 
-            base_nt: 0,
-            ppudata_inc: 0,
+            ppudata_inc: 1,
             sp_pattern: false,
             bg_pattern: false,
             large_sprite: false,
@@ -63,67 +171,418 @@ impl Ppu {
             sp_show_left: false,
             show_bg: false,
             show_sp: false,
+            emphasis: 0,
 
             sp_overflow: false,
             sp0_hit: false,
             vblank_flag: false,
 
-            scroll: [0; 2],
-            addr_status: 0,
-
             v: 0,
             t: 0,
             x: 0,
             w: false,
 
+            read_buffer: 0,
+            io_latch: 0,
+            io_latch_frame: [0; 8],
+
+            nt_byte: 0,
+            at_bits: 0,
+            pattern_lo: 0,
+            pattern_hi: 0,
+            bg_shift: [0; 2],
+            at_shift: [0; 2],
+
+            sprites: [Sprite::default(); 8],
+            sprite_count: 0,
+
             frame_odd: false,
+            reset_ignore: false,
+        }
+    }
+
+    /// 64 bit FNV-1a of the framebuffer including emphasis bits, stable across versions and
+    /// platforms so it can be stored in movies and tests
+    pub fn frame_hash(&self) -> u64 {
+        self.visible_hash(&Overscan::default())
+    }
+
+    /// [`Ppu::frame_hash`] of the part `overscan` leaves visible
+    pub fn visible_hash(&self, overscan: &Overscan) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+
+        for px in overscan.crop(&self.framebuffer, 1) {
+            for b in px.to_le_bytes() {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+
+        hash
+    }
+
+    /// The framebuffer as little endian `u16`s, palette index in bits 0-5 and emphasis in 6-8
+    pub fn raw_frame(&self) -> Vec<u8> {
+        self.framebuffer.iter().flat_map(|px| px.to_le_bytes()).collect()
+    }
+
+    /// Registers the RESET line clears, memories and `v` are kept
+    pub fn reset(&mut self) {
+        self.ppudata_inc = 1;
+        self.sp_pattern = false;
+        self.bg_pattern = false;
+        self.large_sprite = false;
+        self.nmi_on_vblank = false;
+
+        self.grayscale = false;
+        self.bg_show_left = false;
+        self.sp_show_left = false;
+        self.show_bg = false;
+        self.show_sp = false;
+        self.emphasis = 0;
+
+        self.t = 0;
+        self.x = 0;
+        self.w = false;
+        self.read_buffer = 0;
+        self.frame_odd = false;
+        self.reset_ignore = true;
+    }
+
+    /// Nametable address and pixel offset of the top left corner of the screen
+    pub fn scroll_origin(&self) -> (u16, u8, u8) {
+        let x = ((self.t & 0x1f) << 3) as u8 | self.x;
+        let y = (((self.t >> 5) & 0x1f) << 3) as u8 | ((self.t >> 12) & 7) as u8;
+        (0x2000 | (self.t & 0xc00), x, y)
+    }
+
+    pub fn rendering(&self) -> bool {
+        self.show_bg || self.show_sp
+    }
+
+    fn status(&self) -> u8 {
+        ((self.sp_overflow as u8) << 5) | ((self.sp0_hit as u8) << 6) | ((self.vblank_flag as u8) << 7)
+    }
+
+    /// Drive the bits of `mask` on the I/O latch
+    fn latch(&mut self, value: u8, mask: u8) {
+        self.io_latch = (self.io_latch & !mask) | (value & mask);
+
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 {
+                self.io_latch_frame[bit] = self.frame;
+            }
+        }
+    }
+
+    fn decay_latch(&mut self) {
+        for bit in 0..8 {
+            if self.frame - self.io_latch_frame[bit] > IO_LATCH_DECAY_FRAMES {
+                self.io_latch &= !(1 << bit);
+            }
+        }
+    }
+
+    /// Color from palette RAM as it is output, with grayscale applied
+    fn color(&self, addr: u16) -> u8 {
+        self.palette[palette_index(addr)] & if self.grayscale { 0x30 } else { 0x3f }
+    }
+
+    fn increment_x(&mut self) {
+        if self.v & 0x1f == 31 {
+            self.v &= !0x1f;
+            self.v ^= 0x400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let mut y = (self.v & 0x3e0) >> 5;
+
+        if y == 29 {
+            y = 0;
+            self.v ^= 0x800;
+        } else if y == 31 {
+            // attribute rows wrap without switching nametables
+            y = 0;
+        } else {
+            y += 1;
+        }
+
+        self.v = (self.v & !0x3e0) | (y << 5);
+    }
+
+    fn reload_shifters(&mut self) {
+        self.bg_shift[0] = (self.bg_shift[0] & 0xff00) | self.pattern_lo as u16;
+        self.bg_shift[1] = (self.bg_shift[1] & 0xff00) | self.pattern_hi as u16;
+        self.at_shift[0] = (self.at_shift[0] & 0xff00) | if self.at_bits & 1 != 0 { 0xff } else { 0 };
+        self.at_shift[1] = (self.at_shift[1] & 0xff00) | if self.at_bits & 2 != 0 { 0xff } else { 0 };
+    }
+
+    fn shift(&mut self) {
+        for s in self.bg_shift.iter_mut().chain(self.at_shift.iter_mut()) {
+            *s <<= 1;
         }
     }
 }
 
 pub type CiRam = [u8; 2048];
 
-impl Nes<'_> {
+/// Index into [`Ppu::palette`] for a PPU address, $3F10/$3F14/$3F18/$3F1C mirror the backdrop
+/// entries below them
+pub fn palette_index(addr: u16) -> usize {
+    let i = addr as usize & 0x1f;
+    if i & 0x13 == 0x10 { i & 0x0f } else { i }
+}
+
+impl Nes {
     pub(crate) fn step_ppu(&mut self) {
+        let pre_render = self.pre_render_scanline();
+
         self.ppu.scanline += (self.ppu.cycle == 340) as usize;
         self.ppu.cycle = (self.ppu.cycle + 1) % 341;
+        self.ppu.dot_phase = (self.ppu.dot_phase + 2) % 3;
+
+        // only NTSC skips a dot on odd frames
+        let skip = self.region == Region::Ntsc && self.ppu.frame_odd && self.ppu.rendering();
 
-        if (self.ppu.frame_odd && self.ppu.scanline == 261 && self.ppu.cycle == 339) || self.ppu.scanline == 262 {
+        if (skip && self.ppu.scanline == pre_render && self.ppu.cycle == 339) || self.ppu.scanline > pre_render {
             self.ppu.scanline = 0;
             self.ppu.cycle = 0;
 
             self.ppu.frame_odd ^= true;
+            self.ppu.frame += 1;
+            self.ppu.burst_phase = self.ppu.dot_phase;
+            self.ppu.decay_latch();
         }
 
-        match (self.ppu.scanline, self.ppu.cycle) {
-            (0..=239 | 261, 0) => {}, // idle cycle
-            (0..=239 | 261, 1..=256) => { // tile fetch
-                self.ppu.vblank_flag = false;
-            },
-            (0..=239 | 261, 257..=320) => { // next scanline sprite fetch
+        let rendering = self.ppu.rendering();
+        let (scanline, cycle) = (self.ppu.scanline, self.ppu.cycle);
+
+        if rendering && (scanline < 240 || scanline == pre_render) {
+            self.render_dot();
+        }
+
+        if scanline == self.region.vblank_scanline() && cycle == 1 {
+            self.ppu.vblank_flag = true;
+            self.nmi |= self.ppu.nmi_on_vblank;
+        }
+
+        if scanline == pre_render && cycle == 1 {
+            self.ppu.reset_ignore = false;
+            self.ppu.vblank_flag = false;
+            self.ppu.sp0_hit = false;
+            self.ppu.sp_overflow = false;
+        }
+
+        if self.ppu.scanline < 240 && (1..=256).contains(&self.ppu.cycle) {
+            self.output_pixel(rendering);
+        }
+
+        if self.debugger.is_some() {
+            self.debug_ppu();
+        }
+    }
+
+    fn pre_render_scanline(&self) -> usize {
+        self.region.scanlines() - 1
+    }
+
+    fn ppu_fetch(&mut self, addr: u16) -> u8 {
+        self.cart.vmem_load(&self.ppu.ciram, addr & 0x3fff)
+    }
+
+    /// Background and sprite fetches and scroll updates of a dot on a rendering scanline
+    fn render_dot(&mut self) {
+        let dot = self.ppu.cycle;
+        let v = self.ppu.v;
+
+        match dot {
+            0 => {}, // idle cycle
+            2..=257 | 321..=337 => { // tile fetch
+                self.ppu.shift();
+
+                match (dot - 1) % 8 {
+                    0 => {
+                        self.ppu.reload_shifters();
+                        self.ppu.nt_byte = self.ppu_fetch(0x2000 | (v & 0xfff));
+                    },
+                    2 => {
+                        let at = self.ppu_fetch(0x23c0 | (v & 0xc00) | ((v >> 4) & 0x38) | ((v >> 2) & 7));
+                        self.ppu.at_bits = (at >> (((v >> 4) & 4) | (v & 2))) & 3;
+                    },
+                    4 => self.ppu.pattern_lo = self.ppu_fetch(self.bg_pattern_addr()),
+                    6 => self.ppu.pattern_hi = self.ppu_fetch(self.bg_pattern_addr() + 8),
+                    7 => self.ppu.increment_x(),
+                    _ => {},
+                }
             },
-            (0..=239 | 261, 321..=336) => { // next scanline first 2 tile fetch
+            1 => self.ppu.nt_byte = self.ppu_fetch(0x2000 | (v & 0xfff)),
+            338 | 340 => { // unused nametable fetches
+                self.ppu.nt_byte = self.ppu_fetch(0x2000 | (v & 0xfff));
             },
-            (0..=239 | 261, 337 | 339) => { // dummy fetch next scanline tile 3
+            _ => {},
+        }
+
+        match dot {
+            256 => self.ppu.increment_y(),
+            257 => { // next scanline sprite fetch
+                self.ppu.v = (self.ppu.v & !0x41f) | (self.ppu.t & 0x41f);
+                self.evaluate_sprites();
             },
-            (0..=239 | 261, 337..=340) => {}, // fetching
-            (241, 1) => { // vblank stuff
-                self.ppu.vblank_flag = true;
+            280..=304 if self.ppu.scanline == self.pre_render_scanline() => {
+                self.ppu.v = (self.ppu.v & !0x7be0) | (self.ppu.t & 0x7be0);
             },
-            (240..=260, _) => {}, // idle
-            _ => unreachable!(),
+            _ => {},
+        }
+
+        if (257..=320).contains(&dot) {
+            self.ppu.oam_addr = 0;
+        }
+    }
+
+    fn bg_pattern_addr(&self) -> u16 {
+        ((self.ppu.bg_pattern as u16) << 12) + self.ppu.nt_byte as u16 * 16 + (self.ppu.v >> 12)
+    }
+
+    /// Find and fetch the sprites of the next scanline
+    fn evaluate_sprites(&mut self) {
+        let height = if self.ppu.large_sprite { 16 } else { 8 };
+        // no sprites are drawn on the first line
+        let line = if self.ppu.scanline == self.pre_render_scanline() { None } else { Some(self.ppu.scanline) };
+        let mut count = 0;
+
+        for n in 0..64 {
+            let y = self.ppu.oam[n * 4] as usize;
+            let Some(row) = line.map(|l| l.wrapping_sub(y)).filter(|&r| r < height) else { continue };
+
+            if count == 8 {
+                self.ppu.sp_overflow = true;
+                break;
+            }
+
+            let tile = self.ppu.oam[n * 4 + 1] as u16;
+            let attr = self.ppu.oam[n * 4 + 2];
+            let row = if attr & 0x80 != 0 { height - 1 - row } else { row } as u16;
+
+            let addr = if self.ppu.large_sprite {
+                (tile & 1) * 0x1000 + (tile & 0xfe) * 16 + (row / 8) * 16 + row % 8
+            } else {
+                ((self.ppu.sp_pattern as u16) << 12) + tile * 16 + row
+            };
+
+            let mut lo = self.ppu_fetch(addr);
+            let mut hi = self.ppu_fetch(addr + 8);
+
+            if attr & 0x40 != 0 {
+                lo = lo.reverse_bits();
+                hi = hi.reverse_bits();
+            }
+
+            self.ppu.sprites[count] = Sprite { x: self.ppu.oam[n * 4 + 3], attr, lo, hi, zero: n == 0 };
+            count += 1;
+        }
+
+        // empty slots still fetch tile $FF, which mappers watching the PPU bus can see
+        for _ in count..8 {
+            let addr = if self.ppu.large_sprite { 0x1ff0 } else { ((self.ppu.sp_pattern as u16) << 12) + 0xff0 };
+            self.ppu_fetch(addr);
+            self.ppu_fetch(addr + 8);
+        }
+
+        self.ppu.sprite_count = count;
+    }
+
+    fn output_pixel(&mut self, rendering: bool) {
+        let x = self.ppu.cycle - 1;
+        let ppu = &mut self.ppu;
+
+        let color = if !rendering {
+            // with rendering off the backdrop is shown, unless v points into palette RAM
+            if ppu.v & 0x3f00 == 0x3f00 { ppu.color(ppu.v) } else { ppu.color(0) }
+        } else {
+            let mut bg = 0;
+            let mut bg_pal = 0;
+
+            if ppu.show_bg && (x >= 8 || ppu.bg_show_left) {
+                let bit = 15 - ppu.x;
+                bg = ((ppu.bg_shift[0] >> bit) & 1) | (((ppu.bg_shift[1] >> bit) & 1) << 1);
+                bg_pal = ((ppu.at_shift[0] >> bit) & 1) | (((ppu.at_shift[1] >> bit) & 1) << 1);
+            }
+
+            let mut sprite = None;
+
+            if ppu.show_sp && (x >= 8 || ppu.sp_show_left) {
+                sprite = ppu.sprites[..ppu.sprite_count].iter().find_map(|s| {
+                    let dx = x.wrapping_sub(s.x as usize);
+
+                    if dx >= 8 {
+                        return None;
+                    }
+
+                    let px = ((s.lo >> (7 - dx)) & 1) | (((s.hi >> (7 - dx)) & 1) << 1);
+                    (px != 0).then_some((px as u16, *s))
+                });
+            }
+
+            match sprite {
+                Some((px, s)) => {
+                    if s.zero && bg != 0 && x != 255 {
+                        ppu.sp0_hit = true;
+                    }
+
+                    if bg == 0 || s.attr & 0x20 == 0 {
+                        ppu.color(0x10 | ((s.attr as u16 & 3) << 2) | px)
+                    } else {
+                        ppu.color((bg_pal << 2) | bg)
+                    }
+                },
+                None if bg != 0 => ppu.color((bg_pal << 2) | bg),
+                None => ppu.color(0),
+            }
+        };
+
+        ppu.framebuffer[ppu.scanline * FRAME_WIDTH + x] = color as u16 | (ppu.emphasis as u16) << 6;
+    }
+
+    /// Increment `v` after a PPUDATA access
+    fn ppudata_increment(&mut self) {
+        if self.ppu.rendering() && (self.ppu.scanline < 240 || self.ppu.scanline == self.pre_render_scanline()) {
+            // during rendering the access collides with the fetch logic's own increments
+            self.ppu.increment_x();
+            self.ppu.increment_y();
+        } else {
+            self.ppu.v = (self.ppu.v + self.ppu.ppudata_inc as u16) & 0x7fff;
         }
     }
 
     pub(crate) fn store_ppu_mmio(&mut self, addr: u16, data: u8) {
-        match addr {
+        self.ppu.latch(data, 0xff);
+
+        if self.ppu.reset_ignore && matches!(addr & 0x2007, 0x2000 | 0x2001 | 0x2005 | 0x2006) {
+            return;
+        }
+
+        match addr & 0x2007 {
             0x2000 => {
-                self.ppu.base_nt = [0x20, 0x24, 0x28, 0x2c][data as usize & 3];
+                let nmi_on_vblank = self.ppu.nmi_on_vblank;
+
+                self.ppu.t = (self.ppu.t & !0xc00) | ((data as u16 & 3) << 10);
                 self.ppu.ppudata_inc = [1, 32][((data & 4) >> 2) as usize];
                 self.ppu.sp_pattern = data & 0x08 != 0;
                 self.ppu.bg_pattern = data & 0x10 != 0;
                 self.ppu.large_sprite = data & 0x20 != 0;
                 self.ppu.nmi_on_vblank = data & 0x80 != 0;
+
+                // enabling NMI during vblank triggers one right away
+                self.nmi |= !nmi_on_vblank && self.ppu.nmi_on_vblank && self.ppu.vblank_flag;
             },
             0x2001 => {
                 self.ppu.grayscale = data & 0x01 != 0;
@@ -131,52 +590,129 @@ impl Nes<'_> {
                 self.ppu.sp_show_left = data & 0x04 != 0;
                 self.ppu.show_bg = data & 0x08 != 0;
                 self.ppu.show_sp = data & 0x10 != 0;
+                self.ppu.emphasis = data >> 5;
+            },
+            0x2003 => self.ppu.oam_addr = data,
+            0x2004 => {
+                self.ppu.oam[self.ppu.oam_addr as usize] = data;
+                self.ppu.oam_addr += 1;
             },
-            // TODO: oam addr & data
             0x2005 => {
-                self.ppu.scroll[self.ppu.w as usize] = data;
+                if !self.ppu.w {
+                    self.ppu.t = (self.ppu.t & !0x1f) | (data as u16 >> 3);
+                    self.ppu.x = data & 7;
+                } else {
+                    self.ppu.t = (self.ppu.t & 0x8c1f) | ((data as u16 & 7) << 12) | ((data as u16 & 0xf8) << 2);
+                }
+
                 self.ppu.w ^= true;
             },
             0x2006 => {
-                if self.ppu.w {
-                    // TODO: bus conflict causing vertical scrolling weird things
-                }
-
                 if !self.ppu.w {
-                    // high
-                    self.ppu.addr_status &= 0x00ff;
-                    self.ppu.addr_status |= (data as u16) << 8;
+                    // high, bit 14 is cleared as well
+                    self.ppu.t = (self.ppu.t & 0x00ff) | ((data as u16 & 0x3f) << 8);
                 } else {
                     // low
-                    self.ppu.addr_status &= 0xff00;
-                    self.ppu.addr_status |= data as u16;
+                    self.ppu.t = (self.ppu.t & 0xff00) | data as u16;
+                    self.ppu.v = self.ppu.t;
                 }
 
                 self.ppu.w ^= true;
             },
             0x2007 => {
-                self.cart.vmem_store(&mut self.ppu.ciram, self.ppu.addr_status, data);
-                self.ppu.addr_status += self.ppu.ppudata_inc as u16;
+                let addr = self.ppu.v & 0x3fff;
+
+                if self.debugger.is_some() {
+                    self.debug_access(debugger::Space::Ppu, addr, data, true);
+                }
+
+                if addr >= 0x3f00 {
+                    self.ppu.palette[palette_index(addr)] = data & 0x3f;
+                } else {
+                    self.cart.vmem_store(&mut self.ppu.ciram, addr, data);
+                }
+
+                self.ppudata_increment();
             },
-            // TODO: oam dma
             _ => {},
         }
     }
 
-    pub(crate) fn load_ppu_mmio(&mut self, addr: u16) -> Result<u8, ()> {
-        match addr {
+    /// Copy a page of CPU memory to OAM, halting the CPU for 513 or 514 cycles
+    pub(crate) fn oam_dma(&mut self, page: u8) {
+        self.elapse_cycles(1 + self.cycles() % 2);
+
+        for i in 0..=0xff {
+            let data = self.load(((page as u16) << 8) | i);
+            self.store(0x2004, data);
+        }
+    }
+
+    /// Never fails, undriven bits come from the PPU's own I/O latch
+    pub(crate) fn load_ppu_mmio(&mut self, addr: u16) -> Result<u8, cart::OpenBus> {
+        match addr & 0x2007 {
             0x2002 => {
-                let r = ((self.ppu.sp_overflow as u8) << 5) | ((self.ppu.sp0_hit as u8) << 6) | ((self.ppu.vblank_flag as u8) << 7);
+                self.ppu.latch(self.ppu.status(), 0xe0);
                 self.ppu.vblank_flag = false;
                 self.ppu.w = false;
-                Ok(r)
+            },
+            0x2004 => {
+                let mut r = self.ppu.oam[self.ppu.oam_addr as usize];
+
+                // unimplemented attribute bits
+                if self.ppu.oam_addr & 3 == 2 {
+                    r &= 0xe3;
+                }
+
+                self.ppu.latch(r, 0xff);
             },
             0x2007 => {
-                let r = self.cart.vmem_load(&mut self.ppu.ciram, self.ppu.addr_status);
-                self.ppu.addr_status += self.ppu.ppudata_inc as u16;
-                Ok(r)
+                let addr = self.ppu.v & 0x3fff;
+
+                let r = if addr >= 0x3f00 {
+                    // palette reads aren't buffered, but refill the buffer from the nametable below
+                    self.ppu.read_buffer = self.cart.vmem_load(&self.ppu.ciram, addr & 0x2fff);
+                    self.ppu.latch(self.ppu.color(addr), 0x3f);
+                    self.ppu.io_latch
+                } else {
+                    let r = self.ppu.read_buffer;
+                    self.ppu.read_buffer = self.cart.vmem_load(&self.ppu.ciram, addr);
+                    self.ppu.latch(r, 0xff);
+                    r
+                };
+
+                if self.debugger.is_some() {
+                    self.debug_access(debugger::Space::Ppu, addr, r, false);
+                }
+
+                self.ppudata_increment();
             },
-            _ => Err(())
+            _ => {}, // write only
+        }
+
+        Ok(self.ppu.io_latch)
+    }
+
+    pub(crate) fn peek_ppu_mmio(&self, addr: u16) -> Result<u8, cart::OpenBus> {
+        let latch = self.ppu.io_latch;
+
+        Ok(match addr & 0x2007 {
+            0x2002 => self.ppu.status() | (latch & 0x1f),
+            0x2004 => self.ppu.oam[self.ppu.oam_addr as usize],
+            0x2007 if self.ppu.v & 0x3fff >= 0x3f00 => self.ppu.color(self.ppu.v) | (latch & 0xc0),
+            0x2007 => self.ppu.read_buffer,
+            _ => latch,
+        })
+    }
+
+    /// Read the PPU bus without side effects, for viewers and debuggers
+    pub fn ppu_peek(&self, addr: u16) -> u8 {
+        let addr = addr & 0x3fff;
+
+        if addr >= 0x3f00 {
+            self.ppu.palette[palette_index(addr)]
+        } else {
+            self.cart.vmem_peek(&self.ppu.ciram, addr)
         }
     }
 }
@@ -0,0 +1,145 @@
+//! RAM search, narrowing down where a game keeps a variable by how it changes between frames.
+//! Memory is read straight out of the RAM arrays so searching never disturbs the console.
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ram {
+    /// The 2 KiB at $0000
+    Internal,
+    /// Cartridge PRG RAM, the first 8 KiB of it at $6000
+    Prg,
+}
+
+impl Ram {
+    pub const ALL: [Ram; 2] = [Ram::Internal, Ram::Prg];
+
+    pub fn bytes(self, nes: &Nes) -> &[u8] {
+        match self {
+            Ram::Internal => &nes.iram,
+            Ram::Prg => nes.cart.memory(cart::Memory::PrgRam),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub ram: Ram,
+    pub offset: usize,
+}
+
+impl Location {
+    /// Where the CPU sees it, `None` for PRG RAM beyond the first bank
+    pub fn cpu_addr(&self) -> Option<u16> {
+        match self.ram {
+            Ram::Internal => Some(self.offset as u16),
+            Ram::Prg => (self.offset < 0x2000).then_some(0x6000 + self.offset as u16),
+        }
+    }
+
+    pub fn read(&self, nes: &Nes, view: View) -> Option<i64> {
+        let bytes = self.ram.bytes(nes).get(self.offset..self.offset + view.size())?;
+        Some(view.decode(bytes))
+    }
+
+    /// Raw cheat codes holding it at `value`, one per byte
+    pub fn cheat_codes(&self, view: View, value: i64) -> Option<Vec<String>> {
+        let addr = self.cpu_addr()?;
+        let codes = view.encode(value).into_iter().enumerate().map(|(i, byte)| format!("{:04X}:{byte:02X}", addr + i as u16));
+        Some(codes.collect())
+    }
+}
+
+/// How bytes are read as a number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct View {
+    /// 16 bits rather than 8
+    pub word: bool,
+    pub signed: bool,
+    pub big_endian: bool,
+}
+
+impl View {
+    pub fn size(&self) -> usize {
+        if self.word { 2 } else { 1 }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> i64 {
+        match (self.word, self.signed) {
+            (false, false) => bytes[0] as i64,
+            (false, true) => bytes[0] as i8 as i64,
+            (true, signed) => {
+                let raw = [bytes[0], bytes[1]];
+                let word = if self.big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) };
+                if signed { word as i16 as i64 } else { word as i64 }
+            },
+        }
+    }
+
+    /// The bytes of `value`, wrapped to the view's size
+    pub fn encode(&self, value: i64) -> Vec<u8> {
+        match (self.word, self.big_endian) {
+            (false, _) => vec![value as u8],
+            (true, false) => (value as u16).to_le_bytes().to_vec(),
+            (true, true) => (value as u16).to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// How a value has to compare to the last time to stay a candidate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    /// Changed by exactly this much
+    By(i64),
+    /// Is this now
+    Equals(i64),
+}
+
+impl Compare {
+    fn keeps(self, old: i64, new: i64) -> bool {
+        match self {
+            Compare::Unchanged => new == old,
+            Compare::Changed => new != old,
+            Compare::Increased => new > old,
+            Compare::Decreased => new < old,
+            Compare::By(n) => new - old == n,
+            Compare::Equals(n) => new == n,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub view: View,
+    /// Locations still in the running and their value when last filtered
+    pub candidates: Vec<(Location, i64)>,
+}
+
+impl Search {
+    /// Every location of every RAM is a candidate
+    pub fn start(nes: &Nes, view: View) -> Self {
+        let candidates = Ram::ALL.into_iter()
+            .flat_map(|ram| (0..(ram.bytes(nes).len() + 1).saturating_sub(view.size())).map(move |offset| Location { ram, offset }))
+            .map(|loc| (loc, loc.read(nes, view).unwrap()))
+            .collect();
+
+        Self { view, candidates }
+    }
+
+    /// Drop the candidates whose value now doesn't compare, remember the others' values
+    pub fn filter(&mut self, nes: &Nes, compare: Compare) {
+        let view = self.view;
+
+        self.candidates.retain_mut(|(loc, old)| match loc.read(nes, view) {
+            Some(new) if compare.keeps(*old, new) => {
+                *old = new;
+                true
+            },
+            _ => false,
+        });
+    }
+}
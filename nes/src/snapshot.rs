@@ -0,0 +1,74 @@
+use super::*;
+
+/// In-memory savestate, everything needed to continue emulation from an exact point
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    cpu: cpu::Cpu,
+    ppu: ppu::Ppu,
+    apu: apu::Apu,
+    region: Region,
+    iram: [u8; 0x800],
+    input: input::Input,
+    cart: Vec<u8>,
+
+    nmi: bool,
+    data_bus: u8,
+    master_clock: u64,
+    ppu_clock: u64,
+}
+
+impl Snapshot {
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn frame(&self) -> usize {
+        self.ppu.frame
+    }
+}
+
+impl Nes {
+    /// Capture the state between two instructions
+    pub fn snapshot(&self) -> Snapshot {
+        let mut cart = Vec::new();
+        self.cart.save_state(&mut cart);
+
+        let mut apu = self.apu.clone();
+        apu.samples.clear();
+
+        Snapshot {
+            cpu: self.cpu.clone(),
+            ppu: self.ppu.clone(),
+            apu,
+            region: self.region,
+            iram: self.iram,
+            input: self.input.clone(),
+            cart,
+
+            nmi: self.nmi,
+            data_bus: self.data_bus,
+            master_clock: self.master_clock,
+            ppu_clock: self.ppu_clock,
+        }
+    }
+
+    /// Go back to `snap`, which must come from a console with the same cartridge. Nothing changes
+    /// if the cartridge rejects the snapshot's state.
+    pub fn restore(&mut self, snap: &Snapshot) -> Result<(), cart::BadState> {
+        self.cart.load_state(&snap.cart)?;
+
+        self.cpu = snap.cpu.clone();
+        self.ppu = snap.ppu.clone();
+        self.apu = snap.apu.clone();
+        self.region = snap.region;
+        self.iram = snap.iram;
+        self.input = snap.input.clone();
+
+        self.nmi = snap.nmi;
+        self.data_bus = snap.data_bus;
+        self.master_clock = snap.master_clock;
+        self.ppu_clock = snap.ppu_clock;
+        self.fetched_bytes = 0;
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use std::io::BufRead;
 
 use super::*;
+use fixture::{rom_nes, RomCart, IDLE};
+
+mod golden;
 
 macro_rules! assert_eq_hex {
     ($a: expr, $b: expr, $($m: tt)*) => {{
@@ -15,25 +18,31 @@ fn run_nestest() {
     // reference: https://www.qmtpro.com/~nes/misc/nestest.txt
     // for rom and log check makefile
 
-    struct TestCart<'a>(&'a [u8]);
+    struct TestCart(Vec<u8>);
 
-    impl cart::Cartridge for TestCart<'_> {
-        fn load(&mut self, addr: u16) -> Result<u8, ()> {
+    impl cart::Cartridge for TestCart {
+        fn load(&mut self, addr: u16) -> Result<u8, cart::OpenBus> {
             Ok(self.0[(addr as usize - 0x8000) & 16383])
         }
 
-        fn store(&mut self, _addr: u16, _data: u8) -> Result<(), ()> { Err(()) }
+        fn store(&mut self, _addr: u16, _data: u8) -> Result<(), cart::OpenBus> { Err(cart::OpenBus) }
+
+        fn peek(&self, addr: u16) -> Result<u8, cart::OpenBus> {
+            Ok(self.0[(addr as usize - 0x8000) & 16383])
+        }
 
         fn vmem_load(&mut self, _ciram: &crate::ppu::CiRam, addr: u16) -> u8 { addr as u8 }
         fn vmem_store(&mut self, _ciram: &mut crate::ppu::CiRam, _addr: u16, _data: u8) {}
+        fn vmem_peek(&self, _ciram: &crate::ppu::CiRam, addr: u16) -> u8 { addr as u8 }
     }
 
     let rom = std::fs::read("../tests/nestest.nes").unwrap();
-    let mut cart = TestCart(&rom[16..16 + 16384]);
-    let mut nes = Nes::new(&mut cart, Some(0xc000));
+    let cart = TestCart(rom[16..16 + 16384].to_vec());
+    let mut nes = Nes::new(Box::new(cart), Some(0xc000), Region::Ntsc);
 
     let mut ref_log = std::io::BufReader::new(std::fs::File::open("../tests/nestest.log").unwrap());
     let mut log = String::new();
+    let mut trace = Vec::new();
 
     loop {
         log.clear();
@@ -41,16 +50,18 @@ fn run_nestest() {
 
         if log.is_empty() { break; }
 
-        let mut line = log.split_whitespace();
-        let pc = u16::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let a = u8::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let x = u8::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let y = u8::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let p = u8::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let s = u8::from_str_radix(line.next().unwrap(), 16).unwrap();
-        let cy = line.next().unwrap().parse::<usize>().unwrap();
-        let ppu_y = line.next().unwrap().parse::<usize>().unwrap();
-        let ppu_x = line.next().unwrap().parse::<usize>().unwrap();
+        // A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+        let regs = &log[48..];
+        let hex = |at: usize| u8::from_str_radix(&regs[at..at + 2], 16).unwrap();
+        let pc = u16::from_str_radix(&log[0..4], 16).unwrap();
+        let a = hex(2);
+        let x = hex(7);
+        let y = hex(12);
+        let p = hex(17);
+        let s = hex(23);
+        let cy = regs[42..].trim().parse::<usize>().unwrap();
+        let ppu_y = regs[30..33].trim().parse::<usize>().unwrap();
+        let ppu_x = regs[34..37].trim().parse::<usize>().unwrap();
 
         assert_eq_hex!(a, nes.cpu.a, "a on cycle {cy}");
         assert_eq_hex!(x, nes.cpu.x, "x on cycle {cy}");
@@ -58,10 +69,747 @@ fn run_nestest() {
         assert_eq_hex!(p, nes.cpu.p, "p on cycle {cy}");
         assert_eq_hex!(s, nes.cpu.s, "s on cycle {cy}");
         assert_eq_hex!(pc, nes.cpu.pc, "pc on cycle {cy}");
-        assert_eq!(cy, nes.cycles_ahead, "cycle count");
+        assert_eq!(cy, nes.cycles(), "cycle count");
         assert_eq!(ppu_x, nes.ppu.cycle, "ppu scanline cycle on cpu cycle {cy}");
         assert_eq!(ppu_y, nes.ppu.scanline, "scanline on cpu cycle {cy}");
 
-        nes.step_everything();
+        trace.clear();
+        nes.write_trace(&mut trace, true, false).unwrap();
+        assert_eq!(core::str::from_utf8(&trace).unwrap().trim_end(), log.trim_end(), "trace on cpu cycle {cy}");
+
+        nes.step_instruction();
+    }
+}
+
+fn run_until_halted(nes: &mut Nes) -> debugger::Break {
+    for _ in 0..10000 {
+        nes.step_instruction();
+
+        if let Some(b) = nes.debugger.as_ref().unwrap().hit() {
+            return b;
+        }
+    }
+
+    panic!("never halted");
+}
+
+#[test]
+fn debugger_conditions() {
+    let mut nes = rom_nes(&[], Region::Ntsc);
+    nes.cpu.a = 0x10;
+    nes.iram[0x300] = 5;
+    nes.iram[0xfe] = 0x34;
+    nes.iram[0xff] = 0x12;
+
+    let eval = |src: &str| debugger::Condition::parse(src).unwrap().eval(&nes, None);
+    assert!(eval("A == $10 && [$0300] > 4"));
+    assert!(eval("{$fe} == 0x1234"));
+    assert!(eval("1 + 2 * 3 == 7 && (1 + 2) * 3 == 9"));
+    assert!(eval("1 << 4 | 1 == 17 || 0"));
+    assert!(eval("!(pc < $c000) && -1 < 0 && ~0 == -1"));
+    assert!(!eval("a != 16"));
+
+    assert!(debugger::Condition::parse("1 +").is_err());
+    assert!(debugger::Condition::parse("[1").is_err());
+    assert!(debugger::Condition::parse("foo == 1").is_err());
+}
+
+#[test]
+fn debugger_breakpoints_and_stepping() {
+    let mut nes = rom_nes(&[
+        0x20, 0x10, 0xc0, // c000 jsr $c010
+        0xa9, 0x01,       // c003 lda #$01
+        0x8d, 0x00, 0x03, // c005 sta $0300
+        0x4c, 0x08, 0xc0, // c008 jmp $c008
+        0, 0, 0, 0, 0,
+        0xa2, 0x05,       // c010 ldx #$05
+        0x60,             // c012 rts
+    ], Region::Ntsc);
+    let mut dbg = debugger::Debugger::new();
+    assert!(!dbg.has_breakpoints());
+    dbg.breakpoints.push(debugger::Breakpoint::new(0xc010));
+    assert!(dbg.has_breakpoints());
+    dbg.watchpoints.push(debugger::Watchpoint::new(debugger::Space::Cpu, 0x0300, 0x03ff, false, true));
+    nes.debugger = Some(dbg);
+
+    assert_eq!(run_until_halted(&mut nes), debugger::Break::Breakpoint(0));
+    assert_eq!(nes.cpu.pc, 0xc010);
+    assert_eq!(nes.debugger.as_ref().unwrap().depth(), 1);
+
+    // halted CPU doesn't move
+    for _ in 0..100 { nes.step_instruction(); }
+    assert_eq!(nes.cpu.pc, 0xc010);
+
+    let d = nes.debugger.as_mut().unwrap();
+    d.step_out();
+    d.resume();
+    assert_eq!(run_until_halted(&mut nes), debugger::Break::Step);
+    assert_eq!(nes.cpu.pc, 0xc003);
+    assert_eq!(nes.cpu.x, 5);
+
+    nes.debugger.as_mut().unwrap().resume();
+    assert_eq!(run_until_halted(&mut nes), debugger::Break::Watchpoint { index: 0, addr: 0x300, value: 1, write: true });
+    assert_eq!(nes.cpu.pc, 0xc008);
+}
+
+#[test]
+fn interrupts() {
+    let mut prg = vec![0xea; 0x4000];
+    prg[..11].copy_from_slice(&[
+        0x58,             // c000 cli
+        0xa9, 0x80,       // c001 lda #$80
+        0x8d, 0x00, 0x20, // c003 sta $2000
+        0x00, 0xea,       // c006 brk
+        0x4c, 0x08, 0xc0, // c008 jmp $c008
+    ]);
+    // nmi at $d000, irq and brk at $e000, both handlers rti right away
+    prg[0x1000] = 0x40;
+    prg[0x2000] = 0x40;
+    prg[0x3ffa..0x3ffc].copy_from_slice(&[0x00, 0xd0]);
+    prg[0x3ffe..].copy_from_slice(&[0x00, 0xe0]);
+    let mut nes = rom_nes(&prg, Region::Ntsc);
+
+    for _ in 0..3 { nes.step_instruction(); }
+    let cycles = nes.cycles();
+    nes.step_instruction();
+    // brk skips its padding byte and pushes p with B set
+    assert_eq!(nes.cycles() - cycles, 7);
+    assert_eq!(nes.cpu.pc, 0xe000);
+    assert_eq!(nes.cpu.s, 0xfa);
+    assert_eq!(&nes.iram[0x1fb..0x1fe], &[0xb0, 0x08, 0xc0]); // N from the lda
+    assert_ne!(nes.cpu.p & 0x04, 0);
+    nes.step_instruction();
+    assert_eq!((nes.cpu.pc, nes.cpu.p & 0x04), (0xc008, 0));
+
+    // nmi at the start of vblank, with B clear
+    while nes.cpu.pc != 0xd000 { nes.step_instruction(); }
+    assert_eq!(nes.ppu.scanline, 241);
+    assert_eq!(nes.iram[0x1fb] & 0x30, 0x20);
+    nes.step_instruction();
+
+    // the APU's frame irq on the last step of the 4 step sequence
+    while nes.cpu.pc != 0xe000 { nes.step_instruction(); }
+    assert!(nes.cycles() >= 29829);
+    assert_eq!(nes.iram[0x1fb] & 0x30, 0x20);
+    assert_ne!(nes.cpu.p & 0x04, 0);
+
+    // masked by I, it stays pending
+    nes.cpu.p |= 0x04;
+    nes.cpu.pc = 0xc008;
+    for _ in 0..100 { nes.step_instruction(); }
+    assert!(nes.apu.irq());
+    assert!((0xc008..0xc00b).contains(&nes.cpu.pc));
+}
+
+#[test]
+fn ppu_registers() {
+    let mut nes = rom_nes(&[], Region::Ntsc);
+
+    // $3F10 mirrors the backdrop
+    nes.store(0x2006, 0x3f);
+    nes.store(0x2006, 0x10);
+    nes.store(0x2007, 0x2a);
+    assert_eq!(nes.ppu.palette[0], 0x2a);
+    assert_eq!(nes.ppu_peek(0x3f00), 0x2a);
+
+    // palette reads aren't buffered, others return the previous read
+    nes.store(0x2006, 0x3f);
+    nes.store(0x2006, 0x00);
+    assert_eq!(nes.load(0x2007), 0x2a);
+    nes.store(0x2006, 0x20);
+    nes.store(0x2006, 0x05);
+    assert_eq!(nes.load(0x2007), 0x00); // nametable below $3F00
+    assert_eq!(nes.load(0x2007), 0x05);
+    assert_eq!(nes.ppu.v, 0x2007);
+
+    // loopy t and fine x, registers are mirrored every 8 bytes
+    nes.store(0x2000, 0x03);
+    nes.store(0x200d, 0x7d);
+    nes.store(0x3ffd, 0x5e);
+    assert_eq!(nes.ppu.scroll_origin(), (0x2c00, 0x7d, 0x5e));
+    assert_eq!(nes.ppu.x, 5);
+
+    // undriven status bits come from the I/O latch
+    nes.store(0x2003, 0x15);
+    assert_eq!(nes.load(0x2002) & 0x1f, 0x15);
+}
+
+#[test]
+fn oam_dma() {
+    // the DMA waits one more cycle for alignment when $4014 is written on an odd cycle
+    for (odd_write, dma_cycles) in [(false, 513), (true, 514)] {
+        let mut nes = rom_nes(&[], Region::Ntsc);
+
+        for i in 0..0x100 {
+            nes.iram[0x200 + i] = i as u8;
+        }
+
+        nes.store(0x2003, 0x04);
+
+        // `cycles` counts finished cycles, the write is the one after
+        if (nes.cycles() + 1) % 2 != odd_write as usize {
+            nes.load(0x0000);
+        }
+
+        let cycles = nes.cycles();
+        nes.store(0x4014, 0x02);
+        assert_eq!(nes.cycles() - cycles, 1 + dma_cycles);
+
+        // OAMADDR is where the copy starts, wrapping around
+        assert_eq!(nes.ppu.oam[4], 0x00);
+        assert_eq!(nes.ppu.oam[3], 0xff);
+        assert_eq!(nes.ppu.oam_addr, 0x04);
+
+        let cycles = nes.cycles();
+        nes.poke(0x4014, 0x03);
+        assert_eq!(nes.cycles(), cycles);
+        assert_eq!(nes.ppu.oam[4], 0x00);
+    }
+}
+
+#[test]
+fn region_timing() {
+    for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+        let mut nes = rom_nes(&[], region);
+        nes.step_frame();
+
+        let start = nes.cycles();
+        nes.step_frame();
+        let cycles = (nes.cycles() - start) as f64;
+
+        // instructions take 2 cycles, so frames end on even cycles
+        let expected = region.cpu_hz() / region.frame_rate();
+        assert!((cycles - expected).abs() <= 2.0, "{region:?}: {cycles} cycles, expected {expected}");
+    }
+}
+
+#[test]
+fn apu() {
+    let run = |nes: &mut Nes, cycles: usize| for _ in 0..cycles { nes.load(0x0000); };
+
+    let mut nes = rom_nes(&[], Region::Ntsc);
+
+    // pulse 1 with a length of 2, counted down on half frames
+    nes.store(0x4015, 0x01);
+    nes.store(0x4017, 0x00);
+    nes.store(0x4003, 0x18);
+    assert_eq!(nes.load(0x4015) & 0x01, 0x01);
+    run(&mut nes, 14913);
+    assert_eq!(nes.load(0x4015) & 0x01, 0x01);
+    run(&mut nes, 14913);
+    assert_eq!(nes.load(0x4015) & 0x01, 0x00);
+
+    // disabling a channel clears its length
+    nes.store(0x4003, 0x08);
+    assert_eq!(nes.load(0x4015) & 0x01, 0x01);
+    nes.store(0x4015, 0x00);
+    assert_eq!(nes.load(0x4015) & 0x01, 0x00);
+
+    // Dendy has PAL frames but NTSC APU periods
+    for (region, irq_at, dmc_period) in [(Region::Ntsc, 29829, 54), (Region::Pal, 33253, 50), (Region::Dendy, 29829, 54)] {
+        let mut nes = rom_nes(&[], region);
+
+        nes.store(0x4017, 0x00);
+        let start = nes.cycles();
+        while !nes.apu.irq() { run(&mut nes, 1); }
+        assert_eq!(nes.cycles() - start, irq_at, "{region:?} frame irq");
+
+        // reading $4015 acknowledges it, inhibiting keeps it from coming back
+        assert_eq!(nes.load(0x4015) & 0x40, 0x40);
+        assert!(!nes.apu.irq());
+        nes.store(0x4017, 0x40);
+        run(&mut nes, 30000);
+        assert!(!nes.apu.irq());
+
+        // a looping DMC sample at the fastest rate changes the output level every period
+        nes.store(0x4011, 0x40);
+        nes.store(0x4010, 0x4f);
+        nes.store(0x4012, 0x00);
+        nes.store(0x4013, 0x00);
+        nes.store(0x4015, 0x10);
+
+        let mut changes = Vec::new();
+        let mut level = nes.apu.output();
+        while changes.len() < 4 {
+            run(&mut nes, 1);
+
+            if nes.apu.output() != level {
+                level = nes.apu.output();
+                changes.push(nes.cycles());
+            }
+        }
+
+        assert_eq!(changes[3] - changes[2], dmc_period, "{region:?} dmc period");
+    }
+}
+
+#[test]
+fn power_on_and_reset() {
+    let mut ram = [0; 16];
+    power::RamFill::Pattern.fill(&mut ram);
+    assert_eq!(ram[..8], [0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+
+    let mut a = [0; 64];
+    let mut b = [0; 64];
+    power::RamFill::Random(1234).fill(&mut a);
+    power::RamFill::Random(1234).fill(&mut b);
+    assert_eq!(a, b);
+    assert!(a.iter().any(|&x| x != a[0]));
+
+    let cart = RomCart::new(&[
+        0xa9, 0x42, // c000 lda #$42
+        0x4c, 0x02, 0xc0, // c002 jmp $c002
+    ]);
+    let config = power::PowerOnConfig { ram: power::RamFill::Ones, prg_ram: None };
+    let mut nes = Nes::with_config(Box::new(cart), None, Region::Ntsc, &config);
+    assert!(nes.iram.iter().all(|&x| x == 0xff));
+
+    for _ in 0..10 { nes.step_instruction(); }
+    nes.store(0x2000, 0x80);
+    nes.cpu.p &= !0x04;
+    nes.reset();
+
+    assert_eq!(nes.cpu.pc, 0xc000);
+    assert_eq!(nes.cpu.s, 0xfa);
+    assert_eq!(nes.cpu.a, 0x42);
+    assert_ne!(nes.cpu.p & 0x04, 0);
+    assert!(!nes.ppu.nmi_on_vblank);
+
+    // PPUCTRL ignores writes until the pre-render line
+    nes.store(0x2000, 0x80);
+    assert!(!nes.ppu.nmi_on_vblank);
+    nes.step_frame();
+    nes.store(0x2000, 0x80);
+    assert!(nes.ppu.nmi_on_vblank);
+}
+
+#[test]
+fn dummy_accesses() {
+    let mut nes = rom_nes(&[
+        0xa9, 0x20,       // c000 lda #$20
+        0x8d, 0x06, 0x20, // c002 sta $2006
+        0xa9, 0x00,       // c005 lda #$00
+        0x8d, 0x06, 0x20, // c007 sta $2006
+        0xa2, 0x08,       // c00a ldx #$08
+        0xbd, 0xff, 0x20, // c00c lda $20ff,x
+        0xee, 0x07, 0x20, // c00f inc $2007
+        0x4c, 0x12, 0xc0, // c012 jmp $c012
+    ], Region::Ntsc);
+    for _ in 0..5 { nes.step_instruction(); }
+    assert_eq!(nes.ppu.v, 0x2000);
+
+    // page crossing reads $2007 before the high byte is fixed, then $2107
+    let start = nes.cycles();
+    nes.step_instruction();
+    assert_eq!(nes.cycles() - start, 5);
+    assert_eq!(nes.ppu.v, 0x2002);
+
+    // read, write back the old value, write the new one
+    let start = nes.cycles();
+    nes.step_instruction();
+    assert_eq!(nes.cycles() - start, 6);
+    assert_eq!(nes.ppu.v, 0x2005);
+}
+
+#[test]
+fn open_bus() {
+    let mut nes = rom_nes(&[
+        0xad, 0x00, 0x50, // c000 lda $5000
+        0x8d, 0x00, 0x00, // c003 sta $0000
+        0x4c, 0x06, 0xc0, // c006 jmp $c006
+    ], Region::Ntsc);
+
+    // nothing answers at $5000, the high byte of the operand is still on the bus
+    nes.step_instruction();
+    assert_eq!(nes.cpu.a, 0x50);
+    assert_eq!(nes.peek(0x4000), 0x50);
+
+    // writes drive the bus too, and $4015 leaves bit 5 alone
+    nes.cpu.a = 0xff;
+    nes.step_instruction();
+    assert_eq!(nes.peek(0x5000), 0xff);
+    assert_eq!(nes.peek(0x4015), 0x20 | nes.apu.peek_status());
+}
+
+/// Reads the first controller into $00 and shows it as the backdrop color
+const JOYPAD_BACKDROP: &[u8] = &[
+    0xa9, 0x01,       // c000 lda #$01
+    0x8d, 0x16, 0x40, // c002 sta $4016
+    0xa9, 0x00,       // c005 lda #$00
+    0x8d, 0x16, 0x40, // c007 sta $4016
+    0xa2, 0x08,       // c00a ldx #$08
+    0xad, 0x16, 0x40, // c00c lda $4016
+    0x4a,             // c00f lsr a
+    0x26, 0x00,       // c010 rol $00
+    0xca,             // c012 dex
+    0xd0, 0xf7,       // c013 bne $c00c
+    0xa9, 0x3f,       // c015 lda #$3f
+    0x8d, 0x06, 0x20, // c017 sta $2006
+    0xa9, 0x00,       // c01a lda #$00
+    0x8d, 0x06, 0x20, // c01c sta $2006
+    0xa5, 0x00,       // c01f lda $00
+    0x29, 0x3f,       // c021 and #$3f
+    0x8d, 0x07, 0x20, // c023 sta $2007
+    0x4c, 0x00, 0xc0, // c026 jmp $c000
+];
+
+#[test]
+fn movies() {
+    let prg = JOYPAD_BACKDROP;
+
+    let mut nes = rom_nes(prg, Region::Ntsc);
+    let mut movie = movie::Movie::new(movie::Anchor::PowerOn(Default::default()), Region::Ntsc);
+
+    for (i, buttons) in [0, input::joypad::A, input::joypad::START | input::joypad::RIGHT, input::joypad::B].into_iter().enumerate() {
+        nes.input.host.buttons[0] = buttons;
+        movie.record_frame(&mut nes, i == 2);
+        // shifted out A first, so the byte ends up reversed
+        assert_eq!(nes.iram[0], buttons.reverse_bits());
+    }
+
+    assert_ne!(movie.frames[0].hash, movie.frames[2].hash);
+
+    let replay = |movie: &movie::Movie| movie.verify(Box::new(RomCart::new(prg))).1;
+
+    let native = movie::Movie::parse(&movie.write().unwrap()).unwrap();
+    assert_eq!(native.frames, movie.frames);
+    assert_eq!(replay(&native), Ok(()));
+
+    let mut tampered = native.clone();
+    tampered.frames[1].joypads[0] = input::joypad::UP;
+    assert_eq!(replay(&tampered), Err(1));
+
+    // neither stores frame hashes
+    let mut inputs = movie.frames.clone();
+    inputs.iter_mut().for_each(|f| f.hash = None);
+    assert_eq!(movie::Movie::parse_fm2(&movie.write_fm2().unwrap()).unwrap().frames, inputs);
+    assert_eq!(movie::Movie::parse_bizhawk(&movie.write_bizhawk().unwrap()).unwrap().frames, inputs);
+
+    // snapshot anchors replay from where they were taken, with only the controllers plugged
+    let mut movie = movie::Movie::from_snapshot(&nes).unwrap();
+    for buttons in [input::joypad::SELECT, input::joypad::DOWN] {
+        nes.input.host.buttons[0] = buttons;
+        movie.record_frame(&mut nes, false);
+    }
+
+    let (replayed, result) = movie.verify(Box::new(RomCart::new(prg)));
+    assert_eq!(result, Ok(()));
+    assert_eq!(replayed.unwrap().ppu.frame, nes.ppu.frame);
+    assert!(movie.write().is_err());
+
+    nes.input.plug(input::Slot::Port(1), input::Device::Zapper);
+    assert!(movie::Movie::from_snapshot(&nes).is_err());
+}
+
+#[test]
+fn golden_frames() {
+    // palette, scrolling at 0, then background and sprites on
+    let mut prg = vec![
+        0xa9, 0x3f,       // c000 lda #$3f
+        0x8d, 0x06, 0x20, // c002 sta $2006
+        0xa9, 0x00,       // c005 lda #$00
+        0x8d, 0x06, 0x20, // c007 sta $2006
+        0xa2, 0x00,       // c00a ldx #$00
+        0xbd, 0x00, 0xc1, // c00c lda $c100,x
+        0x8d, 0x07, 0x20, // c00f sta $2007
+        0xe8,             // c012 inx
+        0xe0, 0x20,       // c013 cpx #$20
+        0xd0, 0xf5,       // c015 bne $c00c
+        0xa9, 0x00,       // c017 lda #$00
+        0x8d, 0x05, 0x20, // c019 sta $2005
+        0x8d, 0x05, 0x20, // c01c sta $2005
+        0xa9, 0x1e,       // c01f lda #$1e
+        0x8d, 0x01, 0x20, // c021 sta $2001
+        0x4c, 0x24, 0xc0, // c024 jmp $c024
+    ];
+    prg.resize(0x100, 0xea);
+    prg.extend([
+        0x0f, 0x16, 0x27, 0x18, 0x0f, 0x1a, 0x30, 0x27, 0x0f, 0x01, 0x21, 0x31, 0x0f, 0x06, 0x16, 0x26,
+        0x0f, 0x2c, 0x12, 0x30, 0x0f, 0x15, 0x25, 0x35, 0x0f, 0x09, 0x19, 0x29, 0x0f, 0x04, 0x14, 0x24,
+    ]);
+
+    // the test cartridge's CHR and nametables read back the low address byte, stripes of tiles
+    let mut nes = rom_nes(&prg, Region::Ntsc);
+    golden::run(&mut nes, 3, None);
+    golden::check(&nes, "render", golden::Golden::Png);
+
+    // the hash only covers what's left after cropping
+    let overscan = ppu::Overscan { left: 8, right: 8, ..ppu::Overscan::TV };
+    assert_eq!((overscan.width(), overscan.height()), (240, 224));
+    golden::check_visible(&nes, "render_overscan", golden::Golden::Hash, &overscan);
+    nes.ppu.framebuffer[0] ^= 1;
+    golden::check_visible(&nes, "render_overscan", golden::Golden::Hash, &overscan);
+
+    let mut nes = rom_nes(JOYPAD_BACKDROP, Region::Ntsc);
+    let mut input = movie::Movie::new(movie::Anchor::PowerOn(Default::default()), Region::Ntsc);
+    input.frames = [0, input::joypad::SELECT, input::joypad::SELECT | input::joypad::UP].map(|b| movie::Frame { joypads: [b, 0], ..Default::default() }).to_vec();
+    golden::run(&mut nes, 4, Some(&input));
+    golden::check(&nes, "joypad_backdrop", golden::Golden::Hash);
+}
+
+#[test]
+fn palettes() {
+    use palette::{Palette, Params, Preset};
+
+    let ntsc = Palette::generate(Preset::Ntsc2C02, &Params::default());
+    assert_eq!(ntsc.colors.len(), 512);
+    assert_eq!(ntsc.colors[0x0f], [0, 0, 0]);
+    assert_eq!(ntsc.colors[0x20], [0xff, 0xff, 0xff]);
+    // $16 is red, emphasizing red keeps it brighter than emphasizing blue
+    let [r, g, b] = ntsc.colors[0x16];
+    assert!(r > g && r > b);
+    assert!(ntsc.colors[0x16 | 1 << 6][0] > ntsc.colors[0x16 | 4 << 6][0]);
+
+    // PAL swaps the red and green emphasis bits
+    let pal = Palette::generate(Preset::Pal2C07, &Params::default());
+    assert_eq!(pal.colors[0x16 | 2 << 6], ntsc.colors[0x16 | 1 << 6]);
+
+    // RGB PPUs turn a channel fully on
+    let rgb = Palette::generate(Preset::Rgb2C03, &Params::default());
+    assert_eq!(rgb.colors[0x0f | 4 << 6], [0, 0, 0xff]);
+
+    let pal_file = ntsc.write_pal(512);
+    assert_eq!(Palette::parse_pal(&pal_file), Ok(ntsc.clone()));
+    let small = Palette::parse_pal(&ntsc.write_pal(64)).unwrap();
+    assert_eq!(small.colors[..64], ntsc.colors[..64]);
+    assert!(Palette::parse_pal(&[0; 100]).is_err());
+
+    let gray = Palette::generate(Preset::Ntsc2C02, &Params { saturation: 0.0, ..Default::default() });
+    assert!(gray.colors[..64].iter().all(|&[r, g, b]| r == g && g == b));
+}
+
+#[test]
+fn ntsc_filter() {
+    use palette::{Palette, Params, Preset as PalettePreset};
+    use ntsc::Preset;
+
+    let params = Params::default();
+    let palette = Palette::generate(PalettePreset::Ntsc2C02, &params);
+    let flat = vec![0x16; ppu::FRAME_WIDTH * ppu::FRAME_HEIGHT];
+    let at = |rgba: &[u8], x: usize, y: usize| {
+        let i = (y * ntsc::WIDTH + x) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2]]
+    };
+
+    let rgb = ntsc::filter(&flat, 0, Preset::Rgb, &palette, &params);
+    assert_eq!(rgb.len(), ntsc::WIDTH * ppu::FRAME_HEIGHT * 4);
+    assert_eq!(at(&rgb, 100, 100), palette.colors[0x16]);
+
+    // away from the edges a flat area decodes close to its palette color
+    let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 24);
+
+    for preset in [Preset::Composite, Preset::SVideo] {
+        let out = ntsc::filter(&flat, 1, preset, &palette, &params);
+        assert!(close(at(&out, 250, 120), palette.colors[0x16]), "{preset:?} {:?}", at(&out, 250, 120));
     }
+
+    let mono = ntsc::filter(&flat, 2, Preset::Monochrome, &palette, &params);
+    assert!((0..ntsc::WIDTH).all(|x| matches!(at(&mono, x, 10), [r, g, b] if r == g && g == b)));
+
+    // the burst phase shifts the artifacts
+    let stripes: Vec<u16> = (0..ppu::FRAME_WIDTH * ppu::FRAME_HEIGHT).map(|i| if i % 2 == 0 { 0x30 } else { 0x0f }).collect();
+    let a = ntsc::filter(&stripes, 0, Preset::Composite, &palette, &params);
+    let b = ntsc::filter(&stripes, 1, Preset::Composite, &palette, &params);
+    assert_ne!(a, b);
+
+    // with rendering off every frame is 89342 dots, a third of a cycle over
+    let mut nes = rom_nes(JOYPAD_BACKDROP, Region::Ntsc);
+    let mut phases = [0; 4].map(|_| {
+        nes.step_frame();
+        nes.ppu.burst_phase
+    });
+    assert_eq!(phases[3], phases[0]);
+    phases[..3].sort();
+    assert_eq!(phases[..3], [0, 1, 2]);
+}
+
+#[test]
+fn zapper() {
+    let mut nes = rom_nes(IDLE, Region::Ntsc);
+    nes.input.ports[1] = Box::new(input::zapper::Zapper { aim: Some((100, 100)), trigger: true });
+
+    let sense_at = |nes: &mut Nes, backdrop: u8, line: usize| {
+        nes.ppu.palette[0] = backdrop;
+        nes.step_frame();
+
+        while nes.ppu.scanline != line {
+            nes.step_instruction();
+        }
+
+        nes.peek(0x4017) & 0x18
+    };
+
+    // light is sensed for a while after the beam drew a bright pixel, trigger in bit 4
+    assert_eq!(sense_at(&mut nes, 0x30, 90), 0x18);
+    assert_eq!(sense_at(&mut nes, 0x30, 110), 0x10);
+    assert_eq!(sense_at(&mut nes, 0x30, 140), 0x18);
+    assert_eq!(sense_at(&mut nes, 0x0f, 110), 0x18);
+    assert_eq!(sense_at(&mut nes, 0x16, 110), 0x18);
+
+    nes.input.ports[1] = Box::new(input::zapper::Zapper { aim: None, trigger: false });
+    assert_eq!(sense_at(&mut nes, 0x30, 110), 0x08);
+}
+
+#[test]
+fn input_devices() {
+    use input::{Device, HostInput, Slot};
+
+    let mut nes = rom_nes(IDLE, Region::Ntsc);
+
+    let read = |nes: &mut Nes, out: u8, addr: u16, n: usize| -> Vec<u8> {
+        nes.store(0x4016, out | 1);
+        nes.store(0x4016, out);
+        (0..n).map(|_| nes.load(addr) & 0x1f).collect()
+    };
+    let serial = |bits: &[u8], shift: u8| bits.iter().rev().fold(0u32, |acc, b| acc << 1 | (b >> shift & 1) as u32);
+
+    // players 1 and 3 on $4016, then the signature and 1s
+    nes.input.plug(Slot::Port(0), Device::FourScore);
+    assert_eq!(nes.input.devices(), [Device::FourScore, Device::FourScore, Device::Unplugged]);
+    nes.input.update(HostInput { buttons: [0x81, 0x02, 0x40, 0x10], ..Default::default() });
+    assert_eq_hex!(serial(&read(&mut nes, 0, 0x4016, 32), 0), 0xff08_4081u32, "four score port 1");
+    assert_eq_hex!(serial(&read(&mut nes, 0, 0x4017, 24), 0), 0x04_1002u32, "four score port 2");
+
+    // either port on its own again puts a controller back in the other
+    nes.input.plug(Slot::Port(1), Device::PowerPad);
+    assert_eq!(nes.input.devices(), [Device::Joypad, Device::PowerPad, Device::Unplugged]);
+    nes.input.update(HostInput { power_pad: 1 << 1 | 1 << 8 | 1 << 11, ..Default::default() });
+    let bits = read(&mut nes, 0, 0x4017, 8);
+    assert_eq_hex!(serial(&bits, 3), 0x09u32, "power pad d3"); // buttons 2 and 9
+    assert_eq_hex!(serial(&bits, 4), 0xf4u32, "power pad d4"); // button 12, then 1s
+
+    nes.input.plug(Slot::Port(1), Device::SnesMouse);
+    nes.input.update(HostInput { motion: (-3, 5), primary: true, ..Default::default() });
+    assert_eq_hex!(serial(&read(&mut nes, 0, 0x4017, 32), 0).reverse_bits(), 0x0041_0583u32, "mouse");
+    // motion is only reported once
+    assert_eq_hex!(serial(&read(&mut nes, 0, 0x4017, 32), 0).reverse_bits(), 0x0041_0000u32, "mouse");
+
+    // rows are scanned by toggling the column, keys read as 0
+    nes.input.plug(Slot::Expansion, Device::Keyboard);
+    let (i, bit) = input::keyboard::key_position("E").unwrap();
+    assert_eq!((i, bit), (13, 2));
+    let mut host = HostInput::default();
+    host.keyboard[i] |= bit;
+    nes.input.update(host);
+    nes.store(0x4016, 0x05);
+    for _ in 0..6 {
+        nes.store(0x4016, 0x06);
+        nes.store(0x4016, 0x04);
+    }
+    nes.store(0x4016, 0x06);
+    assert_eq_hex!(nes.load(0x4017) & 0x1e, 0x1a, "keyboard row 6 column 1");
+}
+
+#[test]
+fn autofire() {
+    use input::{autofire::*, joypad};
+
+    let m = Macro::parse("jump", "Right+A, Right x2, ...").unwrap();
+    assert_eq!(m.steps, [(joypad::RIGHT | joypad::A, 1), (joypad::RIGHT, 2)]);
+    assert_eq!(Macro::parse("jump", &m.write()), Ok(m.clone()));
+    assert!(Macro::parse("bad", "Right, ..., A").is_err());
+    assert!(Macro::parse("bad", "Jump").is_err());
+    assert_eq!(Pattern::parse("xx.").unwrap().write(), "xx.");
+
+    let mut autofire = Autofire::default();
+    autofire.players[0].turbo_b = Pattern::parse("xx.").unwrap();
+    autofire.start(1, m);
+
+    // turbo starts pressed the frame it's held, the macro repeats
+    let frames: Vec<[u8; 4]> = (0..7).map(|i| {
+        let mut buttons = [joypad::UP, 0, 0, 0];
+        let turbo = if i == 4 { 0 } else { joypad::A | joypad::B };
+        autofire.apply(&mut buttons, [turbo, 0, 0, 0]);
+        buttons
+    }).collect();
+
+    let (up, a, b, right) = (joypad::UP, joypad::A, joypad::B, joypad::RIGHT);
+    assert_eq!(frames.iter().map(|f| f[0]).collect::<Vec<_>>(), [up | a | b, up | b, up | a, up | b, up, up | a | b, up | b]);
+    assert_eq!(frames.iter().map(|f| f[1]).collect::<Vec<_>>(), [right | a, right, right, right | a, right, right, right | a]);
+
+    // one-shot macros end
+    autofire.start(0, Macro::parse("tap", "Start, - x2").unwrap());
+    let mut buttons = [0; 4];
+    autofire.apply(&mut buttons, [0; 4]);
+    assert_eq!(buttons[0], joypad::START);
+    (0..2).for_each(|_| autofire.apply(&mut [0; 4], [0; 4]));
+    assert!(autofire.players[0].running.is_some());
+    autofire.apply(&mut [0; 4], [0; 4]);
+    assert!(autofire.players[0].running.is_none());
+}
+
+#[test]
+fn cheats() {
+    use cheat::{Cheat, Patch};
+
+    // Super Mario Bros. infinite lives
+    assert_eq!(Patch::decode("SXIOPO"), Ok(Patch { addr: 0x91d9, value: 0xad, compare: None }));
+    assert_eq!(Patch::decode("sxiopo"), Patch::decode("SXIOPO"));
+    assert_eq!(Patch::decode("0075:09"), Ok(Patch { addr: 0x0075, value: 0x09, compare: None }));
+    assert_eq!(Patch::decode("c001:ea:00"), Ok(Patch { addr: 0xc001, value: 0xea, compare: Some(0x00) }));
+    assert!(Patch::decode("c001:ea:00:1").is_err());
+    assert!(Patch::decode("SXIOP").is_err());
+
+    // 8 letters add a compare value, Pro Action Rocky codes always have one
+    let gg = Patch::decode("SXIOPOAZ").unwrap();
+    assert_eq!((gg.addr, gg.compare.is_some()), (0x91d9, true));
+    assert_eq!(Patch::decode("38A4F6B2"), Ok(Patch { addr: 0xc410, value: 0x5d, compare: Some(0xeb) }));
+
+    let mut nes = rom_nes(IDLE, Region::Ntsc);
+    nes.iram[0x75] = 3;
+
+    nes.cheats = ["0075:09", "c001:ea:12", "c000:ea:4c"].map(|c| Patch::decode(c).unwrap()).to_vec();
+    assert_eq!(nes.load(0x0075), 0x09);
+    assert_eq!(nes.peek(0x0075), 0x09);
+    // only where the byte underneath matches
+    assert_eq!(nes.load(0xc000), 0xea);
+    assert_eq!(nes.load(0xc001), 0x00);
+    assert_eq!(nes.iram[0x75], 3);
+
+    nes.cheats.clear();
+    assert_eq!(nes.load(0xc000), 0x4c);
+
+    let cheats = vec![Cheat::new("sxiopo", "Infinite lives").unwrap(), Cheat { enabled: false, ..Cheat::new("0075:09", "").unwrap() }];
+    let cht = cheat::write_cht(&cheats);
+    assert_eq!(cht, "+ SXIOPO Infinite lives\n- 0075:09\n");
+    assert_eq!(cheat::parse_cht(&cht), Ok(cheats));
+    assert!(cheat::parse_cht("SXIOPO").is_err());
+    assert!(cheat::parse_cht("é SXIOPO").is_err());
+}
+
+#[test]
+fn ram_search() {
+    use search::{Compare, Location, Ram, Search, View};
+
+    let mut nes = rom_nes(IDLE, Region::Ntsc);
+    nes.iram[0x10] = 5;
+    nes.iram[0x20] = 5;
+
+    let mut search = Search::start(&nes, View::default());
+    assert_eq!(search.candidates.len(), 0x800);
+
+    nes.iram[0x10] = 8;
+    nes.iram[0x20] = 4;
+    search.filter(&nes, Compare::Changed);
+    assert_eq!(search.candidates.len(), 2);
+
+    let mut unchanged = search.clone();
+    unchanged.filter(&nes, Compare::Unchanged);
+    assert_eq!(unchanged.candidates.len(), 2);
+    nes.iram[0x10] = 11;
+    unchanged.filter(&nes, Compare::By(3));
+    assert_eq!(unchanged.candidates, [(Location { ram: Ram::Internal, offset: 0x10 }, 11)]);
+
+    search.filter(&nes, Compare::Decreased);
+    assert!(search.candidates.is_empty());
+
+    // signed big endian words, found by value, made into cheats
+    let view = View { word: true, signed: true, big_endian: true };
+    nes.iram[0x30..0x32].copy_from_slice(&[0xff, 0xfe]);
+    let mut search = Search::start(&nes, view);
+    search.filter(&nes, Compare::Equals(-2));
+    let (loc, value) = search.candidates[0];
+    assert_eq!((loc.offset, value), (0x30, -2));
+    assert_eq!(loc.cheat_codes(view, 300), Some(vec!["0030:01".to_string(), "0031:2C".to_string()]));
+    assert_eq!(loc.read(&nes, View { word: true, ..Default::default() }), Some(0xfeff));
 }
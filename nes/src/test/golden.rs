@@ -0,0 +1,123 @@
+//! Screen level regression tests against images in `tests/golden`. `UPDATE_GOLDEN=1 cargo test`
+//! (re)writes them, failures leave `<name>.actual.png` and `<name>.diff.png` next to the golden.
+//! Both only cover what the overscan leaves visible.
+
+use std::{fs::File, io::BufWriter, path::{Path, PathBuf}};
+
+use crate::{movie, palette::Palette, ppu::Overscan, Nes};
+
+/// Run `frames` frames, feeding `movie`'s input while it lasts
+pub fn run(nes: &mut Nes, frames: usize, movie: Option<&movie::Movie>) {
+    for i in 0..frames {
+        match movie.and_then(|m| m.frames.get(i)) {
+            Some(frame) => movie::Movie::run_frame(nes, frame),
+            None => nes.step_frame(),
+        }
+    }
+}
+
+/// What a golden file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Golden {
+    /// `<name>.png`, failures get a diff image
+    Png,
+    /// `<name>.hash`, [`crate::ppu::Ppu::visible_hash`] in hex, for frames not worth an image
+    Hash,
+}
+
+/// Compare the current frame with its golden file
+pub fn check(nes: &Nes, name: &str, golden: Golden) {
+    check_visible(nes, name, golden, &Overscan::default());
+}
+
+pub fn check_visible(nes: &Nes, name: &str, golden: Golden, overscan: &Overscan) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let png = dir.join(format!("{name}.png"));
+    let hash = dir.join(format!("{name}.hash"));
+    let actual = overscan.crop(&Palette::default().framebuffer_rgba(&nes.ppu.framebuffer), 4);
+    let actual_hash = nes.ppu.visible_hash(overscan);
+    let size = [overscan.width(), overscan.height()];
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(&dir).unwrap();
+
+        match golden {
+            Golden::Png => write_png(&png, &actual, size),
+            Golden::Hash => std::fs::write(&hash, format!("{actual_hash:016x}\n")).unwrap(),
+        }
+
+        return;
+    }
+
+    let actual_png = dir.join(format!("{name}.actual.png"));
+    let path = if golden == Golden::Png { &png } else { &hash };
+
+    if !path.exists() {
+        panic!("{name}: no {}, run with UPDATE_GOLDEN=1 to create it", path.display());
+    }
+
+    match golden {
+        Golden::Png => {
+            let expected = read_png(&png);
+
+            if expected != actual {
+                let diff = dir.join(format!("{name}.diff.png"));
+                let wrong = write_diff(&diff, &expected, &actual, size);
+                write_png(&actual_png, &actual, size);
+                panic!("{name}: {wrong} pixels differ from the golden image, see {}", diff.display());
+            }
+        },
+        Golden::Hash => {
+            let expected = std::fs::read_to_string(&hash).unwrap();
+
+            if u64::from_str_radix(expected.trim(), 16) != Ok(actual_hash) {
+                write_png(&actual_png, &actual, size);
+                panic!("{name}: frame hash {actual_hash:016x} isn't {}, see {}", expected.trim(), actual_png.display());
+            }
+        },
+    }
+}
+
+fn write_png(path: &Path, rgba: &[u8], [width, height]: [usize; 2]) {
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(rgba).unwrap();
+}
+
+fn read_png(path: &Path) -> Vec<u8> {
+    let mut decoder = png::Decoder::new(File::open(path).unwrap());
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+
+    match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect(),
+        other => panic!("{}: unsupported color type {other:?}", path.display()),
+    }
+}
+
+/// Differing pixels in red over a darkened copy of the expected image, returns how many differ
+fn write_diff(path: &Path, expected: &[u8], actual: &[u8], size: [usize; 2]) -> usize {
+    let mut wrong = 0;
+    let mut diff = Vec::with_capacity(actual.len());
+
+    for (e, a) in expected.chunks(4).zip(actual.chunks(4)) {
+        if e == a {
+            diff.extend([e[0] / 4, e[1] / 4, e[2] / 4, 0xff]);
+        } else {
+            wrong += 1;
+            diff.extend([0xff, 0, 0, 0xff]);
+        }
+    }
+
+    // a size mismatch shows up as a shorter diff
+    wrong += expected.len().abs_diff(actual.len()) / 4;
+    diff.resize(actual.len(), 0xff);
+    write_png(path, &diff, size);
+    wrong
+}
@@ -0,0 +1,73 @@
+use std::io;
+
+use super::*;
+
+/// Execution trace logger, writes a line in Nintendulator / nestest format before every
+/// instruction
+pub struct Tracer {
+    pub out: Box<dyn io::Write>,
+    /// Include the PPU scanline and dot
+    pub ppu: bool,
+    /// Include the state reported by [`cart::Cartridge::trace_state`]
+    pub mapper: bool,
+}
+
+impl Tracer {
+    pub fn new(out: impl io::Write + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            ppu: true,
+            mapper: false,
+        }
+    }
+}
+
+impl Nes {
+    /// Write the trace line of the instruction at the current `pc`, e.g.
+    ///
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+    pub fn write_trace(&self, out: &mut dyn io::Write, ppu: bool, mapper: bool) -> io::Result<()> {
+        let pc = self.cpu.pc;
+        let (dis, size) = self.disassemble(pc, true);
+
+        let bytes = (0..size)
+            .map(|i| format!("{:02X}", self.peek(pc + i)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // illegal opcodes have their `*` in the column before the mnemonic
+        let pad = if dis.starts_with('*') { "" } else { " " };
+
+        write!(
+            out,
+            "{pc:04X}  {bytes:<8} {pad}{dis:<w$}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.cpu.a, self.cpu.x, self.cpu.y, self.cpu.p, self.cpu.s,
+            w = 33 - pad.len(),
+        )?;
+
+        if ppu {
+            write!(out, " PPU:{:3},{:3}", self.ppu.scanline, self.ppu.cycle)?;
+        }
+
+        write!(out, " CYC:{}", self.cycles())?;
+
+        if mapper {
+            let mut state = String::new();
+            self.cart.trace_state(&mut state);
+
+            if !state.is_empty() {
+                write!(out, " {state}")?;
+            }
+        }
+
+        writeln!(out)
+    }
+
+    pub(crate) fn trace(&mut self) {
+        if let Some(mut t) = self.tracer.take() {
+            // a broken writer shouldn't bring the emulator down, just stop tracing
+            if self.write_trace(&mut t.out, t.ppu, t.mapper).is_ok() {
+                self.tracer = Some(t);
+            }
+        }
+    }
+}
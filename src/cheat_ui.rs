@@ -0,0 +1,139 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use nes::{cheat::{self, Cheat, Patch}, Nes};
+
+/// Cheats of the running ROM, kept next to it in a `.cht` file
+#[derive(Default)]
+pub struct CheatWindow {
+    pub open: bool,
+    pub cheats: Vec<Cheat>,
+    path: Option<PathBuf>,
+    code: String,
+    name: String,
+    status: Option<String>,
+}
+
+impl CheatWindow {
+    pub fn load(&mut self, rom: &Path) -> io::Result<()> {
+        let path = rom.with_extension("cht");
+
+        // without a path a file that didn't load is never overwritten
+        self.cheats = match std::fs::read_to_string(&path) {
+            Ok(text) => cheat::parse_cht(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        self.path = Some(path);
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        self.cheats.clear();
+        self.path = None;
+        self.status = None;
+    }
+
+    pub fn active(&self) -> Vec<Patch> {
+        self.cheats.iter().filter(|c| c.enabled).map(|c| c.patch).collect()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+
+        // no file for ROMs that never had cheats
+        if self.cheats.is_empty() && !path.exists() {
+            return Ok(());
+        }
+
+        std::fs::write(path, cheat::write_cht(&self.cheats))
+    }
+
+    /// Turn on another cheat, for other windows
+    pub fn add(&mut self, cheat: Cheat, nes: &mut Nes) {
+        self.cheats.push(cheat);
+        self.changed(nes);
+    }
+
+    fn changed(&mut self, nes: &mut Nes) {
+        nes.cheats = self.active();
+
+        if let Err(e) = self.save() {
+            self.status = Some(format!("failed to save cheats: {e}"));
+        }
+    }
+
+    pub fn menu(&mut self, ui: &imgui::Ui) {
+        ui.menu_item_config("Cheats").build_with_ref(&mut self.open);
+    }
+
+    /// Cheats stay off while a movie is `locked` in, so it replays the same everywhere
+    pub fn draw(&mut self, ui: &imgui::Ui, nes: &mut Nes, locked: bool) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+
+        ui.window("Cheats").opened(&mut open).build(|| {
+            if locked {
+                ui.text_disabled("cheats are off while a movie runs");
+                return;
+            }
+
+            let mut removed = None;
+
+            for (i, c) in self.cheats.iter_mut().enumerate() {
+                let _id = ui.push_id_usize(i);
+                changed |= ui.checkbox(&c.code, &mut c.enabled);
+
+                if !c.name.is_empty() {
+                    ui.same_line();
+                    ui.text(&c.name);
+                }
+
+                ui.same_line();
+
+                if ui.small_button("Remove") {
+                    removed = Some(i);
+                }
+            }
+
+            if let Some(i) = removed {
+                self.cheats.remove(i);
+                changed = true;
+            }
+
+            ui.separator();
+            ui.input_text("Code", &mut self.code).hint("SXIOPO, 38A4F6B2 or 0075:09").build();
+            ui.input_text("Name", &mut self.name).build();
+
+            if ui.button("Add") {
+                match Cheat::new(&self.code, &self.name) {
+                    Ok(c) => {
+                        self.cheats.push(c);
+                        self.code.clear();
+                        self.name.clear();
+                        self.status = None;
+                        changed = true;
+                    },
+                    Err(e) => self.status = Some(e),
+                }
+            }
+
+            if let Some(status) = &self.status {
+                ui.text(status);
+            }
+        });
+
+        self.open = open;
+
+        if changed {
+            self.changed(nes);
+        }
+    }
+}
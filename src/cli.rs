@@ -0,0 +1,302 @@
+use std::path::{Path, PathBuf};
+
+use crate::script::Script;
+
+pub const USAGE: &str = "\
+usage: rustyness [rom] [options]
+
+headless options, no window is opened when any of these are given:
+  --verify <movie>       replay a movie and compare its frame hashes
+  --screenshot <png>     save the last frame as PNG
+  --raw <file>           save the last frame's palette indices and emphasis bits
+  --video <y4m>          record every frame as Y4M
+  --audio <wav>          record the sound, only together with --video
+  --frames <n>           frames to run before saving, defaults to the movie's length or 1
+  --movie <movie>        input to feed while running
+  --aspect               stretch screenshots to the TV's pixel aspect ratio
+  --palette <pal>        colors for screenshots and video, defaults to the generated 2C02 palette
+  --ntsc <filter>        composite, svideo, rgb or mono signal filter for screenshots and video
+  --scale <scaler>       scale2x, scale3x, smooth2x, smooth3x, diagonal2, diagonal3 or diagonal4 upscaling
+  --scanlines <0-1>      darken every other line by this much
+  --overscan <t,b,l,r>   pixels cropped off each edge, defaults to the configured ones
+
+other options:
+  --script <lua>         run a Lua script alongside the game, emu.exit() ends headless runs early";
+
+/// Command line options
+#[derive(Default)]
+pub struct Args {
+    pub rom: Option<PathBuf>,
+    /// Replay a movie without a window and compare its frame hashes
+    pub verify: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub raw: Option<PathBuf>,
+    pub video: Option<PathBuf>,
+    pub audio: Option<PathBuf>,
+    pub frames: Option<usize>,
+    pub movie: Option<PathBuf>,
+    pub aspect: bool,
+    pub palette: Option<PathBuf>,
+    pub ntsc: Option<nes::ntsc::Preset>,
+    pub scaler: Option<crate::scale::Scaler>,
+    pub scanlines: f32,
+    pub overscan: Option<nes::ppu::Overscan>,
+    pub script: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = std::env::args_os().skip(1);
+        let mut ret = Self::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+            match arg.to_str() {
+                Some("--verify") => ret.verify = Some(value("--verify")?.into()),
+                Some("--screenshot") => ret.screenshot = Some(value("--screenshot")?.into()),
+                Some("--raw") => ret.raw = Some(value("--raw")?.into()),
+                Some("--video") => ret.video = Some(value("--video")?.into()),
+                Some("--audio") => ret.audio = Some(value("--audio")?.into()),
+                Some("--movie") => ret.movie = Some(value("--movie")?.into()),
+                Some("--script") => ret.script = Some(value("--script")?.into()),
+                Some("--palette") => ret.palette = Some(value("--palette")?.into()),
+                Some("--ntsc") => {
+                    ret.ntsc = Some(match value("--ntsc")?.to_str() {
+                        Some("composite") => nes::ntsc::Preset::Composite,
+                        Some("svideo") => nes::ntsc::Preset::SVideo,
+                        Some("rgb") => nes::ntsc::Preset::Rgb,
+                        Some("mono") => nes::ntsc::Preset::Monochrome,
+                        _ => return Err("--ntsc needs composite, svideo, rgb or mono".to_string()),
+                    });
+                },
+                Some("--scale") => {
+                    use crate::scale::Scaler;
+
+                    ret.scaler = Some(match value("--scale")?.to_str() {
+                        Some("scale2x") => Scaler::Scale2x,
+                        Some("scale3x") => Scaler::Scale3x,
+                        Some("smooth2x") => Scaler::Smooth2x,
+                        Some("smooth3x") => Scaler::Smooth3x,
+                        Some("diagonal2") => Scaler::Diagonal(2),
+                        Some("diagonal3") => Scaler::Diagonal(3),
+                        Some("diagonal4") => Scaler::Diagonal(4),
+                        _ => return Err("--scale needs scale2x, scale3x, smooth2x, smooth3x, diagonal2, diagonal3 or diagonal4".to_string()),
+                    });
+                },
+                Some("--overscan") => {
+                    let n = value("--overscan")?;
+                    ret.overscan = Some(n.to_str().and_then(crate::config::parse_overscan).ok_or("--overscan needs top,bottom,left,right")?);
+                },
+                Some("--scanlines") => {
+                    let n = value("--scanlines")?;
+                    ret.scanlines = n.to_str().and_then(|n| n.parse().ok()).filter(|n| (0.0..=1.0).contains(n)).ok_or("--scanlines needs a number from 0 to 1")?;
+                },
+                Some("--frames") => {
+                    let n = value("--frames")?;
+                    ret.frames = Some(n.to_str().and_then(|n| n.parse().ok()).ok_or("--frames needs a number")?);
+                },
+                Some("--aspect") => ret.aspect = true,
+                Some(flag) if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+                _ if ret.rom.is_none() => ret.rom = Some(arg.into()),
+                _ => return Err("more than one ROM given".to_string()),
+            }
+        }
+
+        if ret.audio.is_some() && ret.video.is_none() {
+            return Err("--audio needs --video".to_string());
+        }
+
+        if ret.script.is_some() && ret.verify.is_some() {
+            return Err("--script can't be used with --verify".to_string());
+        }
+
+        if ret.headless() && ret.rom.is_none() {
+            return Err("headless runs need a ROM".to_string());
+        }
+
+        Ok(ret)
+    }
+
+    pub fn headless(&self) -> bool {
+        self.verify.is_some() || self.screenshot.is_some() || self.raw.is_some() || self.video.is_some()
+    }
+}
+
+/// Headless mode, returns the process exit code
+pub fn run(args: &Args) -> i32 {
+    let result = match &args.verify {
+        Some(movie) => verify(args, movie),
+        None => run_frames(args).map(|()| true),
+    };
+
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{e}");
+            2
+        },
+    }
+}
+
+/// Power on, or at the movie's start if there is one. Also returns the ROM's checksum.
+fn power_on(args: &Args, movie: Option<&nes::movie::Movie>) -> std::io::Result<(nes::Nes, String)> {
+    let rom = crate::read_rom(args.rom.as_ref().unwrap())?;
+    let checksum = rom.checksum.clone();
+
+    let Some(movie) = movie else {
+        let region = rom.region;
+        return Ok((rom.power_on(region, &Default::default()), checksum));
+    };
+
+    if let Some(warning) = crate::movie::checksum_warning(movie, &rom.checksum) {
+        eprintln!("warning: {warning}");
+    }
+
+    let nes = movie.start(Box::new(rom.mapper))
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "the movie's savestate doesn't fit this ROM"))?;
+    Ok((nes, checksum))
+}
+
+fn verify(args: &Args, movie: &Path) -> std::io::Result<bool> {
+    let movie = crate::movie::load(movie)?;
+    let rom = crate::read_rom(args.rom.as_ref().unwrap())?;
+    let checksum = rom.checksum.clone();
+    let checked = movie.frames.iter().filter(|f| f.hash.is_some()).count();
+
+    if let Some(warning) = crate::movie::checksum_warning(&movie, &checksum) {
+        eprintln!("warning: {warning}");
+    }
+
+    let (nes, result) = movie.verify(Box::new(rom.mapper));
+    let ok = match result {
+        Ok(()) => {
+            println!("{} frames replayed, {checked} hashes matched", movie.frames.len());
+            true
+        },
+        Err(frame) => {
+            println!("desync on frame {frame}");
+            false
+        },
+    };
+
+    if let Some(nes) = nes {
+        save_frame(args, &nes, &video(args, &checksum)?)?;
+    }
+
+    Ok(ok)
+}
+
+fn run_frames(args: &Args) -> std::io::Result<()> {
+    let movie = args.movie.as_deref().map(crate::movie::load).transpose()?;
+    let (mut nes, checksum) = power_on(args, movie.as_ref())?;
+    let frames = args.frames.unwrap_or(movie.as_ref().map_or(1, |m| m.frames.len()));
+    let mut video = video(args, &checksum)?;
+    let mut script = match &args.script {
+        Some(path) => Some(Script::load(path).map_err(|e| std::io::Error::other(format!("{}: {e}", path.display())))?),
+        None => None,
+    };
+
+    let mut recorder = match &args.video {
+        Some(path) => {
+            // without --audio the sound still has to go somewhere
+            let audio = args.audio.clone().unwrap_or_else(|| path.with_extension("wav"));
+            Some(crate::record::Recorder::create(path, &audio, &nes, &video.render(&nes))?)
+        },
+        None => None,
+    };
+
+    for i in 0..frames {
+        let frame = movie.as_ref().and_then(|m| m.frames.get(i));
+
+        match &mut script {
+            Some(script) => {
+                if script.exited() {
+                    break;
+                }
+
+                script.set_palette(&video.palette);
+                script_frame(script, &mut nes, frame)?;
+                video.overlay = script.overlay();
+            },
+            None => match frame {
+                Some(frame) => nes::movie::Movie::run_frame(&mut nes, frame),
+                None => nes.step_frame(),
+            },
+        }
+
+        if let Some(recorder) = &mut recorder {
+            recorder.frame(&nes, &video)?;
+        }
+
+        nes.apu.samples.clear();
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+
+    if let Some(script) = script {
+        script.finish(Some(&mut nes)).map_err(std::io::Error::other)?;
+    }
+
+    save_frame(args, &nes, &video)
+}
+
+/// One frame of a script, a movie's input wins over `joypad.set`
+fn script_frame(script: &mut Script, nes: &mut nes::Nes, frame: Option<&nes::movie::Frame>) -> std::io::Result<()> {
+    let mut host = nes.input.host.clone();
+    script.before_frame(nes, &mut host.buttons).map_err(std::io::Error::other)?;
+
+    if script.take_reset() {
+        nes.reset();
+    }
+
+    match frame {
+        Some(frame) => nes::movie::Movie::start_frame(nes, frame),
+        None => nes.input.update(host),
+    }
+
+    script.step_frame(nes).map_err(std::io::Error::other)?;
+    script.after_frame(nes).map_err(std::io::Error::other)?;
+
+    for message in script.take_messages() {
+        println!("{message}");
+    }
+
+    Ok(())
+}
+
+fn video(args: &Args, checksum: &str) -> std::io::Result<crate::video::Video> {
+    let overscan = match args.overscan {
+        Some(overscan) => overscan,
+        None => crate::config::Config::load()?.overscan(Some(checksum)),
+    };
+
+    let mut video = crate::video::Video {
+        ntsc: args.ntsc,
+        scaler: args.scaler,
+        scanlines: args.scanlines,
+        overscan,
+        ..Default::default()
+    };
+
+    if let Some(path) = &args.palette {
+        video.load_pal(path)?;
+    }
+
+    Ok(video)
+}
+
+fn save_frame(args: &Args, nes: &nes::Nes, video: &crate::video::Video) -> std::io::Result<()> {
+    if let Some(path) = &args.screenshot {
+        crate::screenshot::save_png(path, nes, video, args.aspect)?;
+    }
+
+    if let Some(path) = &args.raw {
+        crate::screenshot::save_raw(path, nes)?;
+    }
+
+    Ok(())
+}
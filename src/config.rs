@@ -0,0 +1,155 @@
+//! Settings kept between runs, a text file of `key values` lines. Lines after a `[<checksum>]`
+//! header override the defaults for the ROM with that checksum, the rest of the header line
+//! names the game for whoever edits the file.
+//!
+//! ```text
+//! overscan 8 8 0 0
+//! turbo_a x.
+//! turbo_b xx..
+//! macro Run and jump: Right+B x30, Right+B+A x10, ...
+//!
+//! [base64:lV3p2gZ9oGV4rZmhtpS8Zw==] Some Game
+//! overscan 16 8 8 0
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::PathBuf,
+};
+
+use nes::{
+    input::autofire::{Macro, Pattern},
+    ppu::Overscan,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Top, bottom, left and right
+    pub overscan: Overscan,
+    /// Player 1's turbo patterns
+    pub turbo_a: Pattern,
+    pub turbo_b: Pattern,
+    pub macros: Vec<Macro>,
+    /// Overrides by ROM checksum
+    pub games: BTreeMap<String, Game>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Game {
+    pub name: String,
+    pub overscan: Option<Overscan>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/rustyness/rustyness.cfg`, `~/.config` without it, the working directory
+    /// without either
+    pub fn path() -> PathBuf {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        match dir {
+            Some(dir) => dir.join("rustyness").join("rustyness.cfg"),
+            None => PathBuf::from("rustyness.cfg"),
+        }
+    }
+
+    /// The saved settings, defaults if there are none yet
+    pub fn load() -> io::Result<Self> {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(text) => Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, self.write())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut ret = Self::default();
+        let mut game: Option<String> = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let err = |e: &str| format!("line {}: {e}", i + 1);
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let (checksum, name) = header.split_once(']').ok_or_else(|| err("unclosed ["))?;
+                let entry = ret.games.entry(checksum.to_string()).or_default();
+                entry.name = name.trim().to_string();
+                game = Some(checksum.to_string());
+                continue;
+            }
+
+            let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+            match key {
+                "overscan" => {
+                    let overscan = parse_overscan(value).ok_or_else(|| err("overscan needs top, bottom, left and right"))?;
+
+                    match &game {
+                        Some(checksum) => ret.games.get_mut(checksum).unwrap().overscan = Some(overscan),
+                        None => ret.overscan = overscan,
+                    }
+                },
+                "turbo_a" | "turbo_b" | "macro" if game.is_some() => return Err(err(&format!("`{key}` can't be set per game"))),
+                "turbo_a" => ret.turbo_a = Pattern::parse(value).map_err(|e| err(&e))?,
+                "turbo_b" => ret.turbo_b = Pattern::parse(value).map_err(|e| err(&e))?,
+                "macro" => {
+                    let (name, steps) = value.split_once(':').ok_or_else(|| err("macro needs `name: steps`"))?;
+                    ret.macros.push(Macro::parse(name.trim(), steps).map_err(|e| err(&e))?);
+                },
+                _ => return Err(err(&format!("unknown setting `{key}`"))),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    pub fn write(&self) -> String {
+        let overscan = |o: &Overscan| format!("overscan {} {} {} {}\n", o.top, o.bottom, o.left, o.right);
+        let mut out = overscan(&self.overscan);
+        out += &format!("turbo_a {}\nturbo_b {}\n", self.turbo_a.write(), self.turbo_b.write());
+
+        for m in &self.macros {
+            out += &format!("macro {}: {}\n", m.name, m.write());
+        }
+
+        for (checksum, game) in &self.games {
+            out += &format!("\n[{checksum}] {}\n", game.name);
+
+            if let Some(o) = &game.overscan {
+                out += &overscan(o);
+            }
+        }
+
+        out
+    }
+
+    pub fn overscan(&self, checksum: Option<&str>) -> Overscan {
+        checksum
+            .and_then(|checksum| self.games.get(checksum))
+            .and_then(|game| game.overscan)
+            .unwrap_or(self.overscan)
+    }
+}
+
+/// Four numbers, top, bottom, left and right, separated by spaces or commas
+pub fn parse_overscan(text: &str) -> Option<Overscan> {
+    let edges: Vec<usize> = text.split([' ', ',']).filter(|s| !s.is_empty()).map(|s| s.parse().ok()).collect::<Option<_>>()?;
+    let [top, bottom, left, right] = edges[..] else { return None };
+    Some(Overscan { top, bottom, left, right })
+}
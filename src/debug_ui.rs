@@ -0,0 +1,261 @@
+use nes::cart::Memory;
+use nes::debugger::{Break, Breakpoint, Call, Debugger};
+use nes::Nes;
+
+const MEMORIES: [&str; 5] = ["CPU RAM", "PRG ROM", "PRG RAM", "CHR", "CIRAM"];
+const HIGHLIGHT: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+
+/// CPU, disassembly, memory and stack windows
+#[derive(Default)]
+pub struct DebugWindows {
+    pub cpu: bool,
+    pub disassembly: bool,
+    pub memory: bool,
+    pub stack: bool,
+
+    memory_kind: usize,
+    memory_cursor: usize,
+    /// Scroll the disassembly when this changes
+    listing_pc: u16,
+}
+
+impl DebugWindows {
+    pub fn menu(&mut self, ui: &imgui::Ui) {
+        ui.menu_item_config("CPU").build_with_ref(&mut self.cpu);
+        ui.menu_item_config("Disassembly").build_with_ref(&mut self.disassembly);
+        ui.menu_item_config("Memory").build_with_ref(&mut self.memory);
+        ui.menu_item_config("Stack").build_with_ref(&mut self.stack);
+    }
+
+    pub fn draw(&mut self, ui: &imgui::Ui, nes: &mut Nes) {
+        // the debugger is checked on every bus access and dot, only keep it while it's used
+        if self.cpu || self.disassembly || self.stack {
+            nes.debugger.get_or_insert_with(Debugger::new);
+        } else if nes.debugger.as_ref().is_some_and(|d| !d.has_breakpoints()) {
+            nes.debugger = None;
+        }
+
+        if self.cpu {
+            ui.window("CPU").opened(&mut self.cpu).build(|| cpu_window(ui, nes));
+        }
+
+        if self.disassembly {
+            let mut open = true;
+            ui.window("Disassembly").opened(&mut open).build(|| self.disassembly_window(ui, nes));
+            self.disassembly = open;
+        }
+
+        if self.memory {
+            let mut open = true;
+            ui.window("Memory").opened(&mut open).build(|| self.memory_window(ui, nes));
+            self.memory = open;
+        }
+
+        if self.stack {
+            ui.window("Stack").opened(&mut self.stack).build(|| stack_window(ui, nes));
+        }
+    }
+
+    fn memory_window(&mut self, ui: &imgui::Ui, nes: &mut Nes) {
+        ui.combo_simple_string("Memory", &mut self.memory_kind, &MEMORIES);
+
+        let data: &mut [u8] = match self.memory_kind {
+            0 => &mut nes.iram,
+            1 => nes.cart.memory_mut(Memory::PrgRom),
+            2 => nes.cart.memory_mut(Memory::PrgRam),
+            3 => nes.cart.memory_mut(Memory::Chr),
+            _ => &mut nes.ppu.ciram,
+        };
+
+        if data.is_empty() {
+            ui.text_disabled("Not present on this cartridge");
+            return;
+        }
+
+        self.memory_cursor = self.memory_cursor.min(data.len() - 1);
+        ui.text(format!("{:05X}:", self.memory_cursor));
+        ui.same_line();
+        ui.set_next_item_width(48.0);
+        ui.input_scalar("##value", &mut data[self.memory_cursor])
+            .chars_hexadecimal(true)
+            .display_format("%02X")
+            .build();
+
+        ui.child_window("hex").build(|| {
+            let rows = data.len().div_ceil(16);
+
+            for row in imgui::ListClipper::new(rows as i32).begin(ui).iter() {
+                let row = row as usize * 16;
+                ui.text(format!("{row:05X}:"));
+
+                for (i, b) in data.iter().enumerate().take(row + 16).skip(row) {
+                    ui.same_line();
+
+                    if ui.selectable_config(format!("{b:02X}##{i}"))
+                        .selected(i == self.memory_cursor)
+                        .size([ui.calc_text_size("00")[0], 0.0])
+                        .build()
+                    {
+                        self.memory_cursor = i;
+                    }
+                }
+
+                let ascii = data[row..(row + 16).min(data.len())]
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                    .collect::<String>();
+                ui.same_line();
+                ui.text_disabled(ascii);
+            }
+        });
+    }
+
+    fn disassembly_window(&mut self, ui: &imgui::Ui, nes: &mut Nes) {
+        let pc = nes.cpu.pc;
+        let follow = nes.halted() && self.listing_pc != pc;
+        self.listing_pc = pc;
+        let mut addr = listing_start(nes, pc, 48);
+        let mut toggle = None;
+
+        ui.text_disabled("Click an instruction to toggle a breakpoint");
+        ui.child_window("listing").build(|| {
+            for _ in 0..96 {
+                let (text, size) = nes.disassemble(addr, false);
+                let bytes = (0..size).map(|i| format!("{:02X} ", nes.peek(addr + i))).collect::<String>();
+                let bp = nes.debugger.as_ref().unwrap().breakpoints.iter().any(|b| b.addr == addr);
+
+                let clicked = ui.selectable_config(format!("{} {addr:04X}  {bytes:<9} {text}##{addr}", if bp { "*" } else { " " }))
+                    .selected(addr == pc)
+                    .build();
+
+                if addr == pc && follow {
+                    ui.set_scroll_here_y();
+                }
+
+                if clicked {
+                    toggle = Some(addr);
+                }
+
+                addr += size;
+            }
+        });
+
+        if let Some(addr) = toggle {
+            let bps = &mut nes.debugger.as_mut().unwrap().breakpoints;
+
+            if let Some(i) = bps.iter().position(|b| b.addr == addr) {
+                bps.remove(i);
+            } else {
+                bps.push(Breakpoint::new(addr));
+            }
+        }
+    }
+}
+
+fn cpu_window(ui: &imgui::Ui, nes: &mut Nes) {
+    let debugger = nes.debugger.as_mut().unwrap();
+
+    match debugger.hit() {
+        None => ui.text("Running"),
+        Some(Break::Breakpoint(i)) => ui.text(format!("Breakpoint #{i}")),
+        Some(Break::Watchpoint { index, addr, value, write }) => {
+            ui.text(format!("Watchpoint #{index}: {} ${addr:04X} = {value:02X}", if write { "write" } else { "read" }));
+        },
+        Some(Break::Ppu(i)) => ui.text(format!("PPU breakpoint #{i}")),
+        Some(Break::Nmi) => ui.text("NMI"),
+        Some(Break::Irq) => ui.text("IRQ"),
+        Some(Break::Step) => ui.text("Paused"),
+    }
+
+    if debugger.hit().is_some() {
+        if ui.button("Run") {
+            debugger.resume();
+        }
+    } else if ui.button("Pause") {
+        debugger.step_into();
+    }
+
+    ui.same_line();
+    if ui.button("Step into") {
+        debugger.step_into();
+        debugger.resume();
+    }
+
+    ui.same_line();
+    if ui.button("Step over") {
+        debugger.step_over();
+        debugger.resume();
+    }
+
+    ui.same_line();
+    if ui.button("Step out") {
+        debugger.step_out();
+        debugger.resume();
+    }
+
+    ui.separator();
+
+    for (name, reg) in [("A", &mut nes.cpu.a), ("X", &mut nes.cpu.x), ("Y", &mut nes.cpu.y), ("S", &mut nes.cpu.s)] {
+        ui.set_next_item_width(48.0);
+        ui.input_scalar(name, reg).chars_hexadecimal(true).display_format("%02X").build();
+        ui.same_line();
+    }
+
+    ui.set_next_item_width(64.0);
+    ui.input_scalar("PC", &mut nes.cpu.pc).chars_hexadecimal(true).display_format("%04X").build();
+
+    for (i, name) in "NV-BDIZC".chars().enumerate() {
+        let bit = 0x80 >> i;
+        let mut set = nes.cpu.p & bit != 0;
+
+        if ui.checkbox(name.to_string(), &mut set) {
+            nes.cpu.p ^= bit;
+        }
+
+        if i != 7 {
+            ui.same_line();
+        }
+    }
+
+    ui.separator();
+    ui.text(format!("Cycle {}  Frame {}", nes.cycles(), nes.ppu.frame));
+    ui.text(format!("Scanline {}  Dot {}", nes.ppu.scanline, nes.ppu.cycle));
+}
+
+/// Find an address before `pc` that decodes into instructions ending exactly at `pc`
+fn listing_start(nes: &Nes, pc: u16, max_back: u16) -> u16 {
+    for back in (1..=max_back).rev() {
+        let mut addr = pc - back;
+
+        while addr.wrapping_sub(pc) as i16 <= 0 && addr != pc {
+            addr += nes.disassemble(addr, false).1;
+        }
+
+        if addr == pc {
+            return pc - back;
+        }
+    }
+
+    pc
+}
+
+fn stack_window(ui: &imgui::Ui, nes: &mut Nes) {
+    let debugger = nes.debugger.as_ref().unwrap();
+
+    for slot in (nes.cpu.s as usize + 1)..=0xff {
+        ui.text(format!("${:04X}: {:02X}", 0x100 + slot, nes.iram[0x100 + slot]));
+
+        if let Some(call) = debugger.stack_call(slot as u8) {
+            let ret = nes.iram[0x100 + slot] as u16 | (nes.iram[0x100 + (slot + 1) % 0x100] as u16) << 8;
+            let (ret, kind) = match call {
+                Call::Jsr => (ret + 1, "JSR"),
+                Call::Brk => (ret, "BRK"),
+                Call::Nmi => (ret, "NMI"),
+                Call::Irq => (ret, "IRQ"),
+            };
+
+            ui.same_line();
+            ui.text_colored(HIGHLIGHT, format!("return to ${ret:04X} ({kind})"));
+        }
+    }
+}
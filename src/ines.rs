@@ -1,20 +1,22 @@
-use nes::cart::Cartridge;
+use nes::cart::{BadState, Cartridge, Memory, OpenBus};
 use nes::ppu::CiRam;
 use std::io;
 
 pub struct InesFile<'a> {
     pub mapper_id: u16,
-    pub submapper: u8,
 
     pub prg_rom: &'a [u8],
     pub chr_rom: &'a [u8],
 
-    pub prg_ram_size: u16,
-    pub chr_ram_size: u16,
-    pub eeprom_size: u16,
+    pub prg_ram_size: usize,
+    /// Battery backed PRG RAM, mapped after the volatile part
+    pub prg_nvram_size: usize,
 
     pub vert_mirror: bool,
-    pub alt_nt_layout: bool,
+
+    pub region: nes::Region,
+    /// NES 2.0 default expansion device, 0 when unspecified
+    pub input_device: u8,
 }
 
 impl<'a> InesFile<'a> {
@@ -33,22 +35,42 @@ impl<'a> InesFile<'a> {
         let mut chr_rom_size = bytes[5] as usize;
 
         let vert_mirror = bytes[6] & 1 != 0;
+        let battery = bytes[6] & 2 != 0;
         let header_end = 16 + (bytes[6] & 4 != 0) as usize * 512;
-        let alt_nt_layout = bytes[6] & 8 != 0;
 
         let mut mapper_id = ((bytes[7] & 0xf0) as u16) | ((bytes[6] >> 4) as u16);
-        let mut submapper = 0;
+        let mut region = nes::Region::Ntsc;
+        let mut input_device = 0;
+
+        // ines 1.0 only gives the PRG RAM size in 8 kib, and 0 means 8 kib
+        let mut prg_ram_size = bytes[8].max(1) as usize * 8192;
+        let mut prg_nvram_size = 0;
+
+        if battery {
+            prg_nvram_size = core::mem::take(&mut prg_ram_size);
+        }
 
         if bytes[7] & 0x0c == 8 {
             // nes 2.0
 
             mapper_id |= ((bytes[8] & 0x0f) as u16) << 8;
-            submapper = bytes[8] >> 4;
 
             prg_rom_size |= ((bytes[9] & 0x0f) as usize) << 8;
             chr_rom_size |= ((bytes[9] & 0xf0) as usize) << 4;
 
-            // TODO: offset 10 & 11
+            // shift counts, 64 << n bytes
+            let shift = |n: u8| if n == 0 { 0 } else { 64 << n };
+            prg_ram_size = shift(bytes[10] & 0x0f);
+            prg_nvram_size = shift(bytes[10] >> 4);
+
+            region = match bytes[12] & 3 {
+                1 => nes::Region::Pal,
+                3 => nes::Region::Dendy,
+                // multi-region games run fine on NTSC
+                _ => nes::Region::Ntsc,
+            };
+
+            input_device = bytes[15] & 0x3f;
         }
 
         let prg_rom_end = header_end + prg_rom_size * 16384;
@@ -56,29 +78,29 @@ impl<'a> InesFile<'a> {
 
         Ok(Self {
             mapper_id,
-            submapper,
 
             prg_rom: &bytes[header_end..prg_rom_end],
             chr_rom: &bytes[prg_rom_end..chr_rom_end],
 
-            prg_ram_size: 0,
-            chr_ram_size: 0,
-            eeprom_size: 0,
+            prg_ram_size,
+            prg_nvram_size,
 
             vert_mirror,
-            alt_nt_layout,
+
+            region,
+            input_device,
         })
     }
 }
 
 macro_rules! mappers {
-    ($($id:tt : $name:tt $(< $($lt:tt),+ >)?),* $(,)?) => {
-        pub enum InesMapper<'a> {
-            $($name($name $(< $($lt)* >)?)),*
+    ($($id:tt : $name:tt),* $(,)?) => {
+        pub enum InesMapper {
+            $($name($name)),*
         }
 
-        impl<'a> InesMapper<'a> {
-            pub fn new(file: InesFile<'a>) -> Self {
+        impl InesMapper {
+            pub fn new(file: InesFile) -> Self {
                 match file.mapper_id {
                     $($id => Self::$name($name::new(file)),)*
                     _ => todo!("ines mapper id #{:03x}", file.mapper_id),
@@ -86,19 +108,31 @@ macro_rules! mappers {
             }
         }
 
-        impl Cartridge for InesMapper<'_> {
-            fn load(&mut self, addr: u16) -> Result<u8, ()> {
+        impl Cartridge for InesMapper {
+            fn load(&mut self, addr: u16) -> Result<u8, OpenBus> {
                 match self {
                     $(Self::$name(m) => m.load(addr)),*
                 }
             }
 
-            fn store(&mut self, addr: u16, data: u8) -> Result<(), ()> {
+            fn store(&mut self, addr: u16, data: u8) -> Result<(), OpenBus> {
                 match self {
                     $(Self::$name(m) => m.store(addr, data)),*
                 }
             }
 
+            fn peek(&self, addr: u16) -> Result<u8, OpenBus> {
+                match self {
+                    $(Self::$name(m) => m.peek(addr)),*
+                }
+            }
+
+            fn driven_bits(&self, addr: u16) -> u8 {
+                match self {
+                    $(Self::$name(m) => m.driven_bits(addr)),*
+                }
+            }
+
             fn vmem_load(&mut self, ciram: &CiRam, addr: u16) -> u8 {
                 match self {
                     $(Self::$name(m) => m.vmem_load(ciram, addr)),*
@@ -110,6 +144,78 @@ macro_rules! mappers {
                     $(Self::$name(m) => m.vmem_store(ciram, addr, data)),*
                 }
             }
+
+            fn prg_bank(&self, addr: u16) -> Option<usize> {
+                match self {
+                    $(Self::$name(m) => m.prg_bank(addr)),*
+                }
+            }
+
+            fn irq(&self) -> bool {
+                match self {
+                    $(Self::$name(m) => m.irq()),*
+                }
+            }
+
+            fn reset(&mut self) {
+                match self {
+                    $(Self::$name(m) => m.reset()),*
+                }
+            }
+
+            fn cpu_clock(&mut self) {
+                match self {
+                    $(Self::$name(m) => m.cpu_clock()),*
+                }
+            }
+
+            fn vmem_peek(&self, ciram: &CiRam, addr: u16) -> u8 {
+                match self {
+                    $(Self::$name(m) => m.vmem_peek(ciram, addr)),*
+                }
+            }
+
+            fn save_state(&self, out: &mut Vec<u8>) {
+                match self {
+                    $(Self::$name(m) => m.save_state(out)),*
+                }
+            }
+
+            fn load_state(&mut self, state: &[u8]) -> Result<(), BadState> {
+                match self {
+                    $(Self::$name(m) => m.load_state(state)),*
+                }
+            }
+
+            fn nvram(&self) -> &[u8] {
+                match self {
+                    $(Self::$name(m) => m.nvram()),*
+                }
+            }
+
+            fn nvram_mut(&mut self) -> &mut [u8] {
+                match self {
+                    $(Self::$name(m) => m.nvram_mut()),*
+                }
+            }
+
+            fn memory(&self, mem: Memory) -> &[u8] {
+                match self {
+                    $(Self::$name(m) => m.memory(mem)),*
+                }
+            }
+
+            fn memory_mut(&mut self, mem: Memory) -> &mut [u8] {
+                match self {
+                    $(Self::$name(m) => m.memory_mut(mem)),*
+                }
+            }
+
+            fn trace_state(&self, out: &mut String) {
+                match self {
+                    $(Self::$name(m) => m.trace_state(out)),*
+                }
+            }
         }
     };
 }
@@ -123,53 +229,75 @@ fn horiz_mirror(addr: u16) -> u16 {
 }
 
 mappers!(
-    0x000: Nrom<'a>,
+    0x000: Nrom,
 );
 
 /// INES mapper 000
-pub struct Nrom<'a> {
-    prg_rom: &'a [u8],
+pub struct Nrom {
+    prg_rom: Box<[u8]>,
     prg_rom_mask: u16,
 
     prg_ram: Box<[u8]>,
-    prg_ram_mask: u16,
+    /// Where the battery backed part of `prg_ram` starts
+    nvram_start: usize,
 
-    chr_rom: &'a [u8],
+    chr_rom: Box<[u8]>,
     vert_mirror: bool,
 }
 
-impl<'a> Nrom<'a> {
-    pub fn new(file: InesFile<'a>) -> Self {
+impl Nrom {
+    pub fn new(file: InesFile) -> Self {
+        // Family Basic has up to 4 kib, mirrored through $6000-$7fff
+        let prg_ram_size = (file.prg_ram_size + file.prg_nvram_size).min(8192);
+        let nvram_start = file.prg_ram_size.min(prg_ram_size);
+
         Self {
-            prg_rom: file.prg_rom,
+            prg_rom: file.prg_rom.into(),
             prg_rom_mask: file.prg_rom.len() as u16 - 1,
 
-            prg_ram: vec![0; file.prg_ram_size as usize].into(),
-            prg_ram_mask: file.prg_ram_size - 1,
+            prg_ram: vec![0; prg_ram_size].into(),
+            nvram_start,
 
-            chr_rom: file.chr_rom,
+            chr_rom: file.chr_rom.into(),
             vert_mirror: file.vert_mirror,
         }
     }
+
+    /// Sizes aren't always powers of two, so mirror with a modulo
+    fn prg_ram_index(&self, addr: u16) -> usize {
+        (addr - 0x6000) as usize % self.prg_ram.len()
+    }
 }
 
-impl Cartridge for Nrom<'_> {
-    fn load(&mut self, addr: u16) -> Result<u8, ()> {
+impl Cartridge for Nrom {
+    fn load(&mut self, addr: u16) -> Result<u8, OpenBus> {
         match addr {
-            0x6000..=0x7fff => Ok(self.prg_ram[(addr & self.prg_ram_mask) as usize]),
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => Ok(self.prg_ram[self.prg_ram_index(addr)]),
             0x8000..=0xffff => Ok(self.prg_rom[(addr & self.prg_rom_mask) as usize]),
-            _ => Err(()),
+            _ => Err(OpenBus),
+        }
+    }
+
+    fn store(&mut self, addr: u16, data: u8) -> Result<(), OpenBus> {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => { self.prg_ram[self.prg_ram_index(addr)] = data; Ok(()) },
+            _ => Err(OpenBus),
         }
     }
 
-    fn store(&mut self, addr: u16, data: u8) -> Result<(), ()> {
+    fn peek(&self, addr: u16) -> Result<u8, OpenBus> {
         match addr {
-            0x6000..=0x7fff => Ok(self.prg_ram[(addr & self.prg_ram_mask) as usize] = data),
-            _ => Err(()),
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => Ok(self.prg_ram[self.prg_ram_index(addr)]),
+            0x8000..=0xffff => Ok(self.prg_rom[(addr & self.prg_rom_mask) as usize]),
+            _ => Err(OpenBus),
         }
     }
 
     fn vmem_load(&mut self, ciram: &nes::ppu::CiRam, addr: u16) -> u8 {
+        self.vmem_peek(ciram, addr)
+    }
+
+    fn vmem_peek(&self, ciram: &nes::ppu::CiRam, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1fff => self.chr_rom[addr as usize],
             0x2000..=0x2fff if self.vert_mirror => ciram[(vert_mirror(addr) & 0x7ff) as usize],
@@ -185,4 +313,41 @@ impl Cartridge for Nrom<'_> {
             _ => {},
         }
     }
+
+    fn save_state(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.prg_ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), BadState> {
+        if state.len() != self.prg_ram.len() {
+            return Err(BadState);
+        }
+
+        self.prg_ram.copy_from_slice(state);
+        Ok(())
+    }
+
+    fn nvram(&self) -> &[u8] {
+        &self.prg_ram[self.nvram_start..]
+    }
+
+    fn nvram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram[self.nvram_start..]
+    }
+
+    fn memory(&self, mem: Memory) -> &[u8] {
+        match mem {
+            Memory::PrgRom => &self.prg_rom,
+            Memory::PrgRam => &self.prg_ram,
+            Memory::Chr => &self.chr_rom,
+        }
+    }
+
+    fn memory_mut(&mut self, mem: Memory) -> &mut [u8] {
+        match mem {
+            Memory::PrgRom => &mut self.prg_rom,
+            Memory::PrgRam => &mut self.prg_ram,
+            Memory::Chr => &mut self.chr_rom,
+        }
+    }
 }
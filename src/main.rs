@@ -1,4 +1,9 @@
-use std::{num::NonZeroU32, time::Instant};
+use std::{
+    io,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use glow::HasContext;
 use glutin::{
@@ -16,12 +21,471 @@ use imgui_winit_support::{
     WinitPlatform,
 };
 use raw_window_handle::HasWindowHandle;
+use nes::input::{autofire::{Autofire, Macro}, Device, HostInput, Slot};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+mod cheat_ui;
+mod cli;
+mod config;
+mod debug_ui;
+mod ines;
+mod movie;
+mod overlay;
+mod record;
+mod ppu_ui;
+mod sav;
+mod scale;
+mod script;
+mod screenshot;
+mod search_ui;
+mod texture;
+mod video;
 
 struct WindowState {
-    dummy: bool,
+    nes: Option<nes::Nes>,
+    /// Path and checksum of the running ROM
+    rom: Option<(PathBuf, String)>,
+    debug: debug_ui::DebugWindows,
+    cheats: cheat_ui::CheatWindow,
+    search: search_ui::SearchWindow,
+    ppu: ppu_ui::PpuWindows,
+    screen: Option<texture::Texture>,
+    save: Option<sav::SaveFile>,
+    movie: Option<movie::Session>,
+    /// Keyboard and mouse, handed to the console every frame
+    host: HostInput,
+    /// Reset on the next frame, so movies can record it
+    reset: bool,
+    /// The debugger halted the last frame before it finished
+    mid_frame: bool,
+    status: Option<String>,
+    /// Stretch screenshots to the TV's pixel aspect ratio
+    aspect: bool,
+    recorder: Option<record::Recorder>,
+    video: video::Video,
+    config: config::Config,
+    /// Port 1, port 2 and expansion port devices for ROMs that don't pick their own
+    devices: [Device; 3],
+    autofire: Autofire,
+    /// Buttons held on turbo
+    turbo: [u8; 4],
+    /// Macro being typed into the Input menu
+    new_macro: String,
+    script: Option<(script::Script, PathBuf)>,
+}
+
+/// How to start a game
+enum Start {
+    Normal,
+    /// Record a new movie to the path
+    Record(PathBuf),
+    Play(nes::movie::Movie, PathBuf),
+}
+
+/// A ROM file ready to be plugged in
+struct Rom {
+    mapper: ines::InesMapper,
+    region: nes::Region,
+    checksum: String,
+    /// NES 2.0 default expansion device
+    input_device: u8,
+}
+
+fn read_rom(path: &Path) -> io::Result<Rom> {
+    let bytes = std::fs::read(path)?;
+    let file = ines::InesFile::new(&bytes)?;
+    let region = file.region;
+    let checksum = movie::rom_checksum(&file);
+    let input_device = file.input_device;
+    Ok(Rom { mapper: ines::InesMapper::new(file), region, checksum, input_device })
+}
+
+impl Rom {
+    fn power_on(self, region: nes::Region, config: &nes::power::PowerOnConfig) -> nes::Nes {
+        let mut nes = nes::Nes::with_config(Box::new(self.mapper), None, region, config);
+        plug(&mut nes, Device::from_nes2(self.input_device));
+        nes
+    }
+}
+
+impl WindowState {
+    /// Replace the running game, saving the old one's battery RAM first. Movies start without
+    /// battery RAM and with only the two controllers plugged so they replay the same everywhere.
+    /// The old game keeps running if the new one fails to load.
+    fn open_rom(&mut self, path: &Path, start: Start) -> io::Result<()> {
+        let rom = read_rom(path)?;
+        let checksum = rom.checksum.clone();
+        let header_devices = rom.input_device != 0;
+
+        let mut nes = match &start {
+            Start::Play(movie, _) => movie.start(Box::new(rom.mapper))
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the movie's savestate doesn't fit this ROM"))?,
+            _ => {
+                let region = rom.region;
+                let mut nes = rom.power_on(region, &Default::default());
+
+                if !header_devices {
+                    plug(&mut nes, self.devices);
+                }
+
+                nes
+            },
+        };
+
+        if matches!(start, Start::Record(_)) && nes.input.devices() != nes::movie::DEVICES {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "movies only hold two controllers, unplug everything else first"));
+        }
+
+        // written out first, reopening the same game has to read what it last saved
+        if let (Some(old), Some(save)) = (&self.nes, &mut self.save) {
+            save.flush(&*old.cart)?;
+        }
+
+        let save = match start {
+            Start::Normal => sav::SaveFile::open(path, &mut *nes.cart)?,
+            _ => None,
+        };
+
+        self.close_rom();
+        self.save = save;
+
+        if let Err(e) = self.cheats.load(path) {
+            self.status = Some(format!("ignoring cheats: {e}"));
+        }
+
+        match start {
+            Start::Normal => nes.cheats = self.cheats.active(),
+            Start::Record(out) => {
+                let mut movie = nes::movie::Movie::new(nes::movie::Anchor::PowerOn(Default::default()), nes.region);
+                movie.rom_checksum = Some(checksum.clone());
+                movie.rom_name = path.file_stem().map(|name| name.to_string_lossy().into_owned());
+                self.movie = Some(movie::Session::new(movie, movie::Mode::Record, out));
+            },
+            Start::Play(movie, from) => {
+                self.status = movie::checksum_warning(&movie, &checksum);
+                self.movie = Some(movie::Session::new(movie, movie::Mode::Play { frame: 0 }, from));
+            },
+        }
+
+        self.video.overscan = self.config.overscan(Some(&checksum));
+        self.nes = Some(nes);
+        self.rom = Some((path.to_owned(), checksum));
+        Ok(())
+    }
+
+    fn close_rom(&mut self) {
+        self.stop_movie();
+        self.stop_recording();
+
+        if let (Some(nes), Some(save)) = (&self.nes, &mut self.save) {
+            if let Err(e) = save.flush(&*nes.cart) {
+                eprintln!("failed to write save: {e}");
+            }
+        }
+
+        self.nes = None;
+        self.rom = None;
+        self.save = None;
+        self.mid_frame = false;
+        self.cheats.close();
+    }
+
+    /// Stop playback or recording, recordings are written out
+    fn stop_movie(&mut self) {
+        let Some(session) = self.movie.take() else { return };
+
+        self.status = Some(match session.mode {
+            movie::Mode::Record => match movie::save(&session.movie, &session.path) {
+                Ok(()) => format!("saved {}", session.path.display()),
+                Err(e) => format!("failed to save {}: {e}", session.path.display()),
+            },
+            movie::Mode::Play { frame } => match session.mismatch {
+                Some(bad) => format!("stopped on frame {frame}, desynced on frame {bad}"),
+                None => format!("stopped on frame {frame}, in sync"),
+            },
+        });
+    }
+
+    fn play_movie(&mut self, path: &Path) -> io::Result<()> {
+        let Some((rom, _)) = self.rom.clone() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "open a ROM first"));
+        };
+
+        let movie = movie::load(path)?;
+        self.open_rom(&rom, Start::Play(movie, path.to_owned()))
+    }
+
+    /// Save the current frame next to the ROM, as PNG or as raw palette indices
+    fn screenshot(&mut self, raw: bool) {
+        let (Some(nes), Some((rom, _))) = (&self.nes, &self.rom) else { return };
+
+        let path = screenshot::next_path(rom, if raw { "raw" } else { "png" });
+        let result = if raw {
+            screenshot::save_raw(&path, nes)
+        } else {
+            screenshot::save_png(&path, nes, &self.video, self.aspect)
+        };
+
+        self.status = Some(match result {
+            Ok(()) => format!("saved {}", path.display()),
+            Err(e) => format!("failed to save {}: {e}", path.display()),
+        });
+    }
+
+    fn start_recording(&mut self) {
+        let (Some(nes), Some((rom, _))) = (&self.nes, &self.rom) else { return };
+
+        let video = screenshot::next_path(rom, "y4m");
+        let audio = video.with_extension("wav");
+
+        match record::Recorder::create(&video, &audio, nes, &self.video.render(nes)) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                self.status = Some(format!("recording {}", video.display()));
+            },
+            Err(e) => self.status = Some(format!("failed to record {}: {e}", video.display())),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            self.status = Some(match recorder.finish() {
+                Ok(()) => "video saved".to_string(),
+                Err(e) => format!("failed to finish video: {e}"),
+            });
+        }
+    }
+
+    /// Start the script, replacing the running one
+    fn run_script(&mut self, path: &Path) {
+        self.stop_script(None);
+
+        match script::Script::load(path) {
+            Ok(script) => {
+                self.script = Some((script, path.to_owned()));
+                self.status = Some(format!("running {}", path.display()));
+            },
+            Err(e) => self.status = Some(format!("failed to load {}: {e}", path.display())),
+        }
+    }
+
+    /// Stop the running script, `error` is why it had to
+    fn stop_script(&mut self, error: Option<String>) {
+        let Some((script, path)) = self.script.take() else { return };
+        self.video.overlay = Default::default();
+
+        let error = error.or_else(|| script.finish(self.nes.as_mut()).err());
+        self.status = Some(match error {
+            Some(e) => format!("script stopped: {e}"),
+            None => format!("stopped {}", path.display()),
+        });
+    }
+
+    fn run_frame(&mut self) {
+        let Some(nes) = &mut self.nes else { return };
+
+        // nothing runs while the debugger holds the CPU
+        if nes.halted() {
+            return;
+        }
+
+        let mut error = None;
+        let mut ended = false;
+
+        // a frame the debugger broke into finishes with the input it started with
+        if !self.mid_frame {
+            // turbo and macros count emulated frames, and movies record what they pressed
+            let mut host = self.host.clone();
+            self.autofire.apply(&mut host.buttons, self.turbo);
+            // motion is reported once
+            self.host.motion = (0, 0);
+
+            // scripts press buttons and reset too
+            if let Some((script, _)) = &mut self.script {
+                script.set_palette(&self.video.palette);
+                error = script.before_frame(nes, &mut host.buttons).err();
+                self.reset |= script.take_reset();
+            }
+
+            let reset = core::mem::take(&mut self.reset);
+
+            match &mut self.movie {
+                Some(session) => ended = !session.start_frame(nes, &host, reset),
+                None => {
+                    if reset {
+                        nes.reset();
+                    }
+
+                    nes.input.update(host);
+                },
+            }
+        }
+
+        if !ended {
+            let frame = nes.ppu.frame;
+
+            match self.script.as_ref().filter(|_| error.is_none()) {
+                Some((script, _)) => error = script.step_frame(nes).err(),
+                None => nes.step_frame(),
+            }
+
+            self.mid_frame = nes.ppu.frame == frame;
+        }
+
+        if !ended && !self.mid_frame {
+            if let Some(session) = &mut self.movie {
+                session.end_frame(nes);
+            }
+
+            if let Some((script, _)) = self.script.as_mut().filter(|_| error.is_none()) {
+                error = script.after_frame(nes).err();
+
+                if let Some(message) = script.take_messages().pop() {
+                    self.status = Some(message);
+                }
+
+                self.video.overlay = script.overlay();
+            }
+        }
+
+        if ended {
+            self.stop_movie();
+        }
+
+        if error.is_some() || self.script.as_ref().is_some_and(|(script, _)| script.exited()) {
+            self.stop_script(error);
+        }
+    }
+}
+
+impl WindowState {
+    /// 1-9 start and stop controller 1's macros, unless they're typed on the Family BASIC
+    /// keyboard
+    fn macro_key(&mut self, code: KeyCode) {
+        let typing = self.nes.as_ref().is_some_and(|nes| nes.input.devices().contains(&Device::Keyboard));
+        let digits = [
+            KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+            KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+        ];
+        let Some(i) = digits.iter().position(|&k| k == code) else { return };
+
+        if let (false, Some(m)) = (typing, self.config.macros.get(i)) {
+            self.toggle_macro(m.clone());
+        }
+    }
+
+    /// Turbo buttons pressed by `code`, none while A and S are Power Pad buttons
+    fn turbo_key(&self, code: KeyCode) -> u8 {
+        let stepping = self.nes.as_ref().is_some_and(|nes| nes.input.devices().contains(&Device::PowerPad));
+        if stepping { 0 } else { turbo_button(code) }
+    }
+
+    fn toggle_macro(&mut self, m: Macro) {
+        let player = &mut self.autofire.players[0];
+
+        match &player.running {
+            Some((running, _)) if *running == m => player.running = None,
+            _ => player.running = Some((m, 0)),
+        }
+    }
+}
+
+/// Port 1, port 2 and expansion port devices
+fn plug(nes: &mut nes::Nes, devices: [Device; 3]) {
+    for (slot, device) in [Slot::Port(0), Slot::Port(1), Slot::Expansion].into_iter().zip(devices) {
+        // the Four Score replaces whatever the other port got
+        if device != Device::FourScore || slot == Slot::Port(0) {
+            nes.input.plug(slot, device);
+        }
+    }
+}
+
+/// Keyboard layout of controller 1
+fn joypad_button(key: KeyCode) -> u8 {
+    match key {
+        KeyCode::KeyX => nes::input::joypad::A,
+        KeyCode::KeyZ => nes::input::joypad::B,
+        KeyCode::ShiftRight => nes::input::joypad::SELECT,
+        KeyCode::Enter => nes::input::joypad::START,
+        KeyCode::ArrowUp => nes::input::joypad::UP,
+        KeyCode::ArrowDown => nes::input::joypad::DOWN,
+        KeyCode::ArrowLeft => nes::input::joypad::LEFT,
+        KeyCode::ArrowRight => nes::input::joypad::RIGHT,
+        _ => 0,
+    }
+}
+
+/// Turbo buttons of controller 1
+fn turbo_button(key: KeyCode) -> u8 {
+    match key {
+        KeyCode::KeyS => nes::input::joypad::A,
+        KeyCode::KeyA => nes::input::joypad::B,
+        _ => 0,
+    }
+}
+
+/// Power Pad buttons 1-12, side B's rows of four
+fn power_pad_button(key: KeyCode) -> u16 {
+    let rows = [
+        [KeyCode::KeyQ, KeyCode::KeyW, KeyCode::KeyE, KeyCode::KeyR],
+        [KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyF],
+        [KeyCode::KeyZ, KeyCode::KeyX, KeyCode::KeyC, KeyCode::KeyV],
+    ];
+    rows.iter().flatten().position(|&k| k == key).map_or(0, |i| 1 << i)
+}
+
+/// Family BASIC keyboard key on the host's, as named in [`nes::input::keyboard::KEYS`]
+fn keyboard_key(key: KeyCode) -> Option<(usize, u8)> {
+    let name = match key {
+        KeyCode::Enter => "RETURN",
+        KeyCode::Space => "SPACE",
+        KeyCode::Escape => "ESC",
+        KeyCode::Backspace | KeyCode::Delete => "DEL",
+        KeyCode::Insert => "INS",
+        KeyCode::Home => "CLR",
+        KeyCode::End => "STOP",
+        KeyCode::ControlLeft => "CTR",
+        KeyCode::ShiftLeft => "LSHIFT",
+        KeyCode::ShiftRight => "RSHIFT",
+        KeyCode::AltLeft => "GRPH",
+        KeyCode::AltRight => "KANA",
+        KeyCode::ArrowUp => "UP",
+        KeyCode::ArrowDown => "DOWN",
+        KeyCode::ArrowLeft => "LEFT",
+        KeyCode::ArrowRight => "RIGHT",
+        KeyCode::BracketLeft => "[",
+        KeyCode::BracketRight => "]",
+        KeyCode::Semicolon => ";",
+        KeyCode::Quote => ":",
+        KeyCode::Backquote => "@",
+        KeyCode::Backslash => "¥",
+        KeyCode::Minus => "-",
+        KeyCode::Equal => "^",
+        KeyCode::Slash => "/",
+        KeyCode::Comma => ",",
+        KeyCode::Period => ".",
+        KeyCode::IntlRo => "_",
+        // letters, digits and F1-F8 go by their name
+        _ => {
+            let name = format!("{key:?}");
+            let name = name.strip_prefix("Key").or(name.strip_prefix("Digit")).unwrap_or(&name);
+            return nes::input::keyboard::key_position(name);
+        },
+    };
+
+    nes::input::keyboard::key_position(name)
 }
 
 fn main() {
+    let args = cli::Args::parse().unwrap_or_else(|e| {
+        eprintln!("{e}\n{}", cli::USAGE);
+        std::process::exit(2);
+    });
+
+    if args.headless() {
+        std::process::exit(cli::run(&args));
+    }
+
     // Common setup for creating a winit window and imgui context, not specifc
     // to this renderer at all except that glutin is used to create the window
     // since it will give us access to a GL context
@@ -38,8 +502,43 @@ fn main() {
     let mut last_frame = Instant::now();
 
     let mut ws = WindowState {
-        dummy: false,
+        nes: None,
+        rom: None,
+        debug: Default::default(),
+        cheats: Default::default(),
+        search: Default::default(),
+        ppu: Default::default(),
+        screen: None,
+        save: None,
+        movie: None,
+        host: Default::default(),
+        reset: false,
+        mid_frame: false,
+        status: None,
+        aspect: false,
+        recorder: None,
+        video: Default::default(),
+        config: config::Config::load().unwrap_or_else(|e| {
+            eprintln!("ignoring {}: {e}", config::Config::path().display());
+            Default::default()
+        }),
+        devices: [Device::Joypad, Device::Joypad, Device::Unplugged],
+        autofire: Default::default(),
+        turbo: [0; 4],
+        new_macro: String::new(),
+        script: None,
     };
+    ws.video.overscan = ws.config.overscan;
+    ws.autofire.players[0].turbo_a = ws.config.turbo_a.clone();
+    ws.autofire.players[0].turbo_b = ws.config.turbo_b.clone();
+
+    if let Some(path) = &args.rom {
+        ws.open_rom(path, Start::Normal).expect("failed to load rom");
+    }
+
+    if let Some(path) = &args.script {
+        ws.run_script(path);
+    }
 
     // Standard winit event loop
     #[allow(deprecated)]
@@ -69,8 +568,42 @@ fn main() {
                     ctx.clear(glow::COLOR_BUFFER_BIT);
                 }
 
+                ws.run_frame();
+
+                if let (Some(nes), Some(recorder)) = (&ws.nes, &mut ws.recorder) {
+                    if let Err(e) = recorder.frame(nes, &ws.video) {
+                        ws.status = Some(format!("video recording failed: {e}"));
+                        ws.recorder = None;
+                    }
+                }
+
+                if let Some(nes) = ws.nes.as_mut().filter(|_| !ws.mid_frame) {
+                    // there's no audio output yet
+                    nes.apu.samples.clear();
+                }
+
+                if let Some(nes) = &mut ws.nes {
+
+                    if let Some(save) = &mut ws.save {
+                        if let Err(e) = save.autosave(&*nes.cart) {
+                            eprintln!("failed to write save: {e}");
+                        }
+                    }
+                    ws.ppu.update(&mut ig_renderer, nes, &ws.video.palette);
+
+                    let frame = ws.video.render(nes);
+
+                    // filters change the size
+                    if let Some(screen) = ws.screen.take_if(|s| (s.width, s.height) != (frame.width, frame.height)) {
+                        screen.delete(ig_renderer.gl_context());
+                    }
+
+                    let screen = ws.screen.get_or_insert_with(|| texture::Texture::new(&mut ig_renderer, frame.width, frame.height));
+                    screen.update(ig_renderer.gl_context(), &frame.rgba);
+                }
+
                 let ui = imgui_context.frame();
-                draw_frame(&mut ws, &ui);
+                draw_frame(&mut ws, ui);
 
                 winit_platform.prepare_render(ui, &window);
                 let draw_data = imgui_context.render();
@@ -88,8 +621,65 @@ fn main() {
                 event: winit::event::WindowEvent::CloseRequested,
                 ..
             } => {
+                ws.stop_script(None);
+                ws.close_rom();
                 window_target.exit();
             }
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::DroppedFile(path),
+                ..
+            } => {
+                let result = if movie::is_movie(&path) {
+                    ws.play_movie(&path)
+                } else if video::Video::is_pal(&path) {
+                    ws.video.load_pal(&path)
+                } else if script::is_script(&path) {
+                    ws.run_script(&path);
+                    Ok(())
+                } else {
+                    ws.open_rom(&path, Start::Normal)
+                };
+
+                if let Err(e) = result {
+                    ws.status = Some(format!("failed to load {}: {e}", path.display()));
+                }
+            }
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::KeyboardInput { event: ref key, .. },
+                ..
+            } => {
+                if let PhysicalKey::Code(code) = key.physical_key {
+                    if code == KeyCode::F12 && key.state.is_pressed() && !key.repeat {
+                        // shift for the raw dump
+                        ws.screenshot(imgui_context.io().key_shift);
+                    }
+
+                    let (row, key_bit) = keyboard_key(code).unwrap_or((0, 0));
+
+                    if !imgui_context.io().want_capture_keyboard && key.state.is_pressed() {
+                        if !key.repeat {
+                            ws.macro_key(code);
+                        }
+
+                        ws.turbo[0] |= ws.turbo_key(code);
+                        let host = &mut ws.host;
+                        host.buttons[0] |= joypad_button(code);
+                        host.power_pad |= power_pad_button(code);
+                        host.keyboard[row] |= key_bit;
+                        host.microphone |= code == KeyCode::KeyM;
+                    } else {
+                        // releases always go through so buttons can't get stuck
+                        ws.turbo[0] &= !turbo_button(code);
+                        let host = &mut ws.host;
+                        host.buttons[0] &= !joypad_button(code);
+                        host.power_pad &= !power_pad_button(code);
+                        host.keyboard[row] &= !key_bit;
+                        host.microphone &= code != KeyCode::KeyM;
+                    }
+                }
+
+                winit_platform.handle_event(imgui_context.io_mut(), &window, &event);
+            }
             winit::event::Event::WindowEvent {
                 event: winit::event::WindowEvent::Resized(new_size),
                 ..
@@ -114,22 +704,353 @@ fn main() {
 fn draw_frame(state: &mut WindowState, ui: &imgui::Ui) {
     ui.main_menu_bar(|| {
         ui.menu("File", || {
+            if state.nes.is_some() {
+                let playing = matches!(&state.movie, Some(s) if matches!(s.mode, movie::Mode::Play { .. }));
+
+                if ui.menu_item_config("Reset").enabled(!playing).build() {
+                    state.reset = true;
+                }
+
+                ui.separator();
+
+                if ui.menu_item_config("Screenshot").shortcut("F12").build() {
+                    state.screenshot(false);
+                }
+
+                if ui.menu_item_config("Raw frame dump").shortcut("Shift+F12").build() {
+                    state.screenshot(true);
+                }
+
+                ui.checkbox("Aspect correct screenshots", &mut state.aspect);
+
+                if state.recorder.is_none() {
+                    if ui.menu_item("Record video") {
+                        state.start_recording();
+                    }
+                } else if ui.menu_item("Stop video recording") {
+                    state.stop_recording();
+                }
+
+                ui.separator();
+            }
+
+            if let Some((_, path)) = &state.script {
+                let path = path.clone();
+
+                if ui.menu_item("Restart script") {
+                    state.run_script(&path);
+                }
+
+                if ui.menu_item("Stop script") {
+                    state.stop_script(None);
+                }
+
+                ui.separator();
+            }
+
             if ui.menu_item("Exit") {
+                state.stop_script(None);
+                state.close_rom();
                 std::process::exit(0);
             }
         });
-        ui.menu("Window", || {
-            if ui.menu_item("Dummy") {
-                state.dummy ^= true;
+        ui.menu("Video", || {
+            if let Some(status) = state.video.menu(ui, state.rom.as_ref().map(|(rom, _)| rom.as_path())) {
+                state.status = Some(status);
             }
+
+            ui.separator();
+            overscan_menu(state, ui);
         });
+        ui.menu("Input", || input_menu(state, ui));
+        ui.menu("Movie", || movie_menu(state, ui));
+        ui.menu("Window", || {
+            state.debug.menu(ui);
+            state.cheats.menu(ui);
+            state.search.menu(ui);
+            ui.separator();
+            state.ppu.menu(ui);
+        });
+
+        if let Some(session) = &state.movie {
+            ui.text(session.status());
+        } else if let Some(status) = &state.status {
+            ui.text(status);
+        }
+    });
+
+    // clicks go to the mouse driven devices, not window drags
+    let pointing = state.nes.as_ref().is_some_and(|nes| nes.input.devices().iter().any(|d| uses_mouse(*d)));
+
+    ui.window("Screen").movable(!pointing).build(|| {
+        if let Some(screen) = &state.screen {
+            // filtered frames can be wider or taller but cover the same picture, show them at
+            // least twice the size and never shrink them
+            let [width, height] = [state.video.overscan.width(), state.video.overscan.height()];
+            let scale = (screen.height / height).max(2) as f32;
+            imgui::Image::new(screen.id, [width as f32 * scale, height as f32 * scale]).build(ui);
+
+            point(ui, &mut state.host, &state.video.overscan, scale);
+        }
     });
 
-    ui.window("Screen").build(|| {
+    if let Some(nes) = &mut state.nes {
+        state.debug.draw(ui, nes);
+        state.ppu.draw(ui, nes, &state.video.palette);
+        state.cheats.draw(ui, nes, state.movie.is_some());
+        // cheats stay off during movies
+        let cheats = state.movie.is_none().then_some(&mut state.cheats);
+        state.search.draw(ui, nes, cheats);
+    }
+}
+
+fn uses_mouse(device: Device) -> bool {
+    matches!(device, Device::Zapper | Device::VausNes | Device::VausFamicom | Device::SnesMouse)
+}
+
+/// The mouse over the screen image just drawn, as screen pixels
+fn point(ui: &imgui::Ui, host: &mut HostInput, overscan: &nes::ppu::Overscan, scale: f32) {
+    let [left, top] = ui.item_rect_min();
+    let [mx, my] = ui.io().mouse_pos;
+    let hovered = ui.is_item_hovered();
+
+    host.pointer = hovered.then(|| {
+        let x = ((mx - left) / scale) as usize + overscan.left;
+        let y = ((my - top) / scale) as usize + overscan.top;
+        (x.min(nes::ppu::FRAME_WIDTH - 1), y.min(nes::ppu::FRAME_HEIGHT - 1))
     });
 
-    if state.dummy {
-        ui.window("Dummy").build(|| {});
+    if hovered {
+        let [dx, dy] = ui.io().mouse_delta;
+        host.motion.0 += (dx / scale) as i32;
+        host.motion.1 += (dy / scale) as i32;
+    }
+
+    host.primary = hovered && ui.is_mouse_down(imgui::MouseButton::Left);
+    host.secondary = hovered && ui.is_mouse_down(imgui::MouseButton::Right);
+}
+
+/// What's plugged in where, taken over by the next ROM unless its header says otherwise
+fn input_menu(state: &mut WindowState, ui: &imgui::Ui) {
+    for (i, (label, slot)) in [("Port 1", Slot::Port(0)), ("Port 2", Slot::Port(1)), ("Expansion port", Slot::Expansion)].into_iter().enumerate() {
+        if i > 0 {
+            ui.separator();
+        }
+
+        ui.text(label);
+        let plugged = state.nes.as_ref().map_or(state.devices, |nes| nes.input.devices());
+        // the same names show up under every slot
+        let _id = ui.push_id_usize(i);
+
+        for device in Device::ALL.into_iter().filter(|d| d.fits(slot)) {
+            // movies only hold the controllers
+            let item = ui.menu_item_config(device.name()).selected(plugged[i] == device).enabled(state.movie.is_none());
+
+            if item.build() {
+                match &mut state.nes {
+                    Some(nes) => {
+                        nes.input.plug(slot, device);
+                        state.devices = nes.input.devices();
+                    },
+                    None => state.devices[i] = device,
+                }
+            }
+        }
+    }
+
+    ui.separator();
+    turbo_menu(state, ui);
+
+    let plugged = state.nes.as_ref().map_or(state.devices, |nes| nes.input.devices());
+    let hints = [
+        (Device::Zapper, "Zapper: aim with the mouse, right click shoots off screen"),
+        (Device::PowerPad, "Power Pad: Q W E R, A S D F, Z X C V, turbo keys are off"),
+        (Device::Keyboard, "Keyboard: typed keys go to Family BASIC"),
+        (Device::Microphone, "Microphone: hold M"),
+    ];
+
+    for (device, hint) in hints {
+        if plugged.contains(&device) {
+            ui.text_disabled(hint);
+        }
+    }
+}
+
+/// Controller 1's turbo patterns and macros, saved to the settings
+fn turbo_menu(state: &mut WindowState, ui: &imgui::Ui) {
+    use nes::input::autofire::Pattern;
+
+    ui.text("Turbo, S for A and A for B");
+    let player = &mut state.autofire.players[0];
+
+    for (label, pattern) in [("Turbo A", &mut player.turbo_a), ("Turbo B", &mut player.turbo_b)] {
+        let mut text = pattern.write();
+
+        // x pressed, . released, one frame each
+        if ui.input_text(label, &mut text).build() {
+            if let Ok(p) = Pattern::parse(&text) {
+                *pattern = p;
+            }
+        }
+    }
+
+    ui.separator();
+    ui.text("Macros, 1-9 start and stop them");
+    let running = player.running.as_ref().map(|(m, _)| m.clone());
+    let mut toggled = None;
+    let mut removed = None;
+
+    for (i, m) in state.config.macros.iter().enumerate() {
+        let _id = ui.push_id_usize(i);
+        let label = format!("{} {}: {}", i + 1, m.name, m.write());
+
+        if ui.menu_item_config(label).selected(running.as_ref() == Some(m)).build() {
+            toggled = Some(m.clone());
+        }
+
+        if ui.is_item_hovered() && ui.is_mouse_clicked(imgui::MouseButton::Right) {
+            removed = Some(i);
+        }
+    }
+
+    if let Some(m) = toggled {
+        state.toggle_macro(m);
+    }
+
+    if let Some(i) = removed {
+        state.config.macros.remove(i);
+    }
+
+    if !state.config.macros.is_empty() {
+        ui.text_disabled("right click removes one");
+    }
+
+    ui.input_text("##new macro", &mut state.new_macro).hint("name: Right+A, Right x2, ...").build();
+
+    if ui.menu_item("Add macro") {
+        let parsed = state.new_macro.split_once(':').ok_or("a macro needs `name: steps`".to_string())
+            .and_then(|(name, steps)| Macro::parse(name.trim(), steps));
+
+        match parsed {
+            Ok(m) => {
+                state.config.macros.push(m);
+                state.new_macro.clear();
+            },
+            Err(e) => state.status = Some(e),
+        }
+    }
+
+    if ui.menu_item("Save turbo and macros") {
+        state.config.turbo_a = state.autofire.players[0].turbo_a.clone();
+        state.config.turbo_b = state.autofire.players[0].turbo_b.clone();
+
+        state.status = Some(match state.config.save() {
+            Ok(()) => "settings saved".to_string(),
+            Err(e) => format!("failed to save settings: {e}"),
+        });
+    }
+}
+
+fn overscan_menu(state: &mut WindowState, ui: &imgui::Ui) {
+    let overscan = &mut state.video.overscan;
+    ui.text("Overscan");
+
+    for (label, edge) in [
+        ("Top", &mut overscan.top),
+        ("Bottom", &mut overscan.bottom),
+        ("Left", &mut overscan.left),
+        ("Right", &mut overscan.right),
+    ] {
+        ui.slider(label, 0, 32, edge);
+    }
+
+    if ui.menu_item("TV") {
+        *overscan = nes::ppu::Overscan::TV;
+    }
+
+    if ui.menu_item("None") {
+        *overscan = Default::default();
+    }
+
+    let overscan = *overscan;
+    let config = &mut state.config;
+    let mut changed = false;
+
+    if ui.menu_item("Save as default") {
+        config.overscan = overscan;
+        changed = true;
+    }
+
+    if let Some((rom, checksum)) = &state.rom {
+        if ui.menu_item("Save for this game") {
+            let game = config.games.entry(checksum.clone()).or_default();
+            game.name = rom.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            game.overscan = Some(overscan);
+            changed = true;
+        }
+
+        let has_override = config.games.get(checksum).is_some_and(|game| game.overscan.is_some());
+
+        if ui.menu_item_config("Use the default for this game").enabled(has_override).build() {
+            if let Some(game) = config.games.get_mut(checksum) {
+                game.overscan = None;
+            }
+
+            config.games.retain(|_, game| game.overscan.is_some());
+            state.video.overscan = config.overscan;
+            changed = true;
+        }
+    }
+
+    if changed {
+        let path = config::Config::path();
+        state.status = Some(match config.save() {
+            Ok(()) => format!("saved {}", path.display()),
+            Err(e) => format!("failed to save {}: {e}", path.display()),
+        });
+    }
+}
+
+fn movie_menu(state: &mut WindowState, ui: &imgui::Ui) {
+    let Some((rom, _)) = state.rom.clone() else {
+        ui.text_disabled("open a ROM first");
+        return;
+    };
+    let native = rom.with_extension("rmv");
+
+    if state.movie.is_some() {
+        if ui.menu_item("Stop") {
+            state.stop_movie();
+        }
+
+        return;
+    }
+
+    let mut result = Ok(());
+
+    if ui.menu_item("Record from power on") {
+        result = state.open_rom(&rom, Start::Record(native.clone()));
+    }
+
+    if ui.menu_item_config("Play").enabled(native.exists()).build() {
+        result = state.play_movie(&native);
+    }
+
+    ui.separator();
+
+    for (label, ext) in [("Export FM2", "fm2"), ("Export BizHawk input log", "txt")] {
+        if ui.menu_item_config(label).enabled(native.exists()).build() {
+            let out = rom.with_extension(ext);
+            result = movie::load(&native).and_then(|m| movie::save(&m, &out));
+
+            if result.is_ok() {
+                state.status = Some(format!("saved {}", out.display()));
+            }
+        }
+    }
+
+    if let Err(e) = result {
+        state.status = Some(e.to_string());
     }
 }
 
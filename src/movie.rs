@@ -0,0 +1,117 @@
+use base64::Engine;
+use nes::movie::{Frame, Movie};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// FCEUX's `romChecksum`, MD5 of PRG and CHR ROM without the header
+pub fn rom_checksum(file: &crate::ines::InesFile) -> String {
+    let mut md5 = md5::Context::new();
+    md5.consume(file.prg_rom);
+    md5.consume(file.chr_rom);
+    format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(md5.compute().0))
+}
+
+/// Read a movie, `.fm2` is FCEUX, `.txt` a BizHawk input log, anything else the native format
+pub fn load(path: &Path) -> io::Result<Movie> {
+    let text = std::fs::read_to_string(path)?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("fm2") => Movie::parse_fm2(&text),
+        Some("txt") => Movie::parse_bizhawk(&text),
+        _ => Movie::parse(&text),
+    }.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn save(movie: &Movie, path: &Path) -> io::Result<()> {
+    let text = match path.extension().and_then(|e| e.to_str()) {
+        Some("fm2") => movie.write_fm2(),
+        Some("txt") => movie.write_bizhawk(),
+        _ => movie.write(),
+    }.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    std::fs::write(path, text)
+}
+
+pub fn is_movie(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("rmv" | "fm2" | "txt"))
+}
+
+/// Warning text if `movie` was made with another ROM
+pub fn checksum_warning(movie: &Movie, checksum: &str) -> Option<String> {
+    movie.rom_checksum.as_ref()
+        .filter(|c| *c != checksum)
+        .map(|c| format!("movie was recorded with a different ROM ({c}), it will probably desync"))
+}
+
+pub enum Mode {
+    Record,
+    Play { frame: usize },
+}
+
+/// A movie being recorded or played back in the frontend
+pub struct Session {
+    pub movie: Movie,
+    pub mode: Mode,
+    pub path: PathBuf,
+    /// First frame that didn't match the recorded hash
+    pub mismatch: Option<usize>,
+    /// Input of the frame being run, until it finishes
+    current: Option<Frame>,
+}
+
+impl Session {
+    pub fn new(movie: Movie, mode: Mode, path: PathBuf) -> Self {
+        Self { movie, mode, path, mismatch: None, current: None }
+    }
+
+    /// Hold the buttons for the next frame, the movie's or when recording the controllers in
+    /// `host`. Returns `false` when playback reached the end.
+    pub fn start_frame(&mut self, nes: &mut nes::Nes, host: &nes::input::HostInput, reset: bool) -> bool {
+        let frame = match self.mode {
+            Mode::Record => Frame { joypads: [host.buttons[0], host.buttons[1]], reset, hash: None },
+            Mode::Play { frame } => match self.movie.frames.get(frame) {
+                Some(frame) => *frame,
+                None => return false,
+            },
+        };
+
+        Movie::start_frame(nes, &frame);
+        self.current = Some(frame);
+        true
+    }
+
+    /// Record the frame started last once it finished, or check it against the recording
+    pub fn end_frame(&mut self, nes: &nes::Nes) {
+        let Some(mut frame) = self.current.take() else { return };
+        let hash = nes.ppu.frame_hash();
+
+        match &mut self.mode {
+            Mode::Record => {
+                frame.hash = Some(hash);
+                self.movie.frames.push(frame);
+            },
+            Mode::Play { frame: i } => {
+                if self.mismatch.is_none() && frame.hash.is_some_and(|h| h != hash) {
+                    self.mismatch = Some(*i);
+                }
+
+                *i += 1;
+            },
+        }
+    }
+
+    pub fn status(&self) -> String {
+        let mut status = match self.mode {
+            Mode::Record => format!("recording frame {}", self.movie.frames.len()),
+            Mode::Play { frame } => format!("playing frame {frame}/{}", self.movie.frames.len()),
+        };
+
+        if let Some(frame) = self.mismatch {
+            status += &format!(", desynced on frame {frame}");
+        }
+
+        status
+    }
+}
@@ -0,0 +1,194 @@
+//! Shapes and text scripts draw over the picture, in NES pixels
+
+use nes::ppu::{self, Overscan};
+
+/// RGBA, alpha blended
+pub type Color = [u8; 4];
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Pixel(i32, i32, Color),
+    Line(i32, i32, i32, i32, Color),
+    /// Corners, fill and outline
+    Box(i32, i32, i32, i32, Color, Color),
+    /// Top left, text, text and background color
+    Text(i32, i32, String, Color, Color),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    pub shapes: Vec<Shape>,
+}
+
+/// 3x5 glyphs for ` ` to `_`, lowercase letters use the capitals
+const FONT: [[u8; 5]; 64] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], [0b010, 0b010, 0b010, 0b000, 0b010], [0b101, 0b101, 0b000, 0b000, 0b000], [0b101, 0b111, 0b101, 0b111, 0b101],
+    [0b011, 0b110, 0b010, 0b011, 0b110], [0b101, 0b001, 0b010, 0b100, 0b101], [0b010, 0b101, 0b010, 0b101, 0b011], [0b010, 0b010, 0b000, 0b000, 0b000],
+    [0b001, 0b010, 0b010, 0b010, 0b001], [0b100, 0b010, 0b010, 0b010, 0b100], [0b000, 0b101, 0b010, 0b101, 0b000], [0b000, 0b010, 0b111, 0b010, 0b000],
+    [0b000, 0b000, 0b000, 0b010, 0b100], [0b000, 0b000, 0b111, 0b000, 0b000], [0b000, 0b000, 0b000, 0b000, 0b010], [0b001, 0b001, 0b010, 0b100, 0b100],
+    [0b111, 0b101, 0b101, 0b101, 0b111], [0b010, 0b110, 0b010, 0b010, 0b111], [0b111, 0b001, 0b111, 0b100, 0b111], [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001], [0b111, 0b100, 0b111, 0b001, 0b111], [0b111, 0b100, 0b111, 0b101, 0b111], [0b111, 0b001, 0b001, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111], [0b111, 0b101, 0b111, 0b001, 0b111], [0b000, 0b010, 0b000, 0b010, 0b000], [0b000, 0b010, 0b000, 0b010, 0b100],
+    [0b001, 0b010, 0b100, 0b010, 0b001], [0b000, 0b111, 0b000, 0b111, 0b000], [0b100, 0b010, 0b001, 0b010, 0b100], [0b111, 0b001, 0b010, 0b000, 0b010],
+    [0b111, 0b101, 0b111, 0b100, 0b111], [0b010, 0b101, 0b111, 0b101, 0b101], [0b110, 0b101, 0b110, 0b101, 0b110], [0b011, 0b100, 0b100, 0b100, 0b011],
+    [0b110, 0b101, 0b101, 0b101, 0b110], [0b111, 0b100, 0b110, 0b100, 0b111], [0b111, 0b100, 0b110, 0b100, 0b100], [0b011, 0b100, 0b101, 0b101, 0b011],
+    [0b101, 0b101, 0b111, 0b101, 0b101], [0b111, 0b010, 0b010, 0b010, 0b111], [0b001, 0b001, 0b001, 0b101, 0b010], [0b101, 0b101, 0b110, 0b101, 0b101],
+    [0b100, 0b100, 0b100, 0b100, 0b111], [0b101, 0b111, 0b111, 0b101, 0b101], [0b110, 0b101, 0b101, 0b101, 0b101], [0b010, 0b101, 0b101, 0b101, 0b010],
+    [0b110, 0b101, 0b110, 0b100, 0b100], [0b010, 0b101, 0b101, 0b110, 0b011], [0b110, 0b101, 0b110, 0b101, 0b101], [0b011, 0b100, 0b010, 0b001, 0b110],
+    [0b111, 0b010, 0b010, 0b010, 0b010], [0b101, 0b101, 0b101, 0b101, 0b111], [0b101, 0b101, 0b101, 0b101, 0b010], [0b101, 0b101, 0b111, 0b111, 0b101],
+    [0b101, 0b101, 0b010, 0b101, 0b101], [0b101, 0b101, 0b010, 0b010, 0b010], [0b111, 0b001, 0b010, 0b100, 0b111], [0b011, 0b010, 0b010, 0b010, 0b011],
+    [0b100, 0b100, 0b010, 0b001, 0b001], [0b110, 0b010, 0b010, 0b010, 0b110], [0b010, 0b101, 0b000, 0b000, 0b000], [0b000, 0b000, 0b000, 0b000, 0b111],
+];
+
+/// Pixels a glyph advances
+const ADVANCE: i32 = 4;
+
+impl Overlay {
+    /// Draw onto a cropped frame with `per_pixel` columns per NES pixel
+    pub fn draw(&self, rgba: &mut [u8], per_pixel: usize, overscan: &Overscan) {
+        let (width, height) = (overscan.width() as i32, overscan.height() as i32);
+
+        let mut plot = |x: i32, y: i32, color: Color| {
+            let (x, y) = (x - overscan.left as i32, y - overscan.top as i32);
+
+            if color[3] == 0 || !(0..width).contains(&x) || !(0..height).contains(&y) {
+                return;
+            }
+
+            for col in 0..per_pixel {
+                let i = ((y as usize * width as usize + x as usize) * per_pixel + col) * 4;
+                let a = color[3] as u32;
+
+                for c in 0..3 {
+                    rgba[i + c] = ((rgba[i + c] as u32 * (255 - a) + color[c] as u32 * a) / 255) as u8;
+                }
+            }
+        };
+
+        for shape in &self.shapes {
+            match *shape {
+                Shape::Pixel(x, y, color) => plot(x, y, color),
+                Shape::Line(x1, y1, x2, y2, color) => line(x1, y1, x2, y2, |x, y| plot(x, y, color)),
+                Shape::Box(x1, y1, x2, y2, fill, outline) => {
+                    let (left, right, top, bottom) = (x1.min(x2), x1.max(x2), y1.min(y2), y1.max(y2));
+                    // only what's on the screen, there can be a lot more of it
+                    let xs = left.max(-1)..=right.min(ppu::FRAME_WIDTH as i32);
+                    let ys = top.max(-1)..=bottom.min(ppu::FRAME_HEIGHT as i32);
+
+                    for y in ys {
+                        for x in xs.clone() {
+                            let edge = x == left || x == right || y == top || y == bottom;
+                            plot(x, y, if edge { outline } else { fill });
+                        }
+                    }
+                },
+                Shape::Text(x, y, ref text, fg, bg) => {
+                    for (row, line) in text.lines().enumerate() {
+                        let y = y + row as i32 * 7;
+                        let x2 = x + line.chars().count() as i32 * ADVANCE;
+
+                        // every column only once, so half transparent backgrounds stay even
+                        for by in y - 1..y + 6 {
+                            for bx in x - 1..x2 {
+                                plot(bx, by, bg);
+                            }
+                        }
+
+                        for (i, c) in line.chars().enumerate() {
+                            let glyph = glyph(c);
+
+                            for (gy, bits) in glyph.iter().enumerate() {
+                                for gx in 0..3 {
+                                    if bits >> (2 - gx) & 1 != 0 {
+                                        plot(x + i as i32 * ADVANCE + gx, y + gy as i32, fg);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase() as u32;
+    match c {
+        0x20..=0x5f => FONT[c as usize - 0x20],
+        // `{|}~` and anything else
+        _ => FONT[b'?' as usize - 0x20],
+    }
+}
+
+/// Bresenham's, endpoints included
+fn line(x1: i32, y1: i32, x2: i32, y2: i32, mut plot: impl FnMut(i32, i32)) {
+    let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+    let (sx, sy) = ((x2 - x1).signum(), (y2 - y1).signum());
+    let (mut x, mut y, mut err) = (x1, y1, dx + dy);
+
+    // lines reaching far off the screen are cut short rather than walked to their end
+    let limit = (ppu::FRAME_WIDTH + ppu::FRAME_HEIGHT) as i32 * 4;
+
+    for _ in 0..=limit {
+        plot(x, y);
+
+        if x == x2 && y == y2 {
+            break;
+        }
+
+        let e2 = 2 * err;
+
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipping_and_blending() {
+        let overscan = Overscan::TV;
+        let (width, height) = (overscan.width(), overscan.height());
+        let red = [255, 0, 0, 255];
+
+        // above, below and left of what's shown, then two columns per pixel
+        let overlay = Overlay {
+            shapes: vec![
+                Shape::Pixel(0, 7, red), Shape::Pixel(0, 232, red), Shape::Pixel(-1, 8, red),
+                Shape::Pixel(1, 8, red), Shape::Pixel(2, 8, [0, 0, 255, 128]),
+            ],
+        };
+        let mut rgba = vec![0; width * height * 2 * 4];
+        overlay.draw(&mut rgba, 2, &overscan);
+
+        assert_eq!(rgba[..8], [0; 8]);
+        assert_eq!(rgba[8..16], [255, 0, 0, 0, 255, 0, 0, 0]);
+        assert_eq!(rgba[16..24], [0, 0, 128, 0, 0, 0, 128, 0]);
+        assert_eq!(rgba.iter().filter(|&&b| b != 0).count(), 4);
+
+        // shapes reaching far off the screen only draw what's on it
+        let overlay = Overlay {
+            shapes: vec![
+                Shape::Box(-1_000_000, -1_000_000, 1_000_000, 1_000_000, [0, 255, 0, 255], red),
+                Shape::Line(0, 0, 1_000_000, 1_000_000, [0, 0, 255, 255]),
+            ],
+        };
+        let mut rgba = vec![0; width * height * 4];
+        overlay.draw(&mut rgba, 1, &overscan);
+
+        let px = |x: usize, y: usize| &rgba[(y * width + x) * 4..][..3];
+        assert_eq!(px(8, 0), [0, 0, 255]);
+        assert_eq!(px(9, 0), [0, 255, 0]);
+        assert_eq!(px(width - 1, height - 1), [0, 255, 0]);
+    }
+}
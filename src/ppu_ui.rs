@@ -0,0 +1,310 @@
+use imgui_glow_renderer::AutoRenderer;
+use nes::{palette::Palette, Nes};
+
+use crate::texture::Texture;
+
+const SCROLL_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
+/// Nametable, pattern table, palette and OAM windows
+#[derive(Default)]
+pub struct PpuWindows {
+    pub nametables: bool,
+    pub pattern_tables: bool,
+    pub palettes: bool,
+    pub oam: bool,
+
+    /// 0-3 background, 4-7 sprite palettes
+    pattern_palette: usize,
+    textures: Option<Textures>,
+}
+
+struct Textures {
+    nametables: Texture,
+    pattern_tables: Texture,
+    sprites: Texture,
+}
+
+/// Pixel values 0-3 of one row of a tile
+fn tile_row(nes: &Nes, addr: u16) -> [u8; 8] {
+    let lo = nes.ppu_peek(addr);
+    let hi = nes.ppu_peek(addr + 8);
+    std::array::from_fn(|i| ((lo >> (7 - i)) & 1) | (((hi >> (7 - i)) & 1) << 1))
+}
+
+/// RGBA of a palette RAM entry with the current emphasis bits
+fn color(nes: &Nes, palette: &Palette, value: u8) -> [u8; 4] {
+    palette.rgba((value as u16 & 0x3f) | (nes.ppu.emphasis as u16) << 6)
+}
+
+/// RGBA of every palette RAM entry, with the backdrop color mirrored into every palette
+fn colors(nes: &Nes, palette: &Palette) -> [[u8; 4]; 32] {
+    std::array::from_fn(|i| {
+        let i = if i % 4 == 0 { 0 } else { i };
+        color(nes, palette, nes.ppu_peek(0x3f00 + i as u16))
+    })
+}
+
+fn put(rgba: &mut [u8], width: usize, x: usize, y: usize, color: [u8; 4]) {
+    let i = (y * width + x) * 4;
+    rgba[i..i + 4].copy_from_slice(&color);
+}
+
+impl PpuWindows {
+    pub fn menu(&mut self, ui: &imgui::Ui) {
+        ui.menu_item_config("Nametables").build_with_ref(&mut self.nametables);
+        ui.menu_item_config("Pattern tables").build_with_ref(&mut self.pattern_tables);
+        ui.menu_item_config("Palettes").build_with_ref(&mut self.palettes);
+        ui.menu_item_config("OAM").build_with_ref(&mut self.oam);
+    }
+
+    /// Redraw the textures of open windows, must happen outside of an imgui frame
+    pub fn update(&mut self, renderer: &mut AutoRenderer, nes: &Nes, palette: &Palette) {
+        let textures = self.textures.get_or_insert_with(|| Textures {
+            nametables: Texture::new(renderer, 512, 480),
+            pattern_tables: Texture::new(renderer, 256, 128),
+            sprites: Texture::new(renderer, 64, 128),
+        });
+        let gl = renderer.gl_context();
+        let colors = colors(nes, palette);
+
+        if self.nametables {
+            let tex = &textures.nametables;
+            let mut rgba = vec![0; tex.width * tex.height * 4];
+            let table = if nes.ppu.bg_pattern { 0x1000 } else { 0 };
+
+            for nt in 0..4 {
+                let base = 0x2000 + nt * 0x400;
+
+                for ty in 0..30 {
+                    for tx in 0..32 {
+                        let tile = nes.ppu_peek(base + ty * 32 + tx) as u16;
+                        let attr = nes.ppu_peek(base + 0x3c0 + (ty / 4) * 8 + tx / 4);
+                        let pal = ((attr >> (((ty & 2) << 1) | (tx & 2))) & 3) as usize;
+
+                        for row in 0..8 {
+                            let px = tile_row(nes, table + tile * 16 + row);
+                            let y = (nt as usize >> 1) * 240 + (ty * 8 + row) as usize;
+
+                            for (col, &p) in px.iter().enumerate() {
+                                let x = (nt as usize & 1) * 256 + tx as usize * 8 + col;
+                                put(&mut rgba, tex.width, x, y, colors[pal * 4 + p as usize]);
+                            }
+                        }
+                    }
+                }
+            }
+
+            tex.update(gl, &rgba);
+        }
+
+        if self.pattern_tables {
+            let tex = &textures.pattern_tables;
+            let mut rgba = vec![0; tex.width * tex.height * 4];
+
+            for tile in 0..512 {
+                for row in 0..8 {
+                    let px = tile_row(nes, tile * 16 + row);
+                    let x = (tile as usize / 256) * 128 + (tile as usize % 16) * 8;
+                    let y = (tile as usize / 16 % 16) * 8 + row as usize;
+
+                    for (col, &p) in px.iter().enumerate() {
+                        put(&mut rgba, tex.width, x + col, y, colors[self.pattern_palette * 4 + p as usize]);
+                    }
+                }
+            }
+
+            tex.update(gl, &rgba);
+        }
+
+        if self.oam {
+            // 8 columns of 8 sprites, each 8x16 so tall sprites fit as well
+            let tex = &textures.sprites;
+            let mut rgba = vec![0; tex.width * tex.height * 4];
+
+            for (i, s) in nes.ppu.oam.chunks(4).enumerate() {
+                let (tile, attr) = (s[1] as u16, s[2]);
+                let pal = 16 + (attr & 3) as usize * 4;
+                let (addr, height) = if nes.ppu.large_sprite {
+                    ((tile & 1) * 0x1000 + (tile & 0xfe) * 16, 16)
+                } else {
+                    ((nes.ppu.sp_pattern as u16) * 0x1000 + tile * 16, 8)
+                };
+
+                for row in 0..height {
+                    // the second tile of a tall sprite follows the first one
+                    let px = tile_row(nes, addr + (row / 8) * 16 + row % 8);
+                    let y = (i / 8) * 16 + if attr & 0x80 != 0 { height - 1 - row } else { row } as usize;
+
+                    for (col, &p) in px.iter().enumerate() {
+                        let x = (i % 8) * 8 + if attr & 0x40 != 0 { 7 - col } else { col };
+                        let color = if p == 0 { [0; 4] } else { colors[pal + p as usize] };
+                        put(&mut rgba, tex.width, x, y, color);
+                    }
+                }
+            }
+
+            tex.update(gl, &rgba);
+        }
+    }
+
+    pub fn draw(&mut self, ui: &imgui::Ui, nes: &Nes, palette: &Palette) {
+        let Some(textures) = &self.textures else { return };
+
+        if self.nametables {
+            ui.window("Nametables").opened(&mut self.nametables).build(|| {
+                nametables_window(ui, nes, &textures.nametables);
+            });
+        }
+
+        if self.pattern_tables {
+            let mut open = true;
+            ui.window("Pattern tables").opened(&mut open).build(|| {
+                let names = ["BG 0", "BG 1", "BG 2", "BG 3", "Sprite 0", "Sprite 1", "Sprite 2", "Sprite 3"];
+                ui.set_next_item_width(96.0);
+                ui.combo_simple_string("Palette", &mut self.pattern_palette, &names);
+
+                let tex = &textures.pattern_tables;
+                imgui::Image::new(tex.id, [tex.width as f32 * 2.0, tex.height as f32 * 2.0]).build(ui);
+
+                if ui.is_item_hovered() {
+                    let [x, y] = hovered_pixel(ui, 2.0);
+                    let tile = (x / 128) * 256 + (y / 8) * 16 + (x % 128) / 8;
+                    ui.tooltip_text(format!("Tile ${:02X} at ${:04X}", tile % 256, tile * 16));
+                }
+            });
+            self.pattern_tables = open;
+        }
+
+        if self.palettes {
+            ui.window("Palettes").opened(&mut self.palettes).build(|| palettes_window(ui, nes, palette));
+        }
+
+        if self.oam {
+            ui.window("OAM").opened(&mut self.oam).build(|| oam_window(ui, nes, &textures.sprites));
+        }
+    }
+}
+
+/// Texture pixel under the mouse for the last drawn image item
+fn hovered_pixel(ui: &imgui::Ui, scale: f32) -> [usize; 2] {
+    let [mx, my] = ui.io().mouse_pos;
+    let [ix, iy] = ui.item_rect_min();
+    [((mx - ix) / scale).max(0.0) as usize, ((my - iy) / scale).max(0.0) as usize]
+}
+
+fn nametables_window(ui: &imgui::Ui, nes: &Nes, tex: &Texture) {
+    let (nt, x, y) = nes.ppu.scroll_origin();
+    ui.text(format!("Scroll: ${nt:04X} + ({x}, {y})"));
+
+    let [ox, oy] = ui.cursor_screen_pos();
+    let [w, h] = [tex.width as f32, tex.height as f32];
+    imgui::Image::new(tex.id, [w, h]).build(ui);
+
+    if ui.is_item_hovered() {
+        let [px, py] = hovered_pixel(ui, 1.0);
+        let addr = 0x2000 + (py / 240) * 0x800 + (px / 256) * 0x400 + (py % 240 / 8) * 32 + (px % 256 / 8);
+        ui.tooltip_text(format!("${addr:04X}: tile ${:02X}", nes.ppu_peek(addr as u16)));
+    }
+
+    // the visible area wraps around at the edges of the four nametables
+    let sx = ((nt & 0x400) >> 10) as f32 * 256.0 + x as f32;
+    let sy = ((nt & 0x800) >> 11) as f32 * 240.0 + y as f32;
+    let draw_list = ui.get_window_draw_list();
+
+    draw_list.with_clip_rect_intersect([ox, oy], [ox + w, oy + h], || {
+        for dx in [0.0, -w] {
+            for dy in [0.0, -h] {
+                let min = [ox + sx + dx, oy + sy + dy];
+                draw_list.add_rect(min, [min[0] + 256.0, min[1] + 240.0], SCROLL_COLOR).build();
+            }
+        }
+    });
+}
+
+fn palettes_window(ui: &imgui::Ui, nes: &Nes, palette: &Palette) {
+    const SIZE: f32 = 20.0;
+    let draw_list = ui.get_window_draw_list();
+
+    for (half, name) in ["Background", "Sprites"].iter().enumerate() {
+        ui.text(name);
+
+        for row in 0..4 {
+            for col in 0..4 {
+                let i = half * 16 + row * 4 + col;
+                let value = nes.ppu_peek(0x3f00 + i as u16);
+                let [r, g, b, _] = color(nes, palette, value);
+
+                let min = ui.cursor_screen_pos();
+                ui.invisible_button(format!("##pal{i}"), [SIZE, SIZE]);
+                draw_list.add_rect(min, [min[0] + SIZE, min[1] + SIZE], [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0])
+                    .filled(true)
+                    .build();
+
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(format!("${:04X}: {value:02X}", 0x3f00 + i));
+                }
+
+                ui.same_line();
+            }
+
+            ui.dummy([SIZE / 2.0, SIZE]);
+
+            if row != 3 {
+                ui.same_line();
+            }
+        }
+    }
+}
+
+fn oam_window(ui: &imgui::Ui, nes: &Nes, tex: &Texture) {
+    const SCALE: f32 = 3.0;
+
+    imgui::Image::new(tex.id, [tex.width as f32 * SCALE, tex.height as f32 * SCALE]).build(ui);
+
+    if ui.is_item_hovered() {
+        let [x, y] = hovered_pixel(ui, SCALE);
+        ui.tooltip_text(format!("Sprite {}", (y / 16) * 8 + x / 8));
+    }
+
+    ui.same_line();
+
+    ui.child_window("sprites").build(|| {
+        ui.columns(7, "oam", true);
+
+        for name in ["#", "X", "Y", "Tile", "Palette", "Priority", "Flip"] {
+            ui.text(name);
+            ui.next_column();
+        }
+
+        ui.separator();
+
+        for (i, s) in nes.ppu.oam.chunks(4).enumerate() {
+            let (y, tile, attr, x) = (s[0], s[1], s[2], s[3]);
+            let flip = match attr >> 6 {
+                0 => "",
+                1 => "H",
+                2 => "V",
+                _ => "HV",
+            };
+
+            for text in [
+                format!("{i}"),
+                format!("{x}"),
+                // sprites are drawn one scanline below their Y
+                format!("{}", y as usize + 1),
+                format!("${tile:02X}"),
+                format!("{}", attr & 3),
+                (if attr & 0x20 != 0 { "Behind" } else { "Front" }).to_string(),
+                flip.to_string(),
+            ] {
+                if y >= 0xef {
+                    ui.text_disabled(text);
+                } else {
+                    ui.text(text);
+                }
+
+                ui.next_column();
+            }
+        }
+    });
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use nes::Nes;
+
+use crate::video::{Frame, Video};
+
+/// Writes every emulated frame to a Y4M video and the APU output to a WAV file. Both are timed
+/// by emulated frames and samples, never the wall clock, so captures are reproducible.
+pub struct Recorder {
+    video: BufWriter<File>,
+    audio: Wav,
+    size: [usize; 2],
+    /// [`nes::ppu::Ppu::frame`] last written
+    frame: usize,
+}
+
+impl Recorder {
+    /// The video's size is the first frame's, later frames are stretched to it
+    pub fn create(path: &Path, audio: &Path, nes: &Nes, first: &Frame) -> io::Result<Self> {
+        let mut video = BufWriter::new(File::create(path)?);
+        let (num, den) = nes.region.frame_rate_ratio();
+
+        // 4:4:4 so there's no chroma subsampling smearing the pixel art
+        writeln!(video, "YUV4MPEG2 W{} H{} F{num}:{den} Ip A1:1 C444", first.width, first.height)?;
+
+        Ok(Self { video, audio: Wav::create(audio, nes.apu.sample_rate)?, size: [first.width, first.height], frame: nes.ppu.frame })
+    }
+
+    /// Append the frame that just finished and the audio generated during it. Call before the
+    /// samples are cleared. Nothing is written until another frame finished.
+    pub fn frame(&mut self, nes: &Nes, video: &Video) -> io::Result<()> {
+        if nes.ppu.frame == self.frame {
+            return Ok(());
+        }
+
+        self.frame = nes.ppu.frame;
+        let frame = video.render(nes);
+        let rgba = match [frame.width, frame.height] == self.size {
+            true => frame.rgba,
+            false => crate::screenshot::resize(&frame.rgba, [frame.width, frame.height], self.size),
+        };
+        let mut planes = vec![0; rgba.len() / 4 * 3];
+        let (y, uv) = planes.split_at_mut(rgba.len() / 4);
+        let (u, v) = uv.split_at_mut(rgba.len() / 4);
+
+        for (i, px) in rgba.chunks(4).enumerate() {
+            // BT.601 studio range
+            let [r, g, b] = [px[0] as i32, px[1] as i32, px[2] as i32];
+            y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+
+        self.video.write_all(b"FRAME\n")?;
+        self.video.write_all(&planes)?;
+        self.audio.write(&nes.apu.samples)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.video.flush()?;
+        self.audio.finish()
+    }
+}
+
+/// 16 bit mono PCM, the sizes in the header are filled in by [`Wav::finish`]
+struct Wav {
+    file: BufWriter<File>,
+    samples: u32,
+}
+
+impl Wav {
+    fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&1u16.to_le_bytes())?; // channels
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * 2).to_le_bytes())?; // bytes per second
+        file.write_all(&2u16.to_le_bytes())?; // bytes per sample
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data\0\0\0\0")?;
+
+        Ok(Self { file, samples: 0 })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &s in samples {
+            // the APU mixes to 0..1, keep the DC offset rather than pop when recording starts
+            let s = (s.clamp(0.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&s.to_le_bytes())?;
+        }
+
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let data = self.samples * 2;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + data).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&data.to_le_bytes())?;
+        self.file.flush()
+    }
+}
@@ -0,0 +1,78 @@
+use nes::cart::Cartridge;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How often battery RAM is written while a game runs, in case the emulator doesn't exit cleanly
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Battery backed RAM of the running cartridge, kept in `<rom>.sav`
+pub struct SaveFile {
+    path: PathBuf,
+    /// Contents as of the last write, to skip writing when nothing changed
+    saved: Vec<u8>,
+    last_write: Instant,
+}
+
+impl SaveFile {
+    /// Load the save next to `rom` into the cartridge. `None` if the cartridge has no battery.
+    pub fn open(rom: &Path, cart: &mut dyn Cartridge) -> io::Result<Option<Self>> {
+        let nvram = cart.nvram_mut();
+
+        if nvram.is_empty() {
+            return Ok(None);
+        }
+
+        let path = rom.with_extension("sav");
+
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let len = bytes.len().min(nvram.len());
+                nvram[..len].copy_from_slice(&bytes[..len]);
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+
+        Ok(Some(Self { path, saved: nvram.to_vec(), last_write: Instant::now() }))
+    }
+
+    /// Write the save if it changed since the last write
+    pub fn flush(&mut self, cart: &dyn Cartridge) -> io::Result<()> {
+        self.last_write = Instant::now();
+        let nvram = cart.nvram();
+
+        if nvram == self.saved {
+            return Ok(());
+        }
+
+        write_atomic(&self.path, nvram)?;
+        self.saved.copy_from_slice(nvram);
+        Ok(())
+    }
+
+    /// Flush every [`AUTOSAVE_INTERVAL`], call once per frame
+    pub fn autosave(&mut self, cart: &dyn Cartridge) -> io::Result<()> {
+        if self.last_write.elapsed() < AUTOSAVE_INTERVAL {
+            return Ok(());
+        }
+
+        self.flush(cart)
+    }
+}
+
+/// Write to a temporary file first so a crash halfway through can't destroy the old save
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("sav.tmp");
+
+    {
+        use std::io::Write;
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+
+    std::fs::rename(&tmp, path)
+}
@@ -0,0 +1,356 @@
+//! Pixel art upscalers and a scanline overlay, on RGBA images
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaler {
+    Scale2x,
+    Scale3x,
+    /// Blends corners cut off by an edge, with hqx's color test but a few rules in place of its
+    /// lookup tables, so the output isn't hqx's
+    Smooth2x,
+    Smooth3x,
+    /// Antialiases diagonal edges found with xBRZ's color distance, a much smaller rule set than
+    /// xBRZ's
+    Diagonal(usize),
+}
+
+impl Scaler {
+    pub const ALL: [Scaler; 7] = [
+        Scaler::Scale2x, Scaler::Scale3x, Scaler::Smooth2x, Scaler::Smooth3x,
+        Scaler::Diagonal(2), Scaler::Diagonal(3), Scaler::Diagonal(4),
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Scaler::Scale2x => "Scale2x".to_string(),
+            Scaler::Scale3x => "Scale3x".to_string(),
+            Scaler::Smooth2x => "Smooth 2x".to_string(),
+            Scaler::Smooth3x => "Smooth 3x".to_string(),
+            Scaler::Diagonal(n) => format!("Diagonal {n}x"),
+        }
+    }
+
+    pub fn factor(self) -> usize {
+        match self {
+            Scaler::Scale2x | Scaler::Smooth2x => 2,
+            Scaler::Scale3x | Scaler::Smooth3x => 3,
+            Scaler::Diagonal(n) => n,
+        }
+    }
+
+    /// Scale a `width` pixels wide image by [`Scaler::factor`]
+    pub fn scale(self, rgba: &[u8], width: usize) -> Vec<u8> {
+        let img = Image::new(rgba, width);
+        let n = self.factor();
+        let mut out = vec![[0; 4]; img.px.len() * n * n];
+
+        for y in 0..img.height {
+            for x in 0..img.width {
+                let block = match self {
+                    Scaler::Scale2x => scale2x(&img, x, y),
+                    Scaler::Scale3x => scale3x(&img, x, y),
+                    Scaler::Smooth2x | Scaler::Smooth3x => smooth(&img, x, y, n),
+                    Scaler::Diagonal(_) => diagonal(&img, x, y, n),
+                };
+
+                for (i, px) in block.into_iter().enumerate() {
+                    out[(y * n + i / n) * width * n + x * n + i % n] = px;
+                }
+            }
+        }
+
+        out.into_iter().flatten().collect()
+    }
+}
+
+/// Nearest neighbour, for when a filter needs more than one row per line
+pub fn double(rgba: &[u8], width: usize) -> Vec<u8> {
+    rgba.chunks(width * 4)
+        .flat_map(|row| {
+            let wide: Vec<u8> = row.chunks(4).flat_map(|px| [px, px]).flatten().copied().collect();
+            [wide.clone(), wide]
+        })
+        .flatten()
+        .collect()
+}
+
+/// Darken the last row of every `scale` rows, `strength` 1 is black
+pub fn scanlines(rgba: &mut [u8], width: usize, scale: usize, strength: f32) {
+    for row in rgba.chunks_mut(width * 4).skip(scale - 1).step_by(scale) {
+        for (i, c) in row.iter_mut().enumerate() {
+            if i % 4 != 3 {
+                *c = (*c as f32 * (1.0 - strength)).round() as u8;
+            }
+        }
+    }
+}
+
+type Px = [u8; 4];
+
+struct Image {
+    px: Vec<Px>,
+    width: usize,
+    height: usize,
+}
+
+impl Image {
+    fn new(rgba: &[u8], width: usize) -> Self {
+        let px: Vec<Px> = rgba.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect();
+        let height = px.len() / width;
+        Self { px, width, height }
+    }
+
+    /// Pixel at an offset from `x`, `y`, the edges repeat
+    fn at(&self, x: usize, y: usize, dx: isize, dy: isize) -> Px {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        self.px[y * self.width + x]
+    }
+
+    /// The 3x3 neighbourhood, row by row
+    fn around(&self, x: usize, y: usize) -> [Px; 9] {
+        std::array::from_fn(|i| self.at(x, y, i as isize % 3 - 1, i as isize / 3 - 1))
+    }
+}
+
+fn scale2x(img: &Image, x: usize, y: usize) -> Vec<Px> {
+    let [_, b, _, d, e, f, _, h, _] = img.around(x, y);
+
+    if b == h || d == f {
+        return vec![e; 4];
+    }
+
+    vec![
+        if d == b { d } else { e },
+        if b == f { f } else { e },
+        if d == h { d } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+fn scale3x(img: &Image, x: usize, y: usize) -> Vec<Px> {
+    let [a, b, c, d, e, f, g, h, i] = img.around(x, y);
+
+    if b == h || d == f {
+        return vec![e; 9];
+    }
+
+    vec![
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) { b } else { e },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) { d } else { e },
+        e,
+        if (b == f && e != i) || (h == f && e != c) { f } else { e },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) { h } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+/// Weighted average of colors
+fn mix(colors: &[(Px, u32)]) -> Px {
+    let total: u32 = colors.iter().map(|&(_, w)| w).sum();
+    std::array::from_fn(|c| (colors.iter().map(|&(px, w)| px[c] as u32 * w).sum::<u32>() / total) as u8)
+}
+
+/// hqx's test, colors are alike when their YUV differences are under its thresholds
+fn similar(a: Px, b: Px) -> bool {
+    let yuv = |[r, g, b, _]: Px| {
+        let [r, g, b] = [r as f32, g as f32, b as f32];
+        [0.299 * r + 0.587 * g + 0.114 * b, -0.169 * r - 0.331 * g + 0.5 * b, 0.5 * r - 0.419 * g - 0.081 * b]
+    };
+    let ([ya, ua, va], [yb, ub, vb]) = (yuv(a), yuv(b));
+    (ya - yb).abs() <= 48.0 && (ua - ub).abs() <= 7.0 && (va - vb).abs() <= 6.0
+}
+
+/// See [`Scaler::Smooth2x`]
+fn smooth(img: &Image, x: usize, y: usize, n: usize) -> Vec<Px> {
+    let around = img.around(x, y);
+    let e = around[4];
+
+    // corners clockwise from the top left, with their horizontal, vertical and diagonal neighbours
+    let corners = [(3, 1, 0), (5, 1, 2), (5, 7, 8), (3, 7, 6)].map(|(h, v, d)| {
+        let (h, v, d) = (around[h], around[v], around[d]);
+
+        if !similar(e, h) && !similar(e, v) && similar(h, v) {
+            // an edge cuts the corner off
+            match similar(e, d) {
+                true => Some(mix(&[(e, 2), (h, 1), (v, 1)])),
+                false => Some(mix(&[(e, 2), (h, 3), (v, 3)])),
+            }
+        } else if !similar(e, d) && similar(e, h) && similar(e, v) {
+            Some(mix(&[(e, 3), (d, 1)]))
+        } else {
+            None
+        }
+    });
+    let corner = |i: usize| corners[i].unwrap_or(e);
+
+    if n == 2 {
+        return vec![corner(0), corner(1), corner(3), corner(2)];
+    }
+
+    // the middle of a side follows an edge running along it
+    let side = |neighbour: usize, a: usize, b: usize| match corners[a].is_some() && corners[b].is_some() && !similar(e, around[neighbour]) {
+        true => mix(&[(e, 3), (around[neighbour], 1)]),
+        false => e,
+    };
+
+    vec![
+        corner(0), side(1, 0, 1), corner(1),
+        side(3, 0, 3), e, side(5, 1, 2),
+        corner(3), side(7, 3, 2), corner(2),
+    ]
+}
+
+/// xBRZ's perceptual distance
+fn distance(a: Px, b: Px) -> f32 {
+    let [r, g, b] = [0, 1, 2].map(|c| a[c] as f32 - b[c] as f32);
+    let y = 0.2627 * r + 0.6780 * g + 0.0593 * b;
+    let cb = 0.5 / (1.0 - 0.0593) * (b - y);
+    let cr = 0.5 / (1.0 - 0.2627) * (r - y);
+    (y * y + cb * cb + cr * cr).sqrt()
+}
+
+/// Which pixels of the 2x2 block at `x`, `y` (top left, top right, bottom left, bottom right)
+/// have their corner in the block's middle cut off by a diagonal edge
+fn diagonal_block(img: &Image, x: usize, y: usize) -> [bool; 4] {
+    let k = |dx: isize, dy: isize| img.at(x, y, dx, dy);
+    let [b, c, e, f, g, h, i, j, kk, l, n, o] =
+        [k(0, -1), k(1, -1), k(-1, 0), k(0, 0), k(1, 0), k(2, 0), k(-1, 1), k(0, 1), k(1, 1), k(2, 1), k(0, 2), k(1, 2)];
+
+    let jg = distance(i, f) + distance(f, c) + distance(n, kk) + distance(kk, h) + 4.0 * distance(j, g);
+    let fk = distance(e, j) + distance(j, o) + distance(b, g) + distance(g, l) + 4.0 * distance(f, kk);
+
+    if jg < fk {
+        // j and g are one line, f and k stick into it
+        [f != g && f != j, false, false, kk != g && kk != j]
+    } else if fk < jg {
+        [false, g != f && g != kk, j != f && j != kk, false]
+    } else {
+        [false; 4]
+    }
+}
+
+fn diagonal(img: &Image, x: usize, y: usize, n: usize) -> Vec<Px> {
+    let e = img.at(x, y, 0, 0);
+
+    // the blocks sharing this pixel's corners, and which of their pixels this one is
+    let corners = [(-1, -1, 3), (0, -1, 2), (-1, 0, 1), (0, 0, 0)].map(|(dx, dy, which)| {
+        let bx = x as isize + dx;
+        let by = y as isize + dy;
+        // blocks hanging over the edge are clamped, which keeps corners there unblended
+        bx >= 0 && by >= 0 && diagonal_block(img, bx as usize, by as usize)[which]
+    });
+
+    let mut out = vec![e; n * n];
+
+    for (corner, (sx, sy)) in corners.into_iter().zip([(-1, -1), (1, -1), (-1, 1), (1, 1)]) {
+        if !corner {
+            continue;
+        }
+
+        let (h, v) = (img.at(x, y, sx, 0), img.at(x, y, 0, sy));
+        let color = if distance(e, h) <= distance(e, v) { h } else { v };
+
+        for (i, px) in out.iter_mut().enumerate() {
+            // subpixel center mirrored so the corner is at 1, 1
+            let u = ((i % n) as f32 + 0.5) / n as f32;
+            let v = ((i / n) as f32 + 0.5) / n as f32;
+            let (u, v) = (if sx < 0 { 1.0 - u } else { u }, if sy < 0 { 1.0 - v } else { v });
+
+            // the edge runs between the middles of the two sides meeting at the corner
+            let coverage = ((u + v - 1.5) / std::f32::consts::SQRT_2 * n as f32 + 0.5).clamp(0.0, 1.0);
+            let w = (coverage * 16.0).round() as u32;
+
+            if w > 0 {
+                *px = mix(&[(*px, 16 - w), (color, w)]);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Red of the top left `n`x`n` pixels of a black corner on a 4x4 white image, cut by a
+    /// diagonal. Everything else has to stay white and all of it gray.
+    fn corner(scaler: Scaler, n: usize) -> Vec<Vec<u8>> {
+        let (k, w) = ([0, 0, 0, 255], [255; 4]);
+        let img = [k, k, w, w, k, w, w, w, w, w, w, w, w, w, w, w].concat();
+        let width = 4 * scaler.factor();
+        let out = scaler.scale(&img, 4);
+        assert_eq!(out.len(), width * width * 4);
+
+        for (i, px) in out.chunks(4).enumerate() {
+            match i % width < n && i / width < n {
+                true => assert_eq!(px, [px[0], px[0], px[0], 255]),
+                false => assert_eq!(px, w, "{scaler:?} at {i}"),
+            }
+        }
+
+        out.chunks(width * 4).take(n).map(|row| row.chunks(4).take(n).map(|px| px[0]).collect()).collect()
+    }
+
+    #[test]
+    fn scalers() {
+        assert_eq!(corner(Scaler::Scale2x, 4), [
+            [0, 0, 0, 0],
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [0, 255, 255, 255],
+        ]);
+        assert_eq!(corner(Scaler::Scale3x, 6), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 255],
+            [0, 0, 0, 0, 0, 255],
+            [0, 0, 0, 0, 255, 255],
+            [0, 0, 0, 255, 255, 255],
+            [0, 255, 255, 255, 255, 255],
+        ]);
+        assert_eq!(corner(Scaler::Smooth2x, 5), [
+            [0, 0, 0, 0, 255],
+            [0, 63, 0, 191, 255],
+            [0, 0, 63, 255, 191],
+            [0, 191, 255, 255, 255],
+            [255, 255, 191, 255, 255],
+        ]);
+        assert_eq!(corner(Scaler::Smooth3x, 7), [
+            [0, 0, 0, 0, 0, 0, 255],
+            [0, 0, 0, 0, 0, 0, 255],
+            [0, 0, 63, 0, 0, 191, 255],
+            [0, 0, 0, 63, 255, 255, 191],
+            [0, 0, 0, 255, 255, 255, 255],
+            [0, 0, 191, 255, 255, 255, 255],
+            [255, 255, 255, 191, 255, 255, 255],
+        ]);
+        assert_eq!(corner(Scaler::Diagonal(2), 4), [
+            [0, 0, 0, 0],
+            [0, 0, 0, 127],
+            [0, 0, 127, 255],
+            [0, 127, 255, 255],
+        ]);
+        assert_eq!(corner(Scaler::Diagonal(3), 6), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 31],
+            [0, 0, 0, 0, 31, 223],
+            [0, 0, 0, 31, 223, 255],
+            [0, 0, 31, 223, 255, 255],
+            [0, 31, 223, 255, 255, 255],
+        ]);
+    }
+
+    #[test]
+    fn scanlines_and_double() {
+        let mut rgba = double(&[10, 20, 30, 255, 40, 50, 60, 255], 2);
+        assert_eq!(rgba.len(), 4 * 2 * 4);
+        assert_eq!(rgba[..16], rgba[16..]);
+
+        scanlines(&mut rgba, 4, 2, 0.5);
+        assert_eq!(rgba[..8], [10, 20, 30, 255, 10, 20, 30, 255]);
+        assert_eq!(rgba[16..24], [5, 10, 15, 255, 5, 10, 15, 255]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use nes::Nes;
+
+use crate::video::Video;
+
+/// Write the current frame as a PNG. `aspect` stretches it horizontally to how a TV shows it.
+pub fn save_png(path: &Path, nes: &Nes, video: &Video, aspect: bool) -> io::Result<()> {
+    let frame = video.render(nes);
+
+    let (width, rgba) = if aspect {
+        let width = ((video.overscan.width() * frame.scale) as f64 * nes.region.pixel_aspect()).round() as usize;
+        (width, stretch(&frame.rgba, frame.width, width))
+    } else {
+        (frame.width, frame.rgba)
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, frame.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&rgba)?;
+    Ok(())
+}
+
+/// Write the palette indices with emphasis bits, see [`nes::ppu::Ppu::raw_frame`]
+pub fn save_raw(path: &Path, nes: &Nes) -> io::Result<()> {
+    std::fs::write(path, nes.ppu.raw_frame())
+}
+
+/// Resample every row of an RGBA image to `to` pixels, blending neighbours so the stretch doesn't
+/// leave uneven pixel widths
+pub fn stretch(rgba: &[u8], from: usize, to: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rgba.len() / from * to);
+
+    for row in rgba.chunks(from * 4) {
+        for x in 0..to {
+            // center of the output pixel in source pixels
+            let src = ((x as f64 + 0.5) * from as f64 / to as f64 - 0.5).max(0.0);
+            let left = (src as usize).min(from - 1);
+            let right = (left + 1).min(from - 1);
+            let t = src - left as f64;
+
+            for c in 0..4 {
+                let l = row[left * 4 + c] as f64;
+                let r = row[right * 4 + c] as f64;
+                out.push((l + (r - l) * t).round() as u8);
+            }
+        }
+    }
+
+    out
+}
+
+/// Fit an image into another size, rows are stretched and whole rows repeated or dropped
+pub fn resize(rgba: &[u8], from: [usize; 2], to: [usize; 2]) -> Vec<u8> {
+    let wide = stretch(rgba, from[0], to[0]);
+    let row = to[0] * 4;
+    (0..to[1]).flat_map(|y| &wide[y * from[1] / to[1] * row..][..row]).copied().collect()
+}
+
+/// `<rom>-0001.<ext>`, the first number that isn't taken
+pub fn next_path(rom: &Path, ext: &str) -> PathBuf {
+    let stem = rom.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+    (1..)
+        .map(|i| rom.with_file_name(format!("{stem}-{i:04}.{ext}")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
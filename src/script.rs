@@ -0,0 +1,556 @@
+//! Lua scripts with FCEUX's `emu`, `memory`, `joypad`, `savestate` and `gui` functions.
+//!
+//! The console is lent to Lua only while script code runs, every function looks it up again so
+//! they error rather than dangle when called from elsewhere. A script's main chunk runs as a
+//! coroutine that `emu.frameadvance()` yields, once a frame.
+
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use mlua::{AnyUserData, Function, Lua, RegistryKey, Table, ThreadStatus, Value};
+use nes::{input::joypad, palette::Palette, snapshot::Snapshot, Nes};
+
+use crate::overlay::{Color, Overlay, Shape};
+
+const NES: &str = "rustyness.nes";
+const MAIN: &str = "rustyness.main";
+const BEFORE: &str = "rustyness.before";
+const AFTER: &str = "rustyness.after";
+const EXIT: &str = "rustyness.exit";
+
+/// FCEUX's joypad table keys
+const BUTTONS: [(&str, u8); 8] = [
+    ("A", joypad::A), ("B", joypad::B), ("select", joypad::SELECT), ("start", joypad::START),
+    ("up", joypad::UP), ("down", joypad::DOWN), ("left", joypad::LEFT), ("right", joypad::RIGHT),
+];
+
+const COLORS: [(&str, Color); 12] = [
+    ("white", [255, 255, 255, 255]), ("black", [0, 0, 0, 255]), ("clear", [0, 0, 0, 0]),
+    ("red", [255, 0, 0, 255]), ("green", [0, 255, 0, 255]), ("blue", [0, 0, 255, 255]),
+    ("gray", [127, 127, 127, 255]), ("grey", [127, 127, 127, 255]), ("orange", [255, 127, 0, 255]),
+    ("yellow", [255, 255, 0, 255]), ("purple", [127, 0, 255, 255]), ("teal", [0, 127, 127, 255]),
+];
+
+/// What script functions leave for the frontend
+#[derive(Default)]
+struct Shared {
+    /// `joypad.set` for the next frame, buttons forced on and off
+    joypad: [(u8, u8); 4],
+    overlay: Overlay,
+    messages: Vec<String>,
+    reset: bool,
+    exit: bool,
+    /// `memory.registerexec` address ranges
+    exec: Vec<(u16, u16, RegistryKey)>,
+    /// What the P00 to P3F colors look like
+    palette: Palette,
+}
+
+/// `savestate.create()` objects
+struct State(Option<Snapshot>);
+
+impl mlua::UserData for State {}
+
+pub struct Script {
+    lua: Lua,
+    shared: Rc<RefCell<Shared>>,
+}
+
+fn error(msg: impl Into<String>) -> mlua::Error {
+    mlua::Error::RuntimeError(msg.into())
+}
+
+/// Run `f` on the console lent to the running script code
+fn with_nes<R>(lua: &Lua, f: impl FnOnce(&mut Nes) -> R) -> mlua::Result<R> {
+    let ud: Option<AnyUserData> = lua.named_registry_value(NES)?;
+    let ud = ud.ok_or_else(|| error("no game is running"))?;
+    let mut nes = ud.borrow_mut::<Nes>().map_err(|_| error("no game is running"))?;
+    Ok(f(&mut nes))
+}
+
+fn parse_color(lua: &Lua, value: Value, default: Color, palette: &Palette) -> mlua::Result<Color> {
+    Ok(match value {
+        Value::Nil => default,
+        Value::Integer(n) => (n as u32).to_be_bytes(),
+        Value::Number(n) => (n as u32).to_be_bytes(),
+        Value::String(s) => {
+            let s = s.to_str()?.to_ascii_lowercase();
+
+            if let Some(hex) = s.strip_prefix('#') {
+                let n = u32::from_str_radix(hex, 16).map_err(|_| error(format!("bad color `{s}`")))?;
+                match hex.len() {
+                    6 => (n << 8 | 0xff).to_be_bytes(),
+                    8 => n.to_be_bytes(),
+                    _ => return Err(error(format!("bad color `{s}`"))),
+                }
+            } else if let Some(index) = s.strip_prefix('p').and_then(|i| u8::from_str_radix(i, 16).ok()) {
+                // NES palette entries, P00 to P3F
+                palette.rgba(index as u16 & 0x3f)
+            } else {
+                COLORS.iter().find(|(name, _)| *name == s).ok_or_else(|| error(format!("unknown color `{s}`")))?.1
+            }
+        },
+        Value::Table(t) => {
+            let channel = |key: &str, i: i64, default: i64| -> mlua::Result<u8> {
+                let v: Option<i64> = t.get(key)?;
+                let v = match v {
+                    Some(v) => Some(v),
+                    None => t.get(i)?,
+                };
+                Ok(v.unwrap_or(default).clamp(0, 255) as u8)
+            };
+            [channel("r", 1, 0)?, channel("g", 2, 0)?, channel("b", 3, 0)?, channel("a", 4, 255)?]
+        },
+        _ => return Err(error(format!("bad color `{}`", lua.coerce_string(value)?.map_or("?".into(), |s| s.to_string_lossy().into_owned())))),
+    })
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let code = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let script = Self { lua: Lua::new(), shared: Default::default() };
+        script.install().map_err(|e| e.to_string())?;
+
+        let lua = &script.lua;
+        let main = lua.load(&code).set_name(path.to_string_lossy()).into_function().map_err(|e| e.to_string())?;
+        lua.set_named_registry_value(MAIN, lua.create_thread(main).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+
+        Ok(script)
+    }
+
+    /// Lend `nes` to the script while `f` runs
+    fn lend<R>(&self, nes: &mut Nes, f: impl FnOnce(&Lua) -> mlua::Result<R>) -> Result<R, String> {
+        let lua = &self.lua;
+
+        let result = lua.scope(|scope| {
+            lua.set_named_registry_value(NES, scope.create_any_userdata_ref_mut(nes)?)?;
+            f(lua)
+        });
+
+        _ = lua.unset_named_registry_value(NES);
+        result.map_err(|e| e.to_string())
+    }
+
+    fn call_hook(lua: &Lua, name: &str) -> mlua::Result<()> {
+        match lua.named_registry_value::<Option<Function>>(name)? {
+            Some(hook) => hook.call(()),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the main chunk up to its next `emu.frameadvance()` and `emu.registerbefore`, then
+    /// apply `joypad.set` to the frame's `buttons`
+    pub fn before_frame(&mut self, nes: &mut Nes, buttons: &mut [u8; 4]) -> Result<(), String> {
+        // drawings last a frame
+        self.shared.borrow_mut().overlay.shapes.clear();
+
+        self.lend(nes, |lua| {
+            let main: mlua::Thread = lua.named_registry_value(MAIN)?;
+
+            if main.status() == ThreadStatus::Resumable {
+                main.resume::<_, ()>(())?;
+            }
+
+            Self::call_hook(lua, BEFORE)
+        })?;
+
+        let joypad = std::mem::take(&mut self.shared.borrow_mut().joypad);
+
+        for (buttons, (on, off)) in buttons.iter_mut().zip(joypad) {
+            *buttons = (*buttons | on) & !off;
+        }
+
+        Ok(())
+    }
+
+    /// Emulate a frame, stopping at every instruction a `memory.registerexec` is waiting for
+    pub fn step_frame(&self, nes: &mut Nes) -> Result<(), String> {
+        if self.shared.borrow().exec.is_empty() {
+            nes.step_frame();
+            return Ok(());
+        }
+
+        let shared = &self.shared;
+
+        self.lend(nes, |lua| {
+            let ud: AnyUserData = lua.named_registry_value(NES)?;
+            let frame = ud.borrow::<Nes>()?.ppu.frame;
+
+            loop {
+                let pc = {
+                    let nes = ud.borrow::<Nes>()?;
+
+                    if nes.ppu.frame != frame || nes.halted() {
+                        break;
+                    }
+
+                    nes.cpu.pc
+                };
+
+                let hooks: Vec<Function> = shared.borrow().exec.iter()
+                    .filter(|(start, end, _)| (*start..=*end).contains(&pc))
+                    .map(|(_, _, key)| lua.registry_value(key))
+                    .collect::<mlua::Result<_>>()?;
+
+                for hook in hooks {
+                    hook.call::<_, ()>(pc)?;
+                }
+
+                ud.borrow_mut::<Nes>()?.step_instruction();
+            }
+
+            Ok(())
+        })
+    }
+
+    pub fn after_frame(&mut self, nes: &mut Nes) -> Result<(), String> {
+        self.lend(nes, |lua| Self::call_hook(lua, AFTER))
+    }
+
+    /// Call `emu.registerexit`'s function, the script is done
+    pub fn finish(self, nes: Option<&mut Nes>) -> Result<(), String> {
+        match nes {
+            Some(nes) => self.lend(nes, |lua| Self::call_hook(lua, EXIT)),
+            None => Self::call_hook(&self.lua, EXIT).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn overlay(&self) -> Overlay {
+        self.shared.borrow().overlay.clone()
+    }
+
+    /// `emu.message` texts since the last call
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.shared.borrow_mut().messages)
+    }
+
+    /// `emu.softreset()` was called
+    pub fn take_reset(&mut self) -> bool {
+        std::mem::take(&mut self.shared.borrow_mut().reset)
+    }
+
+    /// Colors for `P00` to `P3F`, the default 2C02 palette until set
+    pub fn set_palette(&mut self, palette: &Palette) {
+        let mut shared = self.shared.borrow_mut();
+        if shared.palette != *palette {
+            shared.palette = palette.clone();
+        }
+    }
+
+    /// `emu.exit()` was called
+    pub fn exited(&self) -> bool {
+        self.shared.borrow().exit
+    }
+
+    fn install(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let globals = lua.globals();
+
+        let emu = lua.create_table()?;
+        let memory = lua.create_table()?;
+        let joypad = lua.create_table()?;
+        let savestate = lua.create_table()?;
+        let gui = lua.create_table()?;
+
+        // emu
+
+        emu.set("framecount", lua.create_function(|lua, ()| with_nes(lua, |nes| nes.ppu.frame))?)?;
+        emu.set("emulating", lua.create_function(|lua, ()| Ok(with_nes(lua, |_| ()).is_ok()))?)?;
+
+        let shared = self.shared.clone();
+        emu.set("softreset", lua.create_function(move |_, ()| {
+            shared.borrow_mut().reset = true;
+            Ok(())
+        })?)?;
+
+        let shared = self.shared.clone();
+        emu.set("message", lua.create_function(move |lua, value: Value| {
+            let text = lua.coerce_string(value)?.map_or("nil".to_string(), |s| s.to_string_lossy().into_owned());
+            shared.borrow_mut().messages.push(text);
+            Ok(())
+        })?)?;
+
+        let shared = self.shared.clone();
+        emu.set("exit", lua.create_function(move |_, ()| {
+            shared.borrow_mut().exit = true;
+            Ok(())
+        })?)?;
+
+        for (name, key) in [("registerbefore", BEFORE), ("registerafter", AFTER), ("registerexit", EXIT)] {
+            emu.set(name, lua.create_function(move |lua, f: Option<Function>| lua.set_named_registry_value(key, f))?)?;
+        }
+
+        emu.set("print", globals.get::<_, Function>("print")?)?;
+
+        // memory
+
+        let read = |lua: &Lua, addr: i64| with_nes(lua, |nes| nes.peek(addr as u16));
+
+        memory.set("readbyte", lua.create_function(move |lua, addr: i64| read(lua, addr))?)?;
+        memory.set("readbyteunsigned", lua.create_function(move |lua, addr: i64| read(lua, addr))?)?;
+        memory.set("readbytesigned", lua.create_function(move |lua, addr: i64| Ok(read(lua, addr)? as i8))?)?;
+
+        let read_word = move |lua: &Lua, (addr, high): (i64, Option<i64>)| -> mlua::Result<u16> {
+            Ok(read(lua, addr)? as u16 | (read(lua, high.unwrap_or(addr + 1))? as u16) << 8)
+        };
+
+        memory.set("readword", lua.create_function(read_word)?)?;
+        memory.set("readwordunsigned", lua.create_function(read_word)?)?;
+        memory.set("readwordsigned", lua.create_function(move |lua, args| Ok(read_word(lua, args)? as i16))?)?;
+
+        memory.set("readbyterange", lua.create_function(|lua, (addr, len): (i64, i64)| {
+            let bytes = with_nes(lua, |nes| (0..len.max(0)).map(|i| nes.peek((addr + i) as u16)).collect::<Vec<u8>>())?;
+            lua.create_string(bytes)
+        })?)?;
+
+        memory.set("writebyte", lua.create_function(|lua, (addr, value): (i64, i64)| {
+            with_nes(lua, |nes| nes.poke(addr as u16, value as u8))
+        })?)?;
+
+        memory.set("getregister", lua.create_function(|lua, name: String| {
+            with_nes(lua, |nes| {
+                let cpu = &nes.cpu;
+                Ok(match name.to_ascii_lowercase().as_str() {
+                    "a" => cpu.a as u16,
+                    "x" => cpu.x as u16,
+                    "y" => cpu.y as u16,
+                    "s" => cpu.s as u16,
+                    "p" => cpu.p as u16,
+                    "pc" => cpu.pc,
+                    _ => return Err(error(format!("unknown register `{name}`"))),
+                })
+            })?
+        })?)?;
+
+        memory.set("setregister", lua.create_function(|lua, (name, value): (String, i64)| {
+            with_nes(lua, |nes| {
+                let cpu = &mut nes.cpu;
+                match name.to_ascii_lowercase().as_str() {
+                    "a" => cpu.a = value as u8,
+                    "x" => cpu.x = value as u8,
+                    "y" => cpu.y = value as u8,
+                    "s" => cpu.s = value as u8,
+                    "p" => cpu.p = value as u8,
+                    "pc" => cpu.pc = value as u16,
+                    _ => return Err(error(format!("unknown register `{name}`"))),
+                }
+                Ok(())
+            })?
+        })?)?;
+
+        // registerexec(addr, [size,] func), nil unregisters
+        let shared = self.shared.clone();
+        let register_exec = lua.create_function(move |lua, (addr, a, b): (i64, Value, Value)| {
+            let (size, f) = match a {
+                Value::Integer(_) | Value::Number(_) => (lua.unpack::<i64>(a)?, b),
+                _ => (1, a),
+            };
+            let start = addr as u16;
+            let end = (addr + size.max(1) - 1).min(0xffff) as u16;
+
+            let mut shared = shared.borrow_mut();
+            shared.exec.retain(|(s, e, _)| (*s, *e) != (start, end));
+
+            if let Some(f) = lua.unpack::<Option<Function>>(f)? {
+                shared.exec.push((start, end, lua.create_registry_value(f)?));
+            }
+
+            Ok(())
+        })?;
+
+        for name in ["registerexec", "registerexecute", "registerrun"] {
+            memory.set(name, register_exec.clone())?;
+        }
+
+        // joypad
+
+        let get = lua.create_function(|lua, port: i64| {
+            let buttons = with_nes(lua, |nes| nes.input.host.buttons.get(port as usize - 1).copied())?
+                .ok_or_else(|| error(format!("no joypad {port}")))?;
+            let table = lua.create_table()?;
+
+            for (name, bit) in BUTTONS {
+                table.set(name, buttons & bit != 0)?;
+            }
+
+            Ok(table)
+        })?;
+
+        joypad.set("get", get.clone())?;
+        joypad.set("read", get)?;
+
+        // true holds a button and false keeps it released on the next frame, nil leaves it alone
+        let shared = self.shared.clone();
+        let set = lua.create_function(move |_, (port, buttons): (i64, Table)| {
+            let mut shared = shared.borrow_mut();
+            let (on, off) = shared.joypad.get_mut((port - 1) as usize).ok_or_else(|| error(format!("no joypad {port}")))?;
+
+            for pair in buttons.pairs::<String, Option<bool>>() {
+                let (name, held) = pair?;
+                let (_, bit) = BUTTONS.iter().find(|(n, _)| n.eq_ignore_ascii_case(&name)).ok_or_else(|| error(format!("unknown button `{name}`")))?;
+
+                match held {
+                    Some(true) => *on |= bit,
+                    Some(false) => *off |= bit,
+                    None => {},
+                }
+            }
+
+            Ok(())
+        })?;
+
+        joypad.set("set", set.clone())?;
+        joypad.set("write", set)?;
+
+        // savestate, slot numbers are accepted but states only live in their objects
+
+        let create = lua.create_function(|lua, _slot: Option<i64>| lua.create_userdata(State(None)))?;
+        savestate.set("create", create.clone())?;
+        savestate.set("object", create)?;
+
+        savestate.set("save", lua.create_function(|lua, state: AnyUserData| {
+            let snap = with_nes(lua, |nes| nes.snapshot())?;
+            state.borrow_mut::<State>()?.0 = Some(snap);
+            Ok(())
+        })?)?;
+
+        savestate.set("load", lua.create_function(|lua, state: AnyUserData| {
+            let snap = state.borrow::<State>()?.0.clone().ok_or_else(|| error("nothing saved in this state"))?;
+            with_nes(lua, |nes| nes.restore(&snap))?.map_err(|_| error("the state is from another cartridge"))
+        })?)?;
+
+        // gui
+
+        let white = [255, 255, 255, 255];
+        let black = [0, 0, 0, 255];
+
+        let shared = self.shared.clone();
+        let pixel = lua.create_function(move |lua, (x, y, color): (f64, f64, Value)| {
+            let color = parse_color(lua, color, white, &shared.borrow().palette)?;
+            shared.borrow_mut().overlay.shapes.push(Shape::Pixel(x as i32, y as i32, color));
+            Ok(())
+        })?;
+
+        let shared = self.shared.clone();
+        let line = lua.create_function(move |lua, (x1, y1, x2, y2, color): (f64, f64, f64, f64, Value)| {
+            let color = parse_color(lua, color, white, &shared.borrow().palette)?;
+            shared.borrow_mut().overlay.shapes.push(Shape::Line(x1 as i32, y1 as i32, x2 as i32, y2 as i32, color));
+            Ok(())
+        })?;
+
+        let shared = self.shared.clone();
+        let rect = lua.create_function(move |lua, (x1, y1, x2, y2, fill, outline): (f64, f64, f64, f64, Value, Value)| {
+            let outline = parse_color(lua, outline, white, &shared.borrow().palette)?;
+            // like FCEUX, the fill defaults to a see through outline color
+            let fill = parse_color(lua, fill, [outline[0], outline[1], outline[2], 0x40], &shared.borrow().palette)?;
+            shared.borrow_mut().overlay.shapes.push(Shape::Box(x1 as i32, y1 as i32, x2 as i32, y2 as i32, fill, outline));
+            Ok(())
+        })?;
+
+        let shared = self.shared.clone();
+        let text = lua.create_function(move |lua, (x, y, text, fg, bg): (f64, f64, Value, Value, Value)| {
+            let text = lua.coerce_string(text)?.map_or("nil".to_string(), |s| s.to_string_lossy().into_owned());
+            let (fg, bg) = {
+                let palette = &shared.borrow().palette;
+                (parse_color(lua, fg, white, palette)?, parse_color(lua, bg, black, palette)?)
+            };
+            shared.borrow_mut().overlay.shapes.push(Shape::Text(x as i32, y as i32, text, fg, bg));
+            Ok(())
+        })?;
+
+        for (names, f) in [
+            (&["pixel", "drawpixel", "setpixel", "writepixel"][..], pixel),
+            (&["line", "drawline"], line),
+            (&["box", "drawbox", "rect", "drawrect"], rect),
+            (&["text", "drawtext"], text),
+        ] {
+            for name in names {
+                gui.set(*name, f.clone())?;
+            }
+        }
+
+        globals.set("emu", emu)?;
+        globals.set("memory", memory)?;
+        globals.set("joypad", joypad)?;
+        globals.set("savestate", savestate)?;
+        globals.set("gui", gui)?;
+
+        // yielding has to happen in Lua, the main chunk is resumed again next frame
+        lua.load("function emu.frameadvance() coroutine.yield() end").exec()
+    }
+}
+
+pub fn is_script(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lua"))
+}
+
+#[cfg(test)]
+mod tests {
+    use nes::{fixture, Region};
+
+    use super::*;
+
+    #[test]
+    fn colors() {
+        let lua = Lua::new();
+        let palette = Palette::default();
+        let color = |src: &str| parse_color(&lua, lua.load(src).eval().unwrap(), [9; 4], &palette).ok();
+
+        assert_eq!(color("nil"), Some([9; 4]));
+        assert_eq!(color("'Red'"), Some([255, 0, 0, 255]));
+        assert_eq!(color("'#123456'"), Some([0x12, 0x34, 0x56, 0xff]));
+        assert_eq!(color("'#12345678'"), Some([0x12, 0x34, 0x56, 0x78]));
+        assert_eq!(color("0x11223344"), Some([0x11, 0x22, 0x33, 0x44]));
+        assert_eq!(color("'P30'"), Some(palette.rgba(0x30)));
+        assert_eq!(color("{r = 1, b = 300}"), Some([1, 0, 255, 255]));
+        assert_eq!(color("{1, 2, 3, 4}"), Some([1, 2, 3, 4]));
+        assert!(color("'#12345'").is_none());
+        assert!(color("'mauve'").is_none());
+        assert!(color("true").is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("rustyness-script-{}.lua", std::process::id()));
+        std::fs::write(&path, "
+            local state = savestate.create()
+            memory.writebyte(0x10, 42)
+            savestate.save(state)
+            joypad.set(1, {A = true, start = false})
+            gui.pixel(1, 2, 'P30')
+            emu.frameadvance()
+
+            memory.writebyte(0x10, memory.readbyte(0x10) + 1)
+            emu.message(memory.readbyte(0x10))
+            emu.frameadvance()
+
+            savestate.load(state)
+            emu.message(memory.readbyte(0x10))
+            emu.exit()
+        ").unwrap();
+        let script = Script::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut script = script.unwrap();
+        let mut nes = fixture::rom_nes(fixture::IDLE, Region::Ntsc);
+        let frame = |script: &mut Script, nes: &mut Nes, buttons: u8| {
+            let mut buttons = [buttons, 0, 0, 0];
+            script.before_frame(nes, &mut buttons).unwrap();
+            script.step_frame(nes).unwrap();
+            script.after_frame(nes).unwrap();
+            buttons[0]
+        };
+
+        assert_eq!(frame(&mut script, &mut nes, joypad::START | joypad::B), joypad::A | joypad::B);
+        assert!(matches!(script.overlay().shapes[..], [Shape::Pixel(1, 2, c)] if c == Palette::default().rgba(0x30)));
+        assert_eq!(nes.iram[0x10], 42);
+
+        assert_eq!(frame(&mut script, &mut nes, joypad::START), joypad::START);
+        assert!(script.overlay().shapes.is_empty());
+        assert_eq!(script.take_messages(), ["43"]);
+
+        frame(&mut script, &mut nes, 0);
+        assert_eq!(script.take_messages(), ["42"]);
+        assert_eq!(nes.iram[0x10], 42);
+        assert!(script.exited());
+    }
+}
@@ -0,0 +1,170 @@
+use nes::{
+    cheat::Cheat,
+    search::{Compare, Location, Ram, Search, View},
+    Nes,
+};
+
+use crate::cheat_ui::CheatWindow;
+
+const COMPARES: [&str; 6] = ["Unchanged", "Changed", "Increased", "Decreased", "Changed by", "Equals"];
+
+/// RAM search and the values it turned up
+#[derive(Default)]
+pub struct SearchWindow {
+    pub open: bool,
+    search: Option<Search>,
+    view: View,
+    compare: usize,
+    /// For changed by and equals
+    n: i32,
+    watches: Vec<(Location, View)>,
+}
+
+fn label(loc: &Location) -> String {
+    match (loc.ram, loc.cpu_addr()) {
+        (_, Some(addr)) => format!("${addr:04X}"),
+        (Ram::Prg, None) => format!("PRG RAM +{:04X}", loc.offset),
+        (Ram::Internal, None) => unreachable!("internal RAM is always mapped"),
+    }
+}
+
+impl SearchWindow {
+    pub fn menu(&mut self, ui: &imgui::Ui) {
+        ui.menu_item_config("RAM search").build_with_ref(&mut self.open);
+    }
+
+    /// Results can be turned into `cheats` unless that's `None`
+    pub fn draw(&mut self, ui: &imgui::Ui, nes: &mut Nes, cheats: Option<&mut CheatWindow>) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = true;
+        ui.window("RAM search").opened(&mut open).build(|| self.window(ui, nes, cheats));
+        self.open = open;
+    }
+
+    fn window(&mut self, ui: &imgui::Ui, nes: &mut Nes, cheats: Option<&mut CheatWindow>) {
+        // the view can only change between searches, candidates hold values read through it
+        let searching = self.search.is_some();
+        let view = &mut self.view;
+
+        ui.disabled(searching, || {
+            ui.checkbox("16 bit", &mut view.word);
+            ui.same_line();
+            ui.checkbox("Signed", &mut view.signed);
+            ui.same_line();
+            ui.disabled(!view.word, || {
+                ui.checkbox("Big endian", &mut view.big_endian);
+            });
+        });
+
+        if ui.button(if searching { "Restart" } else { "Start" }) {
+            self.search = Some(Search::start(nes, self.view));
+        }
+
+        let Some(search) = &mut self.search else {
+            ui.text_disabled("start takes every value as it is now");
+            self.watches_list(ui, nes);
+            return;
+        };
+
+        ui.same_line();
+
+        if ui.button("Stop") {
+            self.search = None;
+            return;
+        }
+
+        ui.combo_simple_string("##compare", &mut self.compare, &COMPARES);
+
+        if self.compare >= 4 {
+            ui.input_int("##n", &mut self.n).build();
+        }
+
+        if ui.button("Filter") {
+            let compare = match self.compare {
+                0 => Compare::Unchanged,
+                1 => Compare::Changed,
+                2 => Compare::Increased,
+                3 => Compare::Decreased,
+                4 => Compare::By(self.n as i64),
+                _ => Compare::Equals(self.n as i64),
+            };
+            search.filter(nes, compare);
+        }
+
+        ui.same_line();
+        ui.text(format!("{} candidates", search.candidates.len()));
+
+        let view = search.view;
+        let mut watch = None;
+        let mut cheat = None;
+
+        ui.child_window("candidates").size([0.0, 200.0]).build(|| {
+            for i in imgui::ListClipper::new(search.candidates.len() as i32).begin(ui).iter() {
+                let (loc, old) = search.candidates[i as usize];
+                let now = loc.read(nes, view).unwrap_or(old);
+                let _id = ui.push_id_usize(i as usize);
+
+                ui.text(format!("{:<14} {old:>6} {now:>6}", label(&loc)));
+                ui.same_line();
+
+                if ui.small_button("Watch") {
+                    watch = Some(loc);
+                }
+
+                if loc.cpu_addr().is_some() && cheats.is_some() {
+                    ui.same_line();
+
+                    if ui.small_button("Cheat") {
+                        cheat = Some((loc, now));
+                    }
+                }
+            }
+        });
+
+        if let Some(loc) = watch {
+            self.watches.push((loc, view));
+        }
+
+        if let (Some((loc, value)), Some(cheats)) = (cheat, cheats) {
+            // holds it at what it is now, the cheat window can then change it
+            for code in loc.cheat_codes(view, value).unwrap_or_default() {
+                cheats.add(Cheat::new(&code, &format!("RAM search {}", label(&loc))).unwrap(), nes);
+            }
+
+            cheats.open = true;
+        }
+
+        self.watches_list(ui, nes);
+    }
+
+    fn watches_list(&mut self, ui: &imgui::Ui, nes: &Nes) {
+        if self.watches.is_empty() {
+            return;
+        }
+
+        ui.separator();
+        ui.text("Watches");
+        let mut removed = None;
+
+        for (i, (loc, view)) in self.watches.iter().enumerate() {
+            let _id = ui.push_id_usize(i);
+            let value = loc.read(nes, *view).map_or("-".to_string(), |v| v.to_string());
+            let size = if view.word { 4 } else { 2 };
+            let hex = loc.read(nes, View { signed: false, ..*view }).map_or(String::new(), |v| format!("{v:0size$X}"));
+
+            ui.text(format!("{:<14} {value:>6} {hex:>4}", label(loc)));
+            ui.same_line();
+
+            if ui.small_button("Remove") {
+                removed = Some(i);
+            }
+        }
+
+        if let Some(i) = removed {
+            self.watches.remove(i);
+        }
+    }
+}
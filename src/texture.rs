@@ -0,0 +1,48 @@
+use glow::HasContext;
+use imgui_glow_renderer::{AutoRenderer, TextureMap};
+
+/// RGBA texture the emulator writes into every frame and imgui draws
+pub struct Texture {
+    tex: glow::Texture,
+    pub id: imgui::TextureId,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Texture {
+    pub fn new(renderer: &mut AutoRenderer, width: usize, height: usize) -> Self {
+        let gl = renderer.gl_context();
+
+        let tex = unsafe {
+            let tex = gl.create_texture().expect("failed to create texture");
+            gl.bind_texture(glow::TEXTURE_2D, Some(tex));
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D, 0, glow::RGBA as i32, width as i32, height as i32, 0,
+                glow::RGBA, glow::UNSIGNED_BYTE, None,
+            );
+            tex
+        };
+
+        let id = renderer.texture_map_mut().register(tex).expect("failed to register texture");
+        Self { tex, id, width, height }
+    }
+
+    /// `rgba` holds `width * height` pixels
+    pub fn update(&self, gl: &glow::Context, rgba: &[u8]) {
+        assert_eq!(rgba.len(), self.width * self.height * 4);
+
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.tex));
+            gl.tex_sub_image_2d(
+                glow::TEXTURE_2D, 0, 0, 0, self.width as i32, self.height as i32,
+                glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelUnpackData::Slice(rgba),
+            );
+        }
+    }
+
+    pub fn delete(self, gl: &glow::Context) {
+        unsafe { gl.delete_texture(self.tex) };
+    }
+}
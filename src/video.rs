@@ -0,0 +1,204 @@
+use std::{io, path::Path};
+
+use crate::{
+    overlay::Overlay,
+    scale::{self, Scaler},
+};
+use nes::{
+    ntsc,
+    palette::{Palette, Params, Preset},
+    ppu, Nes,
+};
+
+/// How frames are turned into RGB, shared by the screen, screenshots and video recordings
+pub struct Video {
+    /// `None` once a `.pal` file is loaded
+    pub preset: Option<Preset>,
+    pub params: Params,
+    pub palette: Palette,
+    /// Signal level filter, `None` shows the palette colors as they are
+    pub ntsc: Option<ntsc::Preset>,
+    pub scaler: Option<Scaler>,
+    /// How much the scanline overlay darkens, 0 is off
+    pub scanlines: f32,
+    pub overscan: ppu::Overscan,
+    /// Script drawings
+    pub overlay: Overlay,
+}
+
+/// An RGBA image ready to show or save
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+    /// Rows per PPU line
+    pub scale: usize,
+}
+
+impl Default for Video {
+    fn default() -> Self {
+        Self {
+            preset: Some(Preset::Ntsc2C02),
+            params: Params::default(),
+            palette: Palette::default(),
+            ntsc: None,
+            scaler: None,
+            scanlines: 0.0,
+            overscan: Default::default(),
+            overlay: Default::default(),
+        }
+    }
+}
+
+impl Video {
+    pub fn render(&self, nes: &Nes) -> Frame {
+        let fb = &nes.ppu.framebuffer;
+
+        let mut frame = match self.ntsc {
+            Some(preset) => Frame {
+                width: ntsc::WIDTH,
+                height: ppu::FRAME_HEIGHT,
+                rgba: ntsc::filter(fb, nes.ppu.burst_phase, preset, &self.palette, &self.params),
+                scale: 1,
+            },
+            None => Frame {
+                width: ppu::FRAME_WIDTH,
+                height: ppu::FRAME_HEIGHT,
+                rgba: self.palette.framebuffer_rgba(fb),
+                scale: 1,
+            },
+        };
+
+        // cropped before scaling so the scalers see the new edges
+        let per_pixel = frame.width / ppu::FRAME_WIDTH;
+        frame.rgba = self.overscan.crop(&frame.rgba, per_pixel * 4);
+        frame.width = self.overscan.width() * per_pixel;
+        frame.height = self.overscan.height();
+        self.overlay.draw(&mut frame.rgba, per_pixel, &self.overscan);
+
+        if let Some(scaler) = self.scaler {
+            frame.rgba = scaler.scale(&frame.rgba, frame.width);
+            frame.scale = scaler.factor();
+            frame.width *= frame.scale;
+            frame.height *= frame.scale;
+        }
+
+        if self.scanlines > 0.0 {
+            // lines need a row to darken between them
+            if frame.scale == 1 {
+                frame.rgba = scale::double(&frame.rgba, frame.width);
+                frame.scale = 2;
+                frame.width *= 2;
+                frame.height *= 2;
+            }
+
+            scale::scanlines(&mut frame.rgba, frame.width, frame.scale, self.scanlines);
+        }
+
+        frame
+    }
+
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.preset = Some(preset);
+        self.palette = Palette::generate(preset, &self.params);
+    }
+
+    pub fn load_pal(&mut self, path: &Path) -> io::Result<()> {
+        let palette = Palette::parse_pal(&std::fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.preset = None;
+        self.palette = palette;
+        Ok(())
+    }
+
+    /// Write the palette with 64 or 512 entries
+    pub fn save_pal(&self, path: &Path, entries: usize) -> io::Result<()> {
+        std::fs::write(path, self.palette.write_pal(entries))
+    }
+
+    pub fn is_pal(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pal"))
+    }
+
+    /// Palette choices and decoder controls, returns a status message for file operations
+    pub fn menu(&mut self, ui: &imgui::Ui, rom: Option<&Path>) -> Option<String> {
+        let mut status = None;
+
+        ui.text("Filter");
+
+        if ui.menu_item_config("None").selected(self.ntsc.is_none()).build() {
+            self.ntsc = None;
+        }
+
+        for preset in ntsc::Preset::ALL {
+            if ui.menu_item_config(preset.name()).selected(self.ntsc == Some(preset)).build() {
+                self.ntsc = Some(preset);
+            }
+        }
+
+        ui.separator();
+        ui.text("Upscaling");
+
+        if ui.menu_item_config("Off").selected(self.scaler.is_none()).build() {
+            self.scaler = None;
+        }
+
+        for scaler in Scaler::ALL {
+            if ui.menu_item_config(scaler.name()).selected(self.scaler == Some(scaler)).build() {
+                self.scaler = Some(scaler);
+            }
+        }
+
+        ui.slider("Scanlines", 0.0, 1.0, &mut self.scanlines);
+
+        ui.separator();
+        ui.text("Palette");
+
+        for preset in Preset::ALL {
+            if ui.menu_item_config(preset.name()).selected(self.preset == Some(preset)).build() {
+                self.set_preset(preset);
+            }
+        }
+
+        if self.preset.is_none() {
+            ui.menu_item_config(".pal file").selected(true).build();
+        } else {
+            ui.text_disabled("drop a .pal file to load it");
+        }
+
+        // the RGB PPUs have no composite signal to decode, the filter always does
+        let decoded = matches!(self.ntsc, Some(preset) if preset != ntsc::Preset::Rgb);
+
+        if decoded || matches!(self.preset, Some(Preset::Ntsc2C02 | Preset::Pal2C07)) {
+            let params = &mut self.params;
+            let mut changed = ui.slider("Hue", -30.0, 30.0, &mut params.hue);
+            changed |= ui.slider("Saturation", 0.0, 2.0, &mut params.saturation);
+            changed |= ui.slider("Contrast", 0.5, 1.5, &mut params.contrast);
+            changed |= ui.slider("Brightness", -0.5, 0.5, &mut params.brightness);
+            changed |= ui.slider("Gamma", 1.0, 3.0, &mut params.gamma);
+
+            if ui.menu_item("Reset decoder") {
+                *params = Params::default();
+                changed = true;
+            }
+
+            if let (true, Some(preset)) = (changed, self.preset) {
+                self.set_preset(preset);
+            }
+        }
+
+        ui.separator();
+
+        let path = rom.map_or_else(|| "palette.pal".into(), |rom| rom.with_extension("pal"));
+
+        for (label, entries) in [("Save .pal", 512), ("Save .pal without emphasis", 64)] {
+            if ui.menu_item(label) {
+                status = Some(match self.save_pal(&path, entries) {
+                    Ok(()) => format!("saved {}", path.display()),
+                    Err(e) => format!("failed to save {}: {e}", path.display()),
+                });
+            }
+        }
+
+        status
+    }
+}